@@ -0,0 +1,517 @@
+use crate::state::*;
+use crate::turn::{dwelling_growth, DWELLINGS};
+use resources::map_buildings::Buildings::{self, *};
+use resources::map_structs::*;
+use std::fmt;
+
+// Town buildings of the original factions: what each faction can build, the
+// cost and the buildings it requires. A town builds once a day. Higher levels
+// of the fort, the hall, the mage guild and the dwellings include the lower ones.
+
+/// Cost in wood, mercury, ore, sulfur, crystal, gems and gold
+const fn res(w: i32, m: i32, o: i32, s: i32, c: i32, g: i32, gold: i32) -> ResourcePack {
+    ResourcePack([w, m, o, s, c, g, gold])
+}
+
+/// Cost in wood, ore, the same amount of each rare resource and gold
+const fn cost(wood: i32, ore: i32, rare: i32, gold: i32) -> ResourcePack {
+    res(wood, rare, ore, rare, rare, rare, gold)
+}
+
+/// A building the faction can build
+#[derive(Debug, Copy, Clone)]
+pub struct BuildingInfo {
+    pub building: Buildings,
+    pub cost: ResourcePack,
+    /// all of them have to be built first
+    pub requires: &'static [Buildings],
+}
+
+const fn b(
+    building: Buildings,
+    cost: ResourcePack,
+    requires: &'static [Buildings],
+) -> BuildingInfo {
+    BuildingInfo {
+        building,
+        cost,
+        requires,
+    }
+}
+
+const COMMON: &[BuildingInfo] = &[
+    b(VillageHall, cost(0, 0, 0, 0), &[]),
+    b(TownHall, cost(0, 0, 0, 2500), &[Tavern]),
+    b(
+        CityHall,
+        cost(0, 0, 0, 5000),
+        &[TownHall, MagesGuild1, Marketplace, Blacksmith],
+    ),
+    b(Capitol, cost(0, 0, 0, 10000), &[CityHall, Castle]),
+    b(Fort, cost(20, 20, 0, 5000), &[]),
+    b(Citadel, cost(0, 5, 0, 2500), &[Fort]),
+    b(Castle, cost(10, 10, 0, 5000), &[Citadel]),
+    b(Tavern, cost(5, 0, 0, 500), &[]),
+    b(Marketplace, cost(5, 0, 0, 500), &[]),
+    b(ResourceSilo, cost(0, 5, 0, 5000), &[Marketplace]),
+    b(Blacksmith, cost(5, 0, 0, 1000), &[]),
+    b(MagesGuild1, cost(5, 5, 0, 2000), &[]),
+    b(MagesGuild2, cost(5, 5, 4, 1000), &[MagesGuild1]),
+    b(MagesGuild3, cost(5, 5, 6, 1000), &[MagesGuild2]),
+];
+
+const MAGES_GUILD_4: BuildingInfo = b(MagesGuild4, cost(5, 5, 8, 1000), &[MagesGuild3]);
+const MAGES_GUILD_5: BuildingInfo = b(MagesGuild5, cost(5, 5, 10, 1000), &[MagesGuild4]);
+const SHIPYARD: BuildingInfo = b(Shipyard, cost(20, 0, 0, 2000), &[]);
+const ARTIFACT_MERCHANT: BuildingInfo = b(ArtifactMerchant, cost(0, 0, 0, 10000), &[Marketplace]);
+
+const CASTLE: &[BuildingInfo] = &[
+    MAGES_GUILD_4,
+    SHIPYARD,
+    // Lighthouse, Stables, Brotherhood of the Sword, Griffin Bastion
+    b(Special1, cost(0, 10, 0, 2000), &[Shipyard]),
+    b(Special2, cost(10, 0, 0, 2000), &[DwellLvl4]),
+    b(Special3, cost(5, 0, 0, 500), &[Tavern]),
+    b(Horde1, cost(0, 5, 0, 1000), &[DwellLvl3]),
+    b(DwellLvl1, cost(0, 10, 0, 500), &[Fort]),
+    b(DwellLvl1Up, cost(0, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2, cost(5, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2Up, cost(5, 5, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3, cost(0, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl3Up, cost(0, 5, 0, 1000), &[DwellLvl3]),
+    b(DwellLvl4, cost(0, 5, 0, 2000), &[DwellLvl1, Blacksmith]),
+    b(DwellLvl4Up, res(0, 0, 5, 0, 5, 0, 2000), &[DwellLvl4]),
+    b(DwellLvl5, cost(5, 5, 2, 3000), &[DwellLvl4, MagesGuild1]),
+    b(DwellLvl5Up, cost(5, 5, 2, 1000), &[DwellLvl5]),
+    b(DwellLvl6, cost(20, 0, 0, 5000), &[DwellLvl4, Special2]),
+    b(DwellLvl6Up, cost(10, 0, 0, 3000), &[DwellLvl6]),
+    b(DwellLvl7, cost(0, 0, 10, 20000), &[DwellLvl5]),
+    b(DwellLvl7Up, cost(0, 0, 10, 20000), &[DwellLvl7]),
+];
+
+const RAMPART: &[BuildingInfo] = &[
+    MAGES_GUILD_4,
+    MAGES_GUILD_5,
+    // Mystic Pond, Fountain of Fortune, Treasury, Miners' Guild, Dendroid Saplings
+    b(Special1, cost(0, 0, 1, 2000), &[]),
+    b(Special2, res(0, 0, 0, 0, 10, 0, 1500), &[Special1]),
+    b(Special3, cost(5, 0, 0, 5000), &[TownHall]),
+    b(Horde1, cost(0, 5, 0, 1000), &[DwellLvl2]),
+    b(Horde2, cost(5, 0, 0, 2000), &[DwellLvl5]),
+    b(DwellLvl1, cost(10, 0, 0, 500), &[Fort]),
+    b(DwellLvl1Up, cost(5, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2, cost(5, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2Up, cost(5, 0, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3, cost(10, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl3Up, cost(10, 0, 0, 1000), &[DwellLvl3]),
+    b(DwellLvl4, res(0, 0, 0, 0, 10, 0, 2000), &[DwellLvl3]),
+    b(DwellLvl4Up, res(0, 0, 0, 0, 5, 0, 2000), &[DwellLvl4]),
+    b(DwellLvl5, cost(0, 0, 0, 2500), &[DwellLvl3]),
+    b(DwellLvl5Up, cost(5, 0, 0, 1500), &[DwellLvl5]),
+    b(
+        DwellLvl6,
+        res(5, 0, 0, 0, 0, 10, 4000),
+        &[DwellLvl4, DwellLvl5],
+    ),
+    b(DwellLvl6Up, res(5, 0, 0, 0, 0, 5, 3000), &[DwellLvl6]),
+    b(
+        DwellLvl7,
+        res(0, 0, 30, 0, 20, 0, 10000),
+        &[DwellLvl6, MagesGuild2],
+    ),
+    b(
+        DwellLvl7Up,
+        res(0, 0, 30, 0, 20, 0, 20000),
+        &[DwellLvl7, MagesGuild3],
+    ),
+];
+
+const TOWER: &[BuildingInfo] = &[
+    MAGES_GUILD_4,
+    MAGES_GUILD_5,
+    ARTIFACT_MERCHANT,
+    // Lookout Tower, Library, Wall of Knowledge, Sculptor's Wings
+    b(Special2, cost(5, 0, 0, 1000), &[Fort]),
+    b(Special3, cost(5, 5, 5, 1500), &[MagesGuild1]),
+    b(Special4, cost(0, 5, 0, 1000), &[MagesGuild1]),
+    b(Horde1, cost(0, 0, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl1, cost(5, 5, 0, 300), &[Fort]),
+    b(DwellLvl1Up, cost(5, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2, cost(0, 10, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2Up, cost(0, 5, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3, cost(5, 5, 0, 2000), &[DwellLvl1]),
+    b(DwellLvl3Up, res(0, 5, 5, 0, 0, 0, 2000), &[DwellLvl3]),
+    b(DwellLvl4, cost(5, 5, 2, 2500), &[DwellLvl3, MagesGuild1]),
+    b(DwellLvl4Up, cost(5, 5, 2, 2000), &[DwellLvl4]),
+    b(DwellLvl5, res(5, 0, 5, 0, 6, 6, 3000), &[DwellLvl4]),
+    b(DwellLvl5Up, cost(5, 5, 0, 2000), &[DwellLvl5]),
+    b(DwellLvl6, res(5, 0, 5, 0, 0, 10, 4000), &[DwellLvl4]),
+    b(DwellLvl6Up, res(5, 0, 5, 0, 0, 10, 4000), &[DwellLvl6]),
+    b(DwellLvl7, cost(5, 5, 10, 5000), &[DwellLvl6]),
+    b(DwellLvl7Up, res(5, 0, 5, 0, 0, 30, 25000), &[DwellLvl7]),
+];
+
+const INFERNO: &[BuildingInfo] = &[
+    MAGES_GUILD_4,
+    MAGES_GUILD_5,
+    // Brimstone Stormclouds, Order of Fire, Birthing Pools, Cages
+    b(Special1, res(0, 5, 0, 0, 0, 0, 1000), &[MagesGuild1]),
+    b(CastleGate, cost(5, 5, 0, 10000), &[Citadel]),
+    b(Special3, cost(5, 5, 0, 1000), &[MagesGuild1]),
+    b(Horde1, cost(0, 0, 0, 1000), &[DwellLvl1]),
+    b(Horde2, cost(0, 0, 0, 1000), &[DwellLvl3]),
+    b(DwellLvl1, cost(0, 5, 0, 300), &[Fort]),
+    b(DwellLvl1Up, cost(0, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2, cost(5, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2Up, res(0, 5, 5, 0, 0, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3, cost(0, 10, 0, 1500), &[DwellLvl2]),
+    b(DwellLvl3Up, res(0, 0, 0, 5, 0, 0, 1000), &[DwellLvl3]),
+    b(DwellLvl4, cost(5, 5, 0, 2000), &[DwellLvl2]),
+    b(DwellLvl4Up, res(0, 5, 0, 0, 0, 0, 2000), &[DwellLvl4]),
+    b(
+        DwellLvl5,
+        res(0, 10, 0, 0, 0, 0, 3000),
+        &[DwellLvl4, MagesGuild1],
+    ),
+    b(DwellLvl5Up, res(0, 5, 0, 0, 0, 0, 3000), &[DwellLvl5]),
+    b(DwellLvl6, res(0, 0, 0, 10, 0, 0, 4000), &[DwellLvl4]),
+    b(DwellLvl6Up, res(0, 5, 0, 0, 0, 0, 3000), &[DwellLvl6]),
+    b(
+        DwellLvl7,
+        res(0, 10, 10, 0, 0, 0, 15000),
+        &[DwellLvl5, DwellLvl6],
+    ),
+    b(DwellLvl7Up, res(0, 20, 0, 0, 0, 0, 20000), &[DwellLvl7]),
+];
+
+const NECROPOLIS: &[BuildingInfo] = &[
+    MAGES_GUILD_4,
+    MAGES_GUILD_5,
+    SHIPYARD,
+    // Veil of Darkness, Necromancy Amplifier, Skeleton Transformer, Unearthed Graves
+    b(Special1, cost(5, 0, 0, 1000), &[]),
+    b(Special2, cost(0, 0, 0, 1000), &[MagesGuild1]),
+    b(Special3, cost(0, 0, 0, 1000), &[DwellLvl1]),
+    b(Horde1, cost(0, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl1, cost(5, 5, 0, 400), &[Fort]),
+    b(DwellLvl1Up, cost(5, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2, cost(0, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2Up, cost(5, 5, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3, cost(5, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl3Up, res(0, 5, 0, 0, 0, 0, 1000), &[DwellLvl3]),
+    b(DwellLvl4, cost(5, 0, 0, 1500), &[DwellLvl1]),
+    b(DwellLvl4Up, res(5, 0, 0, 0, 5, 0, 1500), &[DwellLvl4]),
+    b(
+        DwellLvl5,
+        res(10, 0, 10, 10, 0, 0, 3000),
+        &[DwellLvl2, MagesGuild1],
+    ),
+    b(DwellLvl5Up, res(5, 0, 5, 5, 0, 0, 4000), &[DwellLvl5]),
+    b(DwellLvl6, cost(10, 10, 0, 6000), &[DwellLvl5]),
+    b(DwellLvl6Up, res(5, 0, 5, 0, 5, 0, 3000), &[DwellLvl6]),
+    b(DwellLvl7, cost(5, 5, 5, 10000), &[DwellLvl6]),
+    b(
+        DwellLvl7Up,
+        res(5, 20, 5, 0, 0, 0, 15000),
+        &[DwellLvl7, MagesGuild2],
+    ),
+];
+
+const DUNGEON: &[BuildingInfo] = &[
+    MAGES_GUILD_4,
+    MAGES_GUILD_5,
+    ARTIFACT_MERCHANT,
+    // Mana Vortex, Portal of Summoning, Battle Scholar Academy, Mushroom Rings
+    b(Special2, cost(0, 0, 0, 1000), &[MagesGuild1]),
+    b(Special3, cost(0, 5, 0, 2500), &[DwellLvl7]),
+    b(Special4, cost(5, 5, 0, 1000), &[MagesGuild1]),
+    b(Horde1, cost(0, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl1, cost(0, 10, 0, 400), &[Fort]),
+    b(DwellLvl1Up, cost(0, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2, cost(0, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2Up, res(0, 0, 0, 2, 2, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3, cost(0, 0, 1, 1000), &[DwellLvl1]),
+    b(DwellLvl3Up, cost(5, 5, 0, 1000), &[DwellLvl3]),
+    b(DwellLvl4, cost(5, 10, 0, 2000), &[DwellLvl1]),
+    b(DwellLvl4Up, cost(5, 5, 5, 2000), &[DwellLvl4]),
+    b(DwellLvl5, res(0, 0, 10, 0, 0, 10, 4000), &[DwellLvl4]),
+    b(DwellLvl5Up, res(0, 0, 5, 0, 0, 5, 3000), &[DwellLvl5]),
+    b(DwellLvl6, res(5, 0, 5, 5, 0, 0, 5000), &[DwellLvl5]),
+    b(DwellLvl6Up, res(5, 0, 0, 5, 0, 0, 3000), &[DwellLvl6]),
+    b(
+        DwellLvl7,
+        res(0, 0, 15, 20, 0, 0, 15000),
+        &[DwellLvl6, MagesGuild3],
+    ),
+    b(
+        DwellLvl7Up,
+        res(0, 0, 20, 20, 0, 0, 15000),
+        &[DwellLvl7, MagesGuild4],
+    ),
+];
+
+const STRONGHOLD: &[BuildingInfo] = &[
+    // Escape Tunnel, Ballista Yard, Hall of Valhalla, Mess Hall
+    b(Special1, cost(5, 5, 0, 2000), &[Fort]),
+    b(FreelancersGuild, cost(5, 0, 0, 1000), &[Marketplace]),
+    b(Special3, cost(5, 0, 0, 1000), &[Blacksmith]),
+    b(Special4, cost(0, 0, 0, 1000), &[Fort]),
+    b(Horde1, cost(0, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl1, cost(5, 5, 0, 200), &[Fort]),
+    b(DwellLvl1Up, cost(5, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2, cost(10, 5, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2Up, cost(5, 5, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3, cost(5, 5, 0, 1000), &[DwellLvl1, Blacksmith]),
+    b(DwellLvl3Up, cost(0, 0, 2, 1000), &[DwellLvl3]),
+    b(DwellLvl4, cost(20, 0, 0, 2000), &[DwellLvl1]),
+    b(
+        DwellLvl4Up,
+        res(0, 0, 0, 0, 5, 0, 2000),
+        &[DwellLvl4, MagesGuild1],
+    ),
+    b(DwellLvl5, cost(0, 10, 0, 2500), &[DwellLvl2]),
+    b(DwellLvl5Up, cost(0, 5, 0, 5000), &[DwellLvl5]),
+    b(DwellLvl6, res(0, 0, 20, 0, 5, 0, 3500), &[DwellLvl4]),
+    b(DwellLvl6Up, res(0, 0, 0, 0, 5, 0, 3000), &[DwellLvl6]),
+    b(DwellLvl7, res(10, 0, 10, 0, 10, 0, 10000), &[DwellLvl6]),
+    b(DwellLvl7Up, res(10, 0, 5, 0, 10, 0, 15000), &[DwellLvl7]),
+];
+
+const FORTRESS: &[BuildingInfo] = &[
+    SHIPYARD,
+    // Cage of Warlords, Glyphs of Fear, Blood Obelisk, Captain's Quarters
+    b(Special1, cost(0, 0, 0, 1000), &[Fort]),
+    b(Special2, cost(0, 0, 0, 1000), &[Fort]),
+    b(Special3, cost(0, 0, 0, 1000), &[Special2]),
+    b(Horde1, cost(0, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl1, cost(10, 0, 0, 400), &[Fort]),
+    b(DwellLvl1Up, cost(10, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2, cost(10, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl2Up, cost(5, 0, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3, cost(5, 5, 0, 1000), &[DwellLvl2]),
+    b(DwellLvl3Up, cost(0, 0, 2, 1000), &[DwellLvl3]),
+    b(DwellLvl4, cost(10, 10, 0, 2000), &[DwellLvl3]),
+    b(DwellLvl4Up, cost(5, 5, 0, 2000), &[DwellLvl4]),
+    b(DwellLvl5, cost(0, 10, 0, 2500), &[DwellLvl2]),
+    b(DwellLvl5Up, cost(0, 5, 0, 2000), &[DwellLvl5]),
+    b(DwellLvl6, cost(15, 0, 0, 3500), &[DwellLvl3]),
+    b(DwellLvl6Up, res(5, 10, 0, 0, 0, 0, 3500), &[DwellLvl6]),
+    b(DwellLvl7, res(10, 0, 0, 10, 0, 0, 10000), &[DwellLvl6]),
+    b(DwellLvl7Up, res(0, 0, 0, 10, 0, 0, 10000), &[DwellLvl7]),
+];
+
+const CONFLUX: &[BuildingInfo] = &[
+    MAGES_GUILD_4,
+    MAGES_GUILD_5,
+    SHIPYARD,
+    ARTIFACT_MERCHANT,
+    // Magic University, Garden of Life
+    b(Special2, cost(0, 0, 10, 5000), &[MagesGuild1]),
+    b(Horde1, cost(0, 0, 0, 1000), &[DwellLvl1]),
+    b(DwellLvl1, cost(5, 0, 0, 1000), &[Fort]),
+    b(DwellLvl1Up, cost(0, 0, 0, 2000), &[DwellLvl1]),
+    b(DwellLvl2, cost(5, 0, 0, 1500), &[DwellLvl1, MagesGuild1]),
+    b(DwellLvl2Up, cost(0, 0, 0, 1500), &[DwellLvl2]),
+    b(DwellLvl3, cost(0, 0, 0, 1500), &[DwellLvl1]),
+    b(DwellLvl3Up, cost(0, 0, 0, 1500), &[DwellLvl3]),
+    b(DwellLvl4, cost(0, 0, 0, 1500), &[DwellLvl1]),
+    b(DwellLvl4Up, cost(0, 0, 0, 1500), &[DwellLvl4]),
+    b(DwellLvl5, cost(0, 0, 0, 1500), &[DwellLvl1]),
+    b(DwellLvl5Up, cost(0, 0, 0, 1500), &[DwellLvl5]),
+    b(
+        DwellLvl6,
+        cost(0, 0, 5, 3000),
+        &[DwellLvl2, DwellLvl3, DwellLvl4, DwellLvl5],
+    ),
+    b(DwellLvl6Up, cost(0, 0, 5, 3000), &[DwellLvl6]),
+    b(DwellLvl7, cost(10, 10, 0, 10000), &[DwellLvl6]),
+    b(DwellLvl7Up, cost(0, 0, 20, 20000), &[DwellLvl7]),
+];
+
+fn faction_table(town: Town) -> &'static [BuildingInfo] {
+    match town {
+        Town::Castle => CASTLE,
+        Town::Rampart => RAMPART,
+        Town::Tower => TOWER,
+        Town::Inferno => INFERNO,
+        Town::Necropolis => NECROPOLIS,
+        Town::Dungeon => DUNGEON,
+        Town::Stronghold => STRONGHOLD,
+        Town::Fortress => FORTRESS,
+        Town::Conflux => CONFLUX,
+        // no data for the factions added after Shadow of Death
+        _ => &[],
+    }
+}
+
+/// Everything the faction can build
+pub fn faction_buildings(town: Town) -> Vec<BuildingInfo> {
+    let table = faction_table(town);
+    if table.is_empty() {
+        return Vec::new();
+    }
+    COMMON.iter().chain(table).copied().collect()
+}
+
+pub fn building_info(town: Town, building: Buildings) -> Option<BuildingInfo> {
+    faction_buildings(town)
+        .into_iter()
+        .find(|i| i.building == building)
+}
+
+/// Dwelling levels 0-6 the two horde buildings of the faction add creatures to, with the bonus
+fn hordes(town: Town) -> [Option<(usize, u32)>; 2] {
+    match town {
+        Town::Castle => [Some((2, 3)), None],
+        Town::Rampart => [Some((1, 4)), Some((4, 2))],
+        Town::Tower => [Some((1, 4)), None],
+        Town::Inferno => [Some((0, 8)), Some((2, 3))],
+        Town::Necropolis => [Some((0, 6)), None],
+        Town::Dungeon => [Some((0, 7)), None],
+        Town::Stronghold => [Some((0, 8)), None],
+        Town::Fortress => [Some((0, 6)), None],
+        Town::Conflux => [Some((0, 10)), None],
+        _ => [None, None],
+    }
+}
+
+/// Weekly bonus of the horde buildings to the dwelling level 0-6 of the town
+pub(crate) fn horde_growth(town: &TownState, level: usize) -> u32 {
+    let Some(faction) = town.faction else {
+        return 0;
+    };
+    let built = [
+        [Horde1, Horde1Upgr]
+            .iter()
+            .any(|b| town.buildings.contains(b)),
+        [Horde2, Horde2Upgr]
+            .iter()
+            .any(|b| town.buildings.contains(b)),
+    ];
+    hordes(faction)
+        .iter()
+        .zip(built)
+        .filter_map(|(horde, built)| horde.filter(|(l, _)| built && *l == level))
+        .map(|(_, bonus)| bonus)
+        .sum()
+}
+
+/// Buildings a higher level includes, from the top levels down so one pass is enough
+const INCLUDED: [(Buildings, Buildings); 16] = [
+    (Castle, Citadel),
+    (Citadel, Fort),
+    (Capitol, CityHall),
+    (CityHall, TownHall),
+    (TownHall, VillageHall),
+    (MagesGuild5, MagesGuild4),
+    (MagesGuild4, MagesGuild3),
+    (MagesGuild3, MagesGuild2),
+    (MagesGuild2, MagesGuild1),
+    (DwellLvl1Up, DwellLvl1),
+    (DwellLvl2Up, DwellLvl2),
+    (DwellLvl3Up, DwellLvl3),
+    (DwellLvl4Up, DwellLvl4),
+    (DwellLvl5Up, DwellLvl5),
+    (DwellLvl6Up, DwellLvl6),
+    (DwellLvl7Up, DwellLvl7),
+];
+
+/// Add the lower levels of the built upgrades
+pub fn add_included(buildings: &mut Vec<Buildings>) {
+    for (higher, lower) in INCLUDED {
+        if buildings.contains(&higher) && !buildings.contains(&lower) {
+            buildings.push(lower);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    UnknownTown,
+    /// neutral towns don't build
+    NoOwner,
+    /// the faction has no such building
+    NotAvailable,
+    AlreadyBuilt,
+    /// the map forbids it
+    Forbidden,
+    BuiltToday,
+    Requires(Buildings),
+    /// a player has one Capitol
+    CapitolElsewhere,
+    NotEnoughResources,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownTown => write!(f, "no such town"),
+            Self::NoOwner => write!(f, "the town has no owner"),
+            Self::NotAvailable => write!(f, "the town can't have this building"),
+            Self::AlreadyBuilt => write!(f, "already built"),
+            Self::Forbidden => write!(f, "forbidden on this map"),
+            Self::BuiltToday => write!(f, "the town has already built today"),
+            Self::Requires(b) => write!(f, "requires {b:?}"),
+            Self::CapitolElsewhere => write!(f, "the player already has a Capitol"),
+            Self::NotEnoughResources => write!(f, "not enough resources"),
+        }
+    }
+}
+
+impl GameState {
+    /// What building costs in the town, or why it can't be built today
+    pub fn can_build(
+        &self,
+        town: ObjectId,
+        building: Buildings,
+    ) -> Result<BuildingInfo, BuildError> {
+        let t = self.towns.get(&town).ok_or(BuildError::UnknownTown)?;
+        let owner = t.owner.ok_or(BuildError::NoOwner)?;
+        if t.buildings.contains(&building) {
+            return Err(BuildError::AlreadyBuilt);
+        }
+        if t.forbidden.contains(&building) {
+            return Err(BuildError::Forbidden);
+        }
+        let info = t
+            .faction
+            .and_then(|f| building_info(f, building))
+            .ok_or(BuildError::NotAvailable)?;
+        if t.built_today {
+            return Err(BuildError::BuiltToday);
+        }
+        if let Some(r) = info.requires.iter().find(|r| !t.buildings.contains(r)) {
+            return Err(BuildError::Requires(*r));
+        }
+        if building == Capitol
+            && self
+                .towns
+                .values()
+                .any(|o| o.owner == Some(owner) && o.buildings.contains(&Capitol))
+        {
+            return Err(BuildError::CapitolElsewhere);
+        }
+        if !self.player(owner).is_some_and(|p| p.can_afford(&info.cost)) {
+            return Err(BuildError::NotEnoughResources);
+        }
+        Ok(info)
+    }
+
+    /// Build in the town, paid by its owner. A new dwelling has the creatures
+    /// of a week to recruit right away.
+    pub fn build(&mut self, town: ObjectId, building: Buildings) -> Result<(), BuildError> {
+        let info = self.can_build(town, building)?;
+        let t = self.towns.get_mut(&town).ok_or(BuildError::UnknownTown)?;
+        t.buildings.push(building);
+        add_included(&mut t.buildings);
+        t.built_today = true;
+        if let Some(level) = DWELLINGS.iter().position(|(basic, _)| *basic == building) {
+            t.available[level] += dwelling_growth(t, level);
+        }
+        let owner = t.owner;
+        if let Some(p) = owner.and_then(|o| self.player_mut(o)) {
+            p.pay(&info.cost);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1211 @@
+use crate::creatures::{self, CreatureStats};
+use crate::damage::{self, Estimate, Strike};
+use crate::rng::Rng;
+use crate::spells::{self, ActiveSpell, SpellInfo, SpellKind};
+use resources::map_structs::*;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+
+// Headless tactical battles, following the original game:
+// - a field of 15x11 hexes with odd rows shifted half a hex to the right, the
+//   attacker deploys in the first column and the defender in the last one,
+// - every round the stacks act by speed, fastest first and the attacker first
+//   on ties; stacks that wait act after the others, slowest first,
+// - a melee attack gets one retaliation per round, shooters can't shoot with
+//   an enemy next to them and do half damage far away, through obstacles or
+//   in melee,
+// - the hero of each side may cast one spell a round, on the turn of any of
+//   its stacks and without taking that turn,
+// - the battle ends when a side has no creatures left or flees.
+// Creatures take one hex and walk around obstacles and other stacks.
+
+pub const FIELD_WIDTH: i8 = 15;
+pub const FIELD_HEIGHT: i8 = 11;
+/// shots at targets further away do half damage
+pub const SHOOTING_RANGE: u32 = 10;
+const MAX_OBSTACLES: u32 = 6;
+
+/// A hex of the battlefield, column x and row y
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hex {
+    pub x: i8,
+    pub y: i8,
+}
+
+impl Hex {
+    pub fn new(x: i8, y: i8) -> Self {
+        Self { x, y }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (0..FIELD_WIDTH).contains(&self.x) && (0..FIELD_HEIGHT).contains(&self.y)
+    }
+
+    // axial coordinates
+    fn axial(self) -> (i32, i32) {
+        let (x, y) = (self.x as i32, self.y as i32);
+        (x - (y - (y & 1)) / 2, y)
+    }
+
+    fn from_axial(q: i32, r: i32) -> Self {
+        Self::new((q + (r - (r & 1)) / 2) as i8, r as i8)
+    }
+
+    pub fn distance(self, other: Hex) -> u32 {
+        let ((q1, r1), (q2, r2)) = (self.axial(), other.axial());
+        let (dq, dr) = (q1 - q2, r1 - r2);
+        ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as u32
+    }
+
+    /// Neighbours on the battlefield
+    pub fn neighbours(self) -> Vec<Hex> {
+        let (q, r) = self.axial();
+        [(1, 0), (-1, 0), (0, -1), (1, -1), (0, 1), (-1, 1)]
+            .iter()
+            .map(|(dq, dr)| Hex::from_axial(q + dq, r + dr))
+            .filter(|h| h.is_valid())
+            .collect()
+    }
+
+    /// Hexes a straight line to the other hex crosses, without both ends
+    fn line_to(self, other: Hex) -> Vec<Hex> {
+        let n = self.distance(other);
+        let ((q1, r1), (q2, r2)) = (self.axial(), other.axial());
+        // nudged off the hex borders so the line picks a side consistently
+        let (q1, r1) = (q1 as f64 + 1e-6, r1 as f64 + 1e-6);
+        let (q2, r2) = (q2 as f64 + 1e-6, r2 as f64 + 1e-6);
+        (1..n)
+            .map(|i| {
+                let t = i as f64 / n as f64;
+                let q = q1 + (q2 - q1) * t;
+                let r = r1 + (r2 - r1) * t;
+                let s = -q - r;
+                let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+                let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+                if dq > dr && dq > ds {
+                    rq = -rr - rs;
+                } else if dr > ds {
+                    rr = -rq - rs;
+                }
+                Hex::from_axial(rq as i32, rr as i32)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Side {
+    Attacker,
+    Defender,
+}
+
+impl Side {
+    pub fn opponent(self) -> Side {
+        match self {
+            Side::Attacker => Side::Defender,
+            Side::Defender => Side::Attacker,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Side::Attacker => 0,
+            Side::Defender => 1,
+        }
+    }
+}
+
+/// The hero leading an army into the battle
+#[derive(Debug, Clone, Default)]
+pub struct BattleHero {
+    pub attack: u32,
+    pub defense: u32,
+    pub power: u32,
+    pub mana: u32,
+    pub skills: Vec<SecSkill>,
+    pub spells: Vec<SpellId>,
+}
+
+impl BattleHero {
+    fn skill(&self, skill: SecSkillId) -> Option<SecSkillLevel> {
+        spells::skill_level(&self.skills, skill)
+    }
+}
+
+/// An army entering the battle
+#[derive(Debug, Clone, Default)]
+pub struct Army {
+    pub slots: Vec<CreatureSlot>,
+    pub formation: ArmyFormation,
+    pub hero: Option<BattleHero>,
+}
+
+/// Index of a stack in the battle
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StackId(pub u8);
+
+#[derive(Debug, Clone)]
+pub struct Stack {
+    pub id: StackId,
+    pub side: Side,
+    /// slot of the army the stack comes from
+    pub slot: u8,
+    pub creature: CreatureId,
+    pub stats: CreatureStats,
+    pub count: u32,
+    /// health left of the top creature
+    pub top_health: u32,
+    pub position: Hex,
+    pub shots: u32,
+    pub retaliations: u32,
+    /// defends until its next turn
+    pub defending: bool,
+    /// buffs and debuffs on the stack
+    pub spells: Vec<ActiveSpell>,
+    /// summoned by a spell, gone after the battle
+    pub summoned: bool,
+}
+
+impl Stack {
+    pub fn alive(&self) -> bool {
+        self.count > 0
+    }
+
+    pub fn total_health(&self) -> u32 {
+        if self.count == 0 {
+            return 0;
+        }
+        (self.count - 1) * self.stats.health + self.top_health
+    }
+
+    /// Attack with the spells on the stack
+    pub fn attack(&self, ranged: bool) -> u32 {
+        (self.stats.attack as i32 + spells::attack_bonus(&self.spells, ranged)).max(0) as u32
+    }
+
+    /// Defense with the spells on the stack and the bonus of the defend
+    /// action, a fifth of it and at least 1
+    pub fn defense(&self) -> u32 {
+        let defense =
+            (self.stats.defense as i32 + spells::defense_bonus(&self.spells)).max(0) as u32;
+        if self.defending {
+            defense + (defense / 5).max(1)
+        } else {
+            defense
+        }
+    }
+
+    /// Speed with the spells on the stack
+    pub fn speed(&self) -> u32 {
+        spells::speed(&self.spells, self.stats.speed)
+    }
+
+    // creatures killed by the damage
+    fn take_damage(&mut self, damage: u32) -> u32 {
+        let before = self.count;
+        let left = self.total_health().saturating_sub(damage);
+        let health = self.stats.health.max(1);
+        self.count = left.div_ceil(health);
+        self.top_health = match left % health {
+            0 => health,
+            h => h,
+        };
+        before - self.count
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Command {
+    Move(Hex),
+    /// walk next to the target, if not there yet, and hit it
+    Attack {
+        target: StackId,
+        from: Hex,
+    },
+    Shoot(StackId),
+    /// act again after the stacks that didn't wait
+    Wait,
+    Defend,
+    /// the side of the active stack leaves the battle
+    Flee,
+    /// the hero of the active stack's side casts the spell on the stack or
+    /// area of the target hex, spells hitting everyone ignore the hex
+    Cast {
+        spell: SpellId,
+        target: Hex,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StrikeKind {
+    Melee,
+    Ranged,
+    Retaliation,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// the side has beaten all enemy stacks
+    Victory(Side),
+    /// the side fled
+    Fled(Side),
+}
+
+impl Outcome {
+    pub fn winner(&self) -> Side {
+        match *self {
+            Outcome::Victory(side) => side,
+            Outcome::Fled(side) => side.opponent(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BattleEvent {
+    NewRound(u32),
+    Moved {
+        stack: StackId,
+        from: Hex,
+        to: Hex,
+    },
+    Struck {
+        attacker: StackId,
+        target: StackId,
+        kind: StrikeKind,
+        damage: u32,
+        killed: u32,
+    },
+    Waited(StackId),
+    Defended(StackId),
+    SpellCast {
+        side: Side,
+        spell: SpellId,
+    },
+    SpellDamage {
+        spell: SpellId,
+        target: StackId,
+        damage: u32,
+        killed: u32,
+    },
+    SpellApplied {
+        spell: SpellId,
+        target: StackId,
+    },
+    Resisted {
+        spell: SpellId,
+        target: StackId,
+    },
+    Summoned(StackId),
+    Finished(Outcome),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Finished,
+    Unreachable,
+    /// not a living enemy stack, or not next to the attack hex
+    InvalidTarget,
+    CantShoot,
+    AlreadyWaited,
+    NoHero,
+    /// the hero has already cast a spell this round
+    AlreadyCast,
+    /// the hero doesn't know the spell, or the map bans it
+    UnknownSpell,
+    NotEnoughMana,
+    /// no stack the spell can hit at the target, or no room for a summon
+    InvalidSpellTarget,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Finished => write!(f, "the battle is over"),
+            Self::Unreachable => write!(f, "the stack can't get there"),
+            Self::InvalidTarget => write!(f, "the stack can't attack this target"),
+            Self::CantShoot => write!(f, "the stack can't shoot"),
+            Self::AlreadyWaited => write!(f, "the stack has already waited this round"),
+            Self::NoHero => write!(f, "no hero leads the army"),
+            Self::AlreadyCast => write!(f, "the hero has already cast a spell this round"),
+            Self::UnknownSpell => write!(f, "the hero can't cast this spell"),
+            Self::NotEnoughMana => write!(f, "not enough mana"),
+            Self::InvalidSpellTarget => write!(f, "the spell can't be cast there"),
+        }
+    }
+}
+
+/// Rows of the stacks of an army by its number of stacks
+fn formation_rows(formation: ArmyFormation, stacks: usize) -> &'static [i8] {
+    const LOOSE: [&[i8]; 8] = [
+        &[],
+        &[5],
+        &[2, 8],
+        &[2, 5, 8],
+        &[0, 4, 6, 10],
+        &[0, 2, 5, 8, 10],
+        &[0, 2, 4, 6, 8, 10],
+        &[0, 2, 4, 5, 6, 8, 10],
+    ];
+    const TIGHT: [&[i8]; 8] = [
+        &[],
+        &[5],
+        &[4, 6],
+        &[4, 5, 6],
+        &[3, 4, 6, 7],
+        &[3, 4, 5, 6, 7],
+        &[2, 3, 4, 6, 7, 8],
+        &[2, 3, 4, 5, 6, 7, 8],
+    ];
+    let table = match formation {
+        ArmyFormation::Loose => &LOOSE,
+        ArmyFormation::Tight => &TIGHT,
+    };
+    table[stacks.min(7)]
+}
+
+#[derive(Debug, Clone)]
+pub struct Battle {
+    stacks: Vec<Stack>,
+    obstacles: BTreeSet<Hex>,
+    round: u32,
+    /// stacks still to act this round, the active one first
+    queue: VecDeque<StackId>,
+    waited: Vec<StackId>,
+    waiting_phase: bool,
+    outcome: Option<Outcome>,
+    rng: Rng,
+    heroes: [Option<BattleHero>; 2],
+    cast_this_round: [bool; 2],
+    /// spells cast by each side, for Eagle Eye
+    cast: [Vec<SpellId>; 2],
+    /// spells of the map, None when it bans none
+    map_spells: Option<AllowedSpells>,
+}
+
+impl Battle {
+    /// Deploy the armies and begin the first round. Slots with unknown
+    /// creatures and stacks beyond the seventh stay out of the battle.
+    pub fn new(attacker: &Army, defender: &Army, seed: u64) -> Self {
+        let mut battle = Battle {
+            stacks: Vec::new(),
+            obstacles: BTreeSet::new(),
+            round: 0,
+            queue: VecDeque::new(),
+            waited: Vec::new(),
+            waiting_phase: false,
+            outcome: None,
+            rng: Rng::new(seed),
+            heroes: [attacker.hero.clone(), defender.hero.clone()],
+            cast_this_round: [false; 2],
+            cast: [Vec::new(), Vec::new()],
+            map_spells: None,
+        };
+        for (side, army) in [(Side::Attacker, attacker), (Side::Defender, defender)] {
+            battle.deploy(side, army);
+        }
+        // obstacles stay clear of the deployment columns
+        let count = battle.rng.range(0, MAX_OBSTACLES);
+        for _ in 0..count {
+            let x = battle.rng.range(2, FIELD_WIDTH as u32 - 3) as i8;
+            let y = battle.rng.range(0, FIELD_HEIGHT as u32 - 1) as i8;
+            battle.obstacles.insert(Hex::new(x, y));
+        }
+        battle.check_outcome();
+        battle.start_round();
+        battle
+    }
+
+    /// Keep the heroes from casting the spells the map bans
+    pub fn with_map_spells(mut self, allowed: &AllowedSpells) -> Self {
+        self.map_spells = Some(allowed.clone());
+        self
+    }
+
+    fn map_allows(&self, spell: SpellId) -> bool {
+        self.map_spells
+            .as_ref()
+            .is_none_or(|a| spells::map_allows(a, spell))
+    }
+
+    fn deploy(&mut self, side: Side, army: &Army) {
+        let mut slots = army
+            .slots
+            .iter()
+            .filter(|s| s.amount > 0)
+            .filter_map(|s| {
+                let c = s.creature?;
+                creatures::creature_stats(c).map(|stats| (s, c, stats))
+            })
+            .collect::<Vec<_>>();
+        slots.sort_by_key(|(s, _, _)| s.slot_num);
+        slots.truncate(7);
+        let rows = formation_rows(army.formation, slots.len());
+        let x = match side {
+            Side::Attacker => 0,
+            Side::Defender => FIELD_WIDTH - 1,
+        };
+        for ((s, creature, stats), y) in slots.into_iter().zip(rows) {
+            self.stacks.push(Stack {
+                id: StackId(self.stacks.len() as u8),
+                side,
+                slot: s.slot_num,
+                creature,
+                stats,
+                count: s.amount,
+                top_health: stats.health,
+                position: Hex::new(x, *y),
+                shots: stats.shots,
+                retaliations: 1,
+                defending: false,
+                spells: Vec::new(),
+                summoned: false,
+            });
+        }
+    }
+
+    pub fn stacks(&self) -> &[Stack] {
+        &self.stacks
+    }
+
+    pub fn stack(&self, id: StackId) -> Option<&Stack> {
+        self.stacks.get(id.0 as usize)
+    }
+
+    pub fn obstacles(&self) -> &BTreeSet<Hex> {
+        &self.obstacles
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    /// The stack to command, None when the battle is over
+    pub fn active(&self) -> Option<StackId> {
+        if self.outcome.is_some() {
+            return None;
+        }
+        self.queue.front().copied()
+    }
+
+    /// Stacks acting after the active one this round, in order
+    pub fn turn_order(&self) -> Vec<StackId> {
+        let mut ret: Vec<_> = self.queue.iter().skip(1).copied().collect();
+        if !self.waiting_phase {
+            ret.extend(self.waiting_order());
+        }
+        ret
+    }
+
+    pub fn hero(&self, side: Side) -> Option<&BattleHero> {
+        self.heroes[side.index()].as_ref()
+    }
+
+    /// Creatures left of the side, with the slots they came from
+    pub fn survivors(&self, side: Side) -> Vec<CreatureSlot> {
+        self.stacks
+            .iter()
+            .filter(|s| s.side == side && s.alive() && !s.summoned)
+            .map(|s| CreatureSlot {
+                slot_num: s.slot,
+                creature: Some(s.creature),
+                amount: s.count,
+            })
+            .collect()
+    }
+
+    fn stack_at(&self, hex: Hex) -> Option<&Stack> {
+        self.stacks.iter().find(|s| s.alive() && s.position == hex)
+    }
+
+    fn is_free(&self, hex: Hex) -> bool {
+        hex.is_valid() && !self.obstacles.contains(&hex) && self.stack_at(hex).is_none()
+    }
+
+    /// Hexes the stack can walk to this turn, with the number of steps
+    pub fn reachable(&self, id: StackId) -> BTreeMap<Hex, u32> {
+        let mut ret = BTreeMap::new();
+        let Some(stack) = self.stack(id).filter(|s| s.alive()) else {
+            return ret;
+        };
+        let mut todo = VecDeque::from([(stack.position, 0)]);
+        while let Some((hex, steps)) = todo.pop_front() {
+            if steps == stack.speed() {
+                continue;
+            }
+            for n in hex.neighbours() {
+                if n != stack.position && self.is_free(n) && !ret.contains_key(&n) {
+                    ret.insert(n, steps + 1);
+                    todo.push_back((n, steps + 1));
+                }
+            }
+        }
+        ret
+    }
+
+    /// The stack has shots left and no enemy next to it
+    pub fn can_shoot(&self, id: StackId) -> bool {
+        let Some(stack) = self.stack(id).filter(|s| s.alive()) else {
+            return false;
+        };
+        stack.shots > 0
+            && !self.stacks.iter().any(|s| {
+                s.alive() && s.side != stack.side && s.position.distance(stack.position) == 1
+            })
+    }
+
+    fn enemy(&self, id: StackId, target: StackId) -> Result<&Stack, CommandError> {
+        let side = self.stacks[id.0 as usize].side;
+        self.stack(target)
+            .filter(|t| t.alive() && t.side != side)
+            .ok_or(CommandError::InvalidTarget)
+    }
+
+    /// Carry out the command of the active stack
+    pub fn execute(&mut self, command: Command) -> Result<Vec<BattleEvent>, CommandError> {
+        let id = self.active().ok_or(CommandError::Finished)?;
+        let position = self.stacks[id.0 as usize].position;
+        let mut events = Vec::new();
+        match command {
+            Command::Move(to) => {
+                if !self.reachable(id).contains_key(&to) {
+                    return Err(CommandError::Unreachable);
+                }
+                self.move_stack(id, to, &mut events);
+            }
+            Command::Attack { target, from } => {
+                let t = self.enemy(id, target)?;
+                if from.distance(t.position) != 1 {
+                    return Err(CommandError::InvalidTarget);
+                }
+                if from != position && !self.reachable(id).contains_key(&from) {
+                    return Err(CommandError::Unreachable);
+                }
+                if from != position {
+                    self.move_stack(id, from, &mut events);
+                }
+                self.strike(id, target, StrikeKind::Melee, &mut events);
+                let t = &self.stacks[target.0 as usize];
+                if t.alive() && t.retaliations > 0 && self.stacks[id.0 as usize].alive() {
+                    self.stacks[target.0 as usize].retaliations -= 1;
+                    self.strike(target, id, StrikeKind::Retaliation, &mut events);
+                }
+            }
+            Command::Shoot(target) => {
+                self.enemy(id, target)?;
+                if !self.can_shoot(id) {
+                    return Err(CommandError::CantShoot);
+                }
+                self.stacks[id.0 as usize].shots -= 1;
+                self.strike(id, target, StrikeKind::Ranged, &mut events);
+            }
+            Command::Wait => {
+                if self.waiting_phase {
+                    return Err(CommandError::AlreadyWaited);
+                }
+                self.waited.push(id);
+                events.push(BattleEvent::Waited(id));
+            }
+            Command::Defend => {
+                self.stacks[id.0 as usize].defending = true;
+                events.push(BattleEvent::Defended(id));
+            }
+            Command::Flee => {
+                self.outcome = Some(Outcome::Fled(self.stacks[id.0 as usize].side));
+            }
+            Command::Cast { spell, target } => {
+                let side = self.stacks[id.0 as usize].side;
+                self.cast(side, spell, target, &mut events)?;
+                // the turn goes on, unless the spell killed the stack
+                self.check_outcome();
+                match self.outcome {
+                    Some(outcome) => events.push(BattleEvent::Finished(outcome)),
+                    None if !self.stacks[id.0 as usize].alive() => self.next_turn(&mut events),
+                    None => (),
+                }
+                return Ok(events);
+            }
+        }
+        self.queue.pop_front();
+        self.check_outcome();
+        match self.outcome {
+            Some(outcome) => events.push(BattleEvent::Finished(outcome)),
+            None => self.next_turn(&mut events),
+        }
+        Ok(events)
+    }
+
+    fn move_stack(&mut self, id: StackId, to: Hex, events: &mut Vec<BattleEvent>) {
+        let stack = &mut self.stacks[id.0 as usize];
+        events.push(BattleEvent::Moved {
+            stack: id,
+            from: stack.position,
+            to,
+        });
+        stack.position = to;
+    }
+
+    // the strike without the random specials
+    fn strike_on(&self, attacker: StackId, target: StackId, kind: StrikeKind) -> Strike {
+        let (a, t) = (
+            &self.stacks[attacker.0 as usize],
+            &self.stacks[target.0 as usize],
+        );
+        let ranged = kind == StrikeKind::Ranged;
+        let (from, to) = (a.position, t.position);
+        let (hero, enemy_hero) = (self.hero(a.side), self.hero(t.side));
+        let active = |s: &Stack, spell| spells::active_mastery(&s.spells, spell);
+        Strike {
+            count: a.count,
+            damage: a.stats.damage,
+            attack: a.attack(ranged) + hero.map_or(0, |h| h.attack),
+            defense: t.defense() + enemy_hero.map_or(0, |h| h.defense),
+            ranged,
+            far: ranged && from.distance(to) > SHOOTING_RANGE,
+            obstructed: ranged && from.line_to(to).iter().any(|h| self.obstacles.contains(h)),
+            shooter_in_melee: !ranged && a.stats.shots > 0,
+            hates: damage::hates(a.creature, t.creature),
+            ignore_defense: damage::ignored_defense(a.creature),
+            offense: hero.and_then(|h| h.skill(damage::OFFENSE)),
+            archery: hero.and_then(|h| h.skill(damage::ARCHERY)),
+            armorer: enemy_hero.and_then(|h| h.skill(damage::ARMORER)),
+            bless: active(a, spells::BLESS),
+            curse: active(a, spells::CURSE),
+            shield: active(t, spells::SHIELD),
+            air_shield: active(t, spells::AIR_SHIELD),
+            stone_skin: active(t, spells::STONE_SKIN),
+            ..Default::default()
+        }
+    }
+
+    /// Damage and kills of an attack or a shot, for the combat tooltip
+    pub fn estimate(&self, attacker: StackId, target: StackId, ranged: bool) -> Option<Estimate> {
+        let t = self.stack(target).filter(|t| t.alive())?;
+        self.stack(attacker).filter(|a| a.alive())?;
+        let kind = if ranged {
+            StrikeKind::Ranged
+        } else {
+            StrikeKind::Melee
+        };
+        let strike = self.strike_on(attacker, target, kind);
+        Some(strike.estimate(t.count, t.stats.health, t.top_health))
+    }
+
+    fn strike(
+        &mut self,
+        attacker: StackId,
+        target: StackId,
+        kind: StrikeKind,
+        events: &mut Vec<BattleEvent>,
+    ) {
+        let mut strike = self.strike_on(attacker, target, kind);
+        let chance = damage::double_damage_chance(self.stacks[attacker.0 as usize].creature);
+        strike.double_damage = chance > 0 && self.rng.percent(chance);
+        let damage = strike.roll(&mut self.rng);
+        let killed = self.stacks[target.0 as usize].take_damage(damage);
+        events.push(BattleEvent::Struck {
+            attacker,
+            target,
+            kind,
+            damage,
+            killed,
+        });
+    }
+
+    /// Spells the hero of the side can cast now: known, not banned by the
+    /// map and affordable, none once the hero has cast this round
+    pub fn castable(&self, side: Side) -> Vec<SpellId> {
+        let Some(hero) = self.hero(side) else {
+            return Vec::new();
+        };
+        if self.cast_this_round[side.index()] {
+            return Vec::new();
+        }
+        hero.spells
+            .iter()
+            .copied()
+            .filter(|s| self.map_allows(*s))
+            .filter(|s| {
+                spells::spell_info(*s)
+                    .is_some_and(|i| i.cost(spells::mastery(&hero.skills, &i)) <= hero.mana)
+            })
+            .collect()
+    }
+
+    /// Stacks the spell hits
+    fn spell_targets(
+        &self,
+        side: Side,
+        info: &SpellInfo,
+        mastery: Option<SecSkillLevel>,
+        target: Hex,
+    ) -> Result<Vec<StackId>, CommandError> {
+        let hits = |s: &&Stack| s.alive() && info.affects(s.creature);
+        let ret: Vec<_> = match (info.kind, info.area) {
+            (SpellKind::Summon(_), _) => Vec::new(),
+            (SpellKind::Buff | SpellKind::Debuff, _) if info.is_mass(mastery) => {
+                let friendly = info.kind == SpellKind::Buff;
+                self.stacks
+                    .iter()
+                    .filter(hits)
+                    .filter(|s| (s.side == side) == friendly)
+                    .map(|s| s.id)
+                    .collect()
+            }
+            (SpellKind::Buff | SpellKind::Debuff, _) => {
+                let friendly = info.kind == SpellKind::Buff;
+                let t = self
+                    .stack_at(target)
+                    .filter(hits)
+                    .filter(|s| (s.side == side) == friendly)
+                    .ok_or(CommandError::InvalidSpellTarget)?;
+                vec![t.id]
+            }
+            (_, spells::Area::Single) => {
+                let t = self
+                    .stack_at(target)
+                    .filter(hits)
+                    .ok_or(CommandError::InvalidSpellTarget)?;
+                vec![t.id]
+            }
+            (_, spells::Area::Ball(radius)) if target.is_valid() => self
+                .stacks
+                .iter()
+                .filter(hits)
+                .filter(|s| s.position.distance(target) <= radius)
+                .map(|s| s.id)
+                .collect(),
+            (_, spells::Area::Ring) if target.is_valid() => self
+                .stacks
+                .iter()
+                .filter(hits)
+                .filter(|s| s.position.distance(target) == 1)
+                .map(|s| s.id)
+                .collect(),
+            (_, spells::Area::All) => self.stacks.iter().filter(hits).map(|s| s.id).collect(),
+            _ => return Err(CommandError::InvalidSpellTarget),
+        };
+        Ok(ret)
+    }
+
+    // the target shrugs off a hostile spell
+    fn resists(&mut self, target: StackId) -> bool {
+        let t = &self.stacks[target.0 as usize];
+        let resistance = self.hero(t.side).and_then(|h| h.skill(spells::RESISTANCE));
+        let chance = spells::magic_resistance(t.creature) + spells::resistance_bonus(resistance);
+        chance > 0 && self.rng.percent(chance)
+    }
+
+    fn cast(
+        &mut self,
+        side: Side,
+        spell: SpellId,
+        target: Hex,
+        events: &mut Vec<BattleEvent>,
+    ) -> Result<(), CommandError> {
+        let hero = self.hero(side).ok_or(CommandError::NoHero)?;
+        if self.cast_this_round[side.index()] {
+            return Err(CommandError::AlreadyCast);
+        }
+        let info = spells::spell_info(spell)
+            .filter(|_| hero.spells.contains(&spell) && self.map_allows(spell))
+            .ok_or(CommandError::UnknownSpell)?;
+        let mastery = spells::mastery(&hero.skills, &info);
+        let cost = info.cost(mastery);
+        if hero.mana < cost {
+            return Err(CommandError::NotEnoughMana);
+        }
+        let power = hero.power.max(1);
+        let sorcery = hero.skill(spells::SORCERY);
+        let targets = self.spell_targets(side, &info, mastery, target)?;
+        let summon_at = match info.kind {
+            SpellKind::Summon(creature) => Some(
+                self.summon_hex(side, creature)
+                    .ok_or(CommandError::InvalidSpellTarget)?,
+            ),
+            _ => None,
+        };
+
+        if let Some(hero) = self.heroes[side.index()].as_mut() {
+            hero.mana -= cost;
+        }
+        self.cast_this_round[side.index()] = true;
+        self.cast[side.index()].push(spell);
+        events.push(BattleEvent::SpellCast { side, spell });
+        match info.kind {
+            SpellKind::Damage { .. } => {
+                let damage = info.damage(power, mastery, sorcery);
+                for t in targets {
+                    if self.resists(t) {
+                        events.push(BattleEvent::Resisted { spell, target: t });
+                        continue;
+                    }
+                    let stack = &mut self.stacks[t.0 as usize];
+                    let reduction = spells::spell_damage_reduction(stack.creature);
+                    let damage = (damage * (100 - reduction) / 100).max(1);
+                    let killed = stack.take_damage(damage);
+                    events.push(BattleEvent::SpellDamage {
+                        spell,
+                        target: t,
+                        damage,
+                        killed,
+                    });
+                }
+            }
+            SpellKind::Buff | SpellKind::Debuff => {
+                for t in targets {
+                    if info.kind == SpellKind::Debuff && self.resists(t) {
+                        events.push(BattleEvent::Resisted { spell, target: t });
+                        continue;
+                    }
+                    let active = ActiveSpell {
+                        spell,
+                        mastery,
+                        rounds: power,
+                    };
+                    spells::add_active(&mut self.stacks[t.0 as usize].spells, active);
+                    events.push(BattleEvent::SpellApplied { spell, target: t });
+                }
+            }
+            SpellKind::Summon(creature) => {
+                let count = info.summoned(power, mastery);
+                if let Some(id) = summon_at.and_then(|h| self.summon(side, creature, count, h)) {
+                    events.push(BattleEvent::Summoned(id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Free hex for a summoned stack, closest to the side's edge and the
+    /// middle row. A side summons one kind of elemental only.
+    fn summon_hex(&self, side: Side, creature: CreatureId) -> Option<Hex> {
+        let other_kind = self
+            .stacks
+            .iter()
+            .any(|s| s.side == side && s.summoned && s.alive() && s.creature != creature);
+        if other_kind || creatures::creature_stats(creature).is_none() {
+            return None;
+        }
+        let edge = match side {
+            Side::Attacker => 0,
+            Side::Defender => FIELD_WIDTH - 1,
+        };
+        (0..FIELD_WIDTH)
+            .flat_map(|x| (0..FIELD_HEIGHT).map(move |y| Hex::new(x, y)))
+            .filter(|h| self.is_free(*h))
+            .min_by_key(|h| ((h.x - edge).abs(), (h.y - FIELD_HEIGHT / 2).abs(), h.y))
+    }
+
+    // the summoned creatures join the side's stack of them or make a new
+    // one, acting from the next round
+    fn summon(
+        &mut self,
+        side: Side,
+        creature: CreatureId,
+        count: u32,
+        hex: Hex,
+    ) -> Option<StackId> {
+        if let Some(s) = self
+            .stacks
+            .iter_mut()
+            .find(|s| s.side == side && s.summoned && s.alive() && s.creature == creature)
+        {
+            s.count += count;
+            return Some(s.id);
+        }
+        let stats = creatures::creature_stats(creature)?;
+        let id = StackId(self.stacks.len() as u8);
+        self.stacks.push(Stack {
+            id,
+            side,
+            slot: 0,
+            creature,
+            stats,
+            count,
+            top_health: stats.health,
+            position: hex,
+            shots: stats.shots,
+            retaliations: 1,
+            defending: false,
+            spells: Vec::new(),
+            summoned: true,
+        });
+        Some(id)
+    }
+
+    /// Spells the hero of the side learns with Eagle Eye, out of the ones
+    /// the enemy hero cast. The hero learns them only once.
+    pub fn eagle_eye(&mut self, side: Side) -> Vec<SpellId> {
+        let Some(hero) = self.heroes[side.index()].as_mut() else {
+            return Vec::new();
+        };
+        let level = spells::skill_level(&hero.skills, spells::EAGLE_EYE);
+        let cast = &self.cast[side.opponent().index()];
+        let learnt = spells::eagle_eye(level, cast, &hero.spells, &mut self.rng);
+        hero.spells.extend(learnt.iter().copied());
+        learnt
+    }
+
+    fn check_outcome(&mut self) {
+        if self.outcome.is_some() {
+            return;
+        }
+        for side in [Side::Attacker, Side::Defender] {
+            if !self.stacks.iter().any(|s| s.side == side && s.alive()) {
+                self.outcome = Some(Outcome::Victory(side.opponent()));
+            }
+        }
+    }
+
+    // stacks that waited, slowest first
+    fn waiting_order(&self) -> Vec<StackId> {
+        let mut ret: Vec<_> = self
+            .waited
+            .iter()
+            .copied()
+            .filter(|id| self.stacks[id.0 as usize].alive())
+            .collect();
+        ret.sort_by_key(|id| {
+            let s = &self.stacks[id.0 as usize];
+            (s.speed(), s.side, s.slot)
+        });
+        ret
+    }
+
+    fn start_round(&mut self) {
+        self.round += 1;
+        let first = self.round == 1;
+        for s in self.stacks.iter_mut() {
+            s.retaliations = 1;
+            if !first {
+                for spell in s.spells.iter_mut() {
+                    spell.rounds -= 1;
+                }
+                s.spells.retain(|spell| spell.rounds > 0);
+            }
+        }
+        self.cast_this_round = [false; 2];
+        let mut order: Vec<_> = self
+            .stacks
+            .iter()
+            .filter(|s| s.alive())
+            .map(|s| s.id)
+            .collect();
+        order.sort_by_key(|id| {
+            let s = &self.stacks[id.0 as usize];
+            (std::cmp::Reverse(s.speed()), s.side, s.slot)
+        });
+        self.queue = order.into();
+        self.waited.clear();
+        self.waiting_phase = false;
+        if let Some(id) = self.queue.front() {
+            self.stacks[id.0 as usize].defending = false;
+        }
+    }
+
+    fn next_turn(&mut self, events: &mut Vec<BattleEvent>) {
+        while self
+            .queue
+            .front()
+            .is_some_and(|id| !self.stacks[id.0 as usize].alive())
+        {
+            self.queue.pop_front();
+        }
+        if self.queue.is_empty() && !self.waiting_phase && !self.waited.is_empty() {
+            self.queue = self.waiting_order().into();
+            self.waiting_phase = true;
+        }
+        if self.queue.is_empty() {
+            self.start_round();
+            events.push(BattleEvent::NewRound(self.round));
+            return;
+        }
+        if let Some(id) = self.queue.front() {
+            self.stacks[id.0 as usize].defending = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIKEMAN: CreatureId = CreatureId(0);
+    const ARCHER: CreatureId = CreatureId(2);
+    const GRIFFIN: CreatureId = CreatureId(4);
+    const ANGEL: CreatureId = CreatureId(12);
+    const ARCHANGEL: CreatureId = CreatureId(13);
+
+    fn army(stacks: &[(CreatureId, u32)]) -> Army {
+        Army {
+            slots: stacks
+                .iter()
+                .enumerate()
+                .map(|(i, (creature, amount))| CreatureSlot {
+                    slot_num: i as u8,
+                    creature: Some(*creature),
+                    amount: *amount,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    // melee attack on the target from where the active stack stands or can walk to
+    fn attack(battle: &Battle, target: StackId) -> Option<Command> {
+        let id = battle.active()?;
+        let position = battle.stack(id)?.position;
+        let to = battle.stack(target)?.position;
+        let reachable = battle.reachable(id);
+        std::iter::once(position)
+            .chain(reachable.keys().copied())
+            .find(|h| h.distance(to) == 1)
+            .map(|from| Command::Attack { target, from })
+    }
+
+    // shoot if possible, otherwise attack the first enemy in reach, otherwise
+    // walk towards the first enemy
+    fn script(battle: &Battle) -> Command {
+        let id = battle.active().unwrap();
+        let side = battle.stack(id).unwrap().side;
+        let enemies: Vec<_> = battle
+            .stacks()
+            .iter()
+            .filter(|s| s.alive() && s.side != side)
+            .collect();
+        if battle.can_shoot(id) {
+            return Command::Shoot(enemies[0].id);
+        }
+        if let Some(command) = enemies.iter().find_map(|s| attack(battle, s.id)) {
+            return command;
+        }
+        let to = enemies[0].position;
+        battle
+            .reachable(id)
+            .into_keys()
+            .min_by_key(|h| h.distance(to))
+            .map_or(Command::Defend, Command::Move)
+    }
+
+    fn fight(seed: u64) -> Vec<BattleEvent> {
+        let attacker = army(&[(ANGEL, 2), (ARCHER, 20), (PIKEMAN, 30)]);
+        let defender = army(&[(PIKEMAN, 50), (GRIFFIN, 10), (ARCHER, 15)]);
+        let mut battle = Battle::new(&attacker, &defender, seed);
+        let mut events = Vec::new();
+        for _ in 0..500 {
+            if battle.outcome().is_some() {
+                break;
+            }
+            let command = script(&battle);
+            events.extend(battle.execute(command).unwrap());
+        }
+        events
+    }
+
+    #[test]
+    fn same_seed_same_battle() {
+        for seed in [1, 7, 42] {
+            let events = fight(seed);
+            assert!(matches!(events.last(), Some(BattleEvent::Finished(_))));
+            assert_eq!(events, fight(seed));
+        }
+    }
+
+    #[test]
+    fn speed_order_and_waiting() {
+        let attacker = army(&[(PIKEMAN, 10), (ANGEL, 1)]);
+        let defender = army(&[(GRIFFIN, 10), (PIKEMAN, 10)]);
+        let mut battle = Battle::new(&attacker, &defender, 0);
+        // fastest first, the attacker first on ties
+        assert_eq!(battle.active(), Some(StackId(1)));
+        assert_eq!(battle.turn_order(), [StackId(2), StackId(0), StackId(3)]);
+
+        let events = battle.execute(Command::Wait).unwrap();
+        assert_eq!(events, [BattleEvent::Waited(StackId(1))]);
+        assert_eq!(battle.active(), Some(StackId(2)));
+        assert_eq!(battle.turn_order(), [StackId(0), StackId(3), StackId(1)]);
+        battle.execute(Command::Wait).unwrap();
+        // the stacks that waited act last, slowest first
+        assert_eq!(battle.turn_order(), [StackId(3), StackId(2), StackId(1)]);
+        battle.execute(Command::Defend).unwrap();
+        battle.execute(Command::Defend).unwrap();
+        assert_eq!(battle.active(), Some(StackId(2)));
+        assert_eq!(
+            battle.execute(Command::Wait),
+            Err(CommandError::AlreadyWaited)
+        );
+        battle.execute(Command::Defend).unwrap();
+        assert_eq!(battle.active(), Some(StackId(1)));
+        let events = battle.execute(Command::Defend).unwrap();
+        assert_eq!(
+            events,
+            [BattleEvent::Defended(StackId(1)), BattleEvent::NewRound(2)]
+        );
+        assert_eq!(battle.active(), Some(StackId(1)));
+    }
+
+    fn retaliated(events: &[BattleEvent]) -> bool {
+        events.iter().any(|e| {
+            matches!(
+                e,
+                BattleEvent::Struck {
+                    kind: StrikeKind::Retaliation,
+                    ..
+                }
+            )
+        })
+    }
+
+    #[test]
+    fn one_retaliation_per_round() {
+        let attacker = army(&[(ARCHANGEL, 1), (ARCHANGEL, 1)]);
+        let defender = army(&[(PIKEMAN, 100)]);
+        let mut battle = Battle::new(&attacker, &defender, 3);
+        let target = StackId(2);
+        let events = battle.execute(attack(&battle, target).unwrap()).unwrap();
+        assert!(retaliated(&events));
+        let events = battle.execute(attack(&battle, target).unwrap()).unwrap();
+        assert!(!retaliated(&events));
+        // the pikemen act last and the next round starts
+        assert_eq!(battle.active(), Some(target));
+        battle.execute(Command::Defend).unwrap();
+        assert_eq!(battle.round(), 2);
+        let events = battle.execute(attack(&battle, target).unwrap()).unwrap();
+        assert!(retaliated(&events));
+    }
+
+    #[test]
+    fn no_shooting_next_to_an_enemy() {
+        let attacker = army(&[(ARCHER, 50)]);
+        let defender = army(&[(ARCHANGEL, 1)]);
+        let mut battle = Battle::new(&attacker, &defender, 5);
+        let archers = StackId(0);
+        assert!(battle.can_shoot(archers));
+        battle.execute(attack(&battle, archers).unwrap()).unwrap();
+        assert_eq!(battle.active(), Some(archers));
+        assert!(!battle.can_shoot(archers));
+        assert_eq!(
+            battle.execute(Command::Shoot(StackId(1))),
+            Err(CommandError::CantShoot)
+        );
+    }
+
+    #[test]
+    fn flee() {
+        let attacker = army(&[(PIKEMAN, 10)]);
+        let defender = army(&[(PIKEMAN, 10)]);
+        let mut battle = Battle::new(&attacker, &defender, 0);
+        assert_eq!(battle.active(), Some(StackId(0)));
+        let events = battle.execute(Command::Flee).unwrap();
+        let outcome = Outcome::Fled(Side::Attacker);
+        assert_eq!(events, [BattleEvent::Finished(outcome)]);
+        assert_eq!(battle.outcome(), Some(outcome));
+        assert_eq!(outcome.winner(), Side::Defender);
+        assert_eq!(battle.active(), None);
+        assert_eq!(battle.execute(Command::Defend), Err(CommandError::Finished));
+    }
+}
@@ -0,0 +1,326 @@
+use resources::map_structs::*;
+
+// Creature statistics of the original game, by creature identifier.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CreatureStats {
+    /// None for neutral creatures
+    pub town: Option<Town>,
+    /// dwelling level 1-7, neutral creatures count as the level they are built like
+    pub level: u8,
+    pub attack: u32,
+    pub defense: u32,
+    pub damage: (u32, u32),
+    pub health: u32,
+    pub speed: u32,
+    /// 0 for creatures that can't shoot
+    pub shots: u32,
+    /// weekly growth of the dwelling
+    pub growth: u32,
+    /// price of a single creature
+    pub cost: ResourcePack,
+}
+
+/// Statistics of the creature, None for unknown and unused identifiers
+pub fn creature_stats(id: CreatureId) -> Option<CreatureStats> {
+    let row = STATS.get(id.0 as usize)?;
+    let [level, attack, defense, min, max, health, speed, shots, growth, gold] = *row;
+    if health == 0 {
+        return None;
+    }
+    let mut cost = [0; 7];
+    cost[Resource::Gold as usize] = gold as i32;
+    if let Some((_, resource, amount)) = RARE_COSTS.iter().find(|(c, _, _)| *c == id.0) {
+        cost[*resource as usize] = *amount;
+    }
+    Some(CreatureStats {
+        town: town(id),
+        level: level as u8,
+        attack,
+        defense,
+        damage: (min, max),
+        health,
+        speed,
+        shots,
+        growth,
+        cost: ResourcePack(cost),
+    })
+}
+
+fn town(id: CreatureId) -> Option<Town> {
+    use Town::*;
+    match id.0 {
+        0..=13 => Some(Castle),
+        14..=27 => Some(Rampart),
+        28..=41 => Some(Tower),
+        42..=55 => Some(Inferno),
+        56..=69 => Some(Necropolis),
+        70..=83 => Some(Dungeon),
+        84..=97 => Some(Stronghold),
+        98..=111 => Some(Fortress),
+        112..=115 | 118..=131 => Some(Conflux),
+        _ => None,
+    }
+}
+
+/// Creatures of a dwelling level of the town, the basic one first and then its upgrade
+pub fn town_creatures(town: Town, level: u8) -> Vec<CreatureId> {
+    (0..STATS.len() as u16)
+        .map(CreatureId)
+        .filter(|c| creature_stats(*c).is_some_and(|s| s.town == Some(town) && s.level == level))
+        .collect()
+}
+
+/// Creature of a one creature dwelling on the adventure map (CreatureGenerator1) by its subid
+pub fn dwelling_creature(subid: u32) -> Option<CreatureId> {
+    DWELLING_CREATURES
+        .get(subid as usize)
+        .map(|c| CreatureId(*c))
+}
+
+/// Subid of the one creature dwelling of the creature, the first one for
+/// creatures with more than one
+pub fn dwelling_subid(creature: CreatureId) -> Option<u32> {
+    DWELLING_CREATURES
+        .iter()
+        .position(|c| *c == creature.0)
+        .map(|i| i as u32)
+}
+
+/// Creatures of a four creature dwelling (CreatureGenerator4) by its subid:
+/// the Elemental Conflux and the Golem Factory
+pub fn multi_dwelling_creatures(subid: u32) -> Vec<CreatureId> {
+    let ids: &[u16] = match subid {
+        0 => &[112, 115, 114, 113],
+        1 => &[33, 32, 116, 117],
+        _ => &[],
+    };
+    ids.iter().map(|c| CreatureId(*c)).collect()
+}
+
+/// Terrain the creatures of the town move on without penalty
+pub fn native_terrain(town: Town) -> Surface {
+    use Town::*;
+    match town {
+        Castle | Rampart | Conflux => Surface::Grass,
+        Tower => Surface::Snow,
+        Inferno => Surface::Lava,
+        Necropolis => Surface::Dirt,
+        Dungeon => Surface::Subterranean,
+        Stronghold => Surface::Rough,
+        Fortress | Cove => Surface::Swamp,
+        Factory => Surface::Sand,
+    }
+}
+
+/// Necropolis creatures and the Mummy
+pub fn is_undead(id: CreatureId) -> bool {
+    matches!(id.0, 56..=69 | 141)
+}
+
+/// Creatures neither alive nor undead: Gargoyles, Golems and Elementals
+pub fn is_non_living(id: CreatureId) -> bool {
+    matches!(
+        id.0,
+        30..=33 | 112..=117 | 120 | 121 | 123 | 125 | 127 | 129
+    )
+}
+
+// creatures of the one creature dwellings by subid, the order of the original game
+const DWELLING_CREATURES: [u16; 80] = [
+    106, 96, 74, 66, 68, 10, 14, 112, 12, 94, // basilisk pit .. cyclops cave
+    54, 104, 16, 113, 52, 18, 114, 30, 36, 86, // forsaken palace .. wolf pen
+    98, 84, 44, 102, 26, 4, 72, 46, 110, 42, // gnoll hut .. imp crucible
+    100, 34, 80, 76, 78, 8, 38, 48, 90, 88, // lizard den .. orc tower
+    50, 82, 92, 28, 40, 22, 70, 115, 60, 108, // hell hole .. wyvern nest
+    20, 24, 64, 62, 56, 58, 2, 6, 0, 118, // enchanted spring .. magic lantern
+    120, 130, 132, 133, 134, 135, 136, 137, 24, 112, // altar of thought .. altar of air
+    113, 114, 115, 138, 139, 140, 141, 142, 143, 144, // altar of earth .. troll bridge
+];
+
+// creatures costing a rare resource besides gold
+const RARE_COSTS: [(u16, Resource, i32); 21] = [
+    (12, Resource::Gems, 1),
+    (13, Resource::Gems, 3),
+    (26, Resource::Crystal, 1),
+    (27, Resource::Crystal, 2),
+    (40, Resource::Gems, 1),
+    (41, Resource::Gems, 2),
+    (54, Resource::Mercury, 1),
+    (55, Resource::Mercury, 2),
+    (69, Resource::Mercury, 1),
+    (82, Resource::Sulfur, 1),
+    (83, Resource::Sulfur, 2),
+    (96, Resource::Crystal, 1),
+    (97, Resource::Crystal, 1),
+    (110, Resource::Sulfur, 1),
+    (111, Resource::Sulfur, 1),
+    (130, Resource::Mercury, 1),
+    (131, Resource::Mercury, 2),
+    (132, Resource::Mercury, 20),
+    (133, Resource::Crystal, 10),
+    (134, Resource::Gems, 8),
+    (135, Resource::Sulfur, 14),
+];
+
+// level, attack, defense, min and max damage, health, speed, shots, growth, gold;
+// zeros for unused identifiers
+#[rustfmt::skip]
+const STATS: [[u32; 10]; 145] = [
+    // Castle
+    [1, 4, 5, 1, 3, 10, 4, 0, 14, 60],         // Pikeman
+    [1, 6, 5, 2, 3, 10, 5, 0, 14, 75],         // Halberdier
+    [2, 6, 3, 2, 3, 10, 4, 12, 9, 100],        // Archer
+    [2, 6, 3, 2, 3, 10, 6, 24, 9, 150],        // Marksman
+    [3, 8, 8, 3, 6, 25, 6, 0, 7, 200],         // Griffin
+    [3, 9, 9, 3, 6, 25, 9, 0, 7, 240],         // Royal Griffin
+    [4, 10, 12, 6, 9, 35, 5, 0, 4, 300],       // Swordsman
+    [4, 12, 12, 7, 10, 35, 6, 0, 4, 400],      // Crusader
+    [5, 12, 7, 10, 12, 30, 5, 12, 3, 400],     // Monk
+    [5, 12, 10, 10, 12, 30, 7, 24, 3, 450],    // Zealot
+    [6, 15, 15, 15, 25, 100, 7, 0, 2, 1000],   // Cavalier
+    [6, 16, 16, 20, 25, 100, 9, 0, 2, 1200],   // Champion
+    [7, 20, 20, 50, 50, 200, 12, 0, 1, 3000],  // Angel
+    [7, 30, 30, 50, 50, 250, 18, 0, 1, 5000],  // Archangel
+    // Rampart
+    [1, 5, 3, 2, 3, 8, 6, 0, 14, 70],          // Centaur
+    [1, 6, 3, 2, 3, 10, 8, 0, 14, 90],         // Centaur Captain
+    [2, 6, 5, 2, 4, 20, 3, 0, 8, 120],         // Dwarf
+    [2, 7, 6, 2, 4, 20, 5, 0, 8, 150],         // Battle Dwarf
+    [3, 9, 5, 3, 5, 15, 6, 24, 7, 200],        // Wood Elf
+    [3, 9, 5, 3, 5, 15, 7, 24, 7, 225],        // Grand Elf
+    [4, 9, 8, 5, 9, 30, 8, 0, 5, 250],         // Pegasus
+    [4, 9, 10, 5, 9, 30, 12, 0, 5, 275],       // Silver Pegasus
+    [5, 9, 12, 10, 14, 55, 3, 0, 3, 350],      // Dendroid Guard
+    [5, 9, 12, 10, 14, 65, 4, 0, 3, 425],      // Dendroid Soldier
+    [6, 15, 14, 18, 22, 90, 7, 0, 2, 850],     // Unicorn
+    [6, 15, 14, 18, 22, 110, 9, 0, 2, 950],    // War Unicorn
+    [7, 18, 18, 40, 50, 180, 10, 0, 1, 2400],  // Green Dragon
+    [7, 27, 27, 40, 50, 250, 16, 0, 1, 4000],  // Gold Dragon
+    // Tower
+    [1, 3, 3, 1, 2, 4, 4, 0, 16, 30],          // Gremlin
+    [1, 4, 4, 1, 2, 4, 5, 8, 16, 40],          // Master Gremlin
+    [2, 6, 6, 2, 3, 16, 6, 0, 9, 130],         // Stone Gargoyle
+    [2, 7, 7, 2, 3, 16, 9, 0, 9, 160],         // Obsidian Gargoyle
+    [3, 7, 10, 4, 5, 30, 3, 0, 6, 150],        // Stone Golem
+    [3, 9, 10, 4, 5, 35, 5, 0, 6, 200],        // Iron Golem
+    [4, 11, 8, 7, 9, 25, 5, 24, 4, 350],       // Mage
+    [4, 12, 9, 7, 9, 30, 7, 24, 4, 450],       // Arch Mage
+    [5, 12, 12, 13, 16, 40, 7, 0, 3, 550],     // Genie
+    [5, 12, 12, 13, 16, 40, 11, 0, 3, 600],    // Master Genie
+    [6, 16, 13, 20, 20, 110, 5, 0, 2, 1100],   // Naga
+    [6, 16, 13, 30, 30, 110, 7, 0, 2, 1600],   // Naga Queen
+    [7, 19, 16, 40, 60, 150, 7, 0, 1, 2000],   // Giant
+    [7, 24, 24, 40, 60, 300, 11, 24, 1, 5000], // Titan
+    // Inferno
+    [1, 2, 3, 1, 2, 4, 5, 0, 15, 50],          // Imp
+    [1, 4, 4, 1, 2, 4, 7, 0, 15, 60],          // Familiar
+    [2, 6, 4, 2, 4, 13, 4, 12, 8, 125],        // Gog
+    [2, 7, 4, 2, 4, 13, 6, 24, 8, 175],        // Magog
+    [3, 10, 6, 2, 7, 25, 7, 0, 5, 200],        // Hell Hound
+    [3, 10, 8, 2, 7, 25, 8, 0, 5, 250],        // Cerberus
+    [4, 10, 10, 7, 9, 35, 5, 0, 4, 250],       // Demon
+    [4, 10, 10, 7, 9, 40, 6, 0, 4, 270],       // Horned Demon
+    [5, 13, 13, 13, 17, 45, 6, 0, 3, 500],     // Pit Fiend
+    [5, 13, 13, 13, 17, 45, 7, 0, 3, 700],     // Pit Lord
+    [6, 16, 12, 16, 24, 90, 9, 0, 2, 900],     // Efreeti
+    [6, 16, 14, 16, 24, 90, 13, 0, 2, 1100],   // Efreet Sultan
+    [7, 19, 21, 30, 40, 160, 11, 0, 1, 2700],  // Devil
+    [7, 26, 28, 30, 40, 200, 17, 0, 1, 4500],  // Arch Devil
+    // Necropolis
+    [1, 5, 4, 1, 3, 6, 4, 0, 12, 60],          // Skeleton
+    [1, 6, 6, 1, 3, 6, 5, 0, 12, 70],          // Skeleton Warrior
+    [2, 5, 5, 2, 3, 15, 3, 0, 8, 100],         // Walking Dead
+    [2, 5, 5, 2, 3, 20, 4, 0, 8, 125],         // Zombie
+    [3, 7, 7, 3, 5, 18, 5, 0, 7, 200],         // Wight
+    [3, 7, 7, 3, 5, 18, 7, 0, 7, 230],         // Wraith
+    [4, 10, 9, 5, 8, 30, 6, 0, 4, 360],        // Vampire
+    [4, 10, 10, 5, 8, 40, 9, 0, 4, 500],       // Vampire Lord
+    [5, 13, 10, 11, 13, 30, 6, 12, 3, 550],    // Lich
+    [5, 13, 10, 11, 15, 40, 7, 24, 3, 600],    // Power Lich
+    [6, 16, 16, 15, 30, 120, 7, 0, 2, 1200],   // Black Knight
+    [6, 18, 18, 15, 30, 120, 9, 0, 2, 1500],   // Dread Knight
+    [7, 17, 15, 25, 50, 150, 9, 0, 1, 1800],   // Bone Dragon
+    [7, 19, 17, 25, 50, 200, 14, 0, 1, 3000],  // Ghost Dragon
+    // Dungeon
+    [1, 4, 3, 1, 3, 5, 4, 0, 14, 50],          // Troglodyte
+    [1, 5, 4, 1, 3, 6, 5, 0, 14, 65],          // Infernal Troglodyte
+    [2, 6, 5, 1, 4, 14, 6, 0, 8, 130],         // Harpy
+    [2, 6, 6, 1, 4, 14, 9, 0, 8, 170],         // Harpy Hag
+    [3, 9, 7, 3, 5, 22, 5, 12, 7, 250],        // Beholder
+    [3, 10, 8, 3, 5, 22, 7, 24, 7, 280],       // Evil Eye
+    [4, 9, 9, 6, 8, 25, 5, 4, 4, 300],         // Medusa
+    [4, 10, 10, 6, 8, 30, 6, 8, 4, 330],       // Medusa Queen
+    [5, 14, 12, 12, 20, 50, 6, 0, 3, 500],     // Minotaur
+    [5, 15, 15, 12, 20, 50, 8, 0, 3, 575],     // Minotaur King
+    [6, 15, 13, 14, 20, 80, 7, 0, 2, 850],     // Manticore
+    [6, 16, 14, 14, 20, 80, 11, 0, 2, 1050],   // Scorpicore
+    [7, 19, 19, 40, 50, 180, 11, 0, 1, 2500],  // Red Dragon
+    [7, 25, 25, 40, 50, 300, 15, 0, 1, 4000],  // Black Dragon
+    // Stronghold
+    [1, 4, 2, 1, 2, 5, 5, 0, 15, 40],          // Goblin
+    [1, 5, 3, 1, 2, 5, 7, 0, 15, 50],          // Hobgoblin
+    [2, 7, 5, 2, 4, 10, 6, 0, 9, 100],         // Wolf Rider
+    [2, 8, 5, 3, 4, 10, 8, 0, 9, 140],         // Wolf Raider
+    [3, 8, 4, 2, 5, 15, 4, 12, 7, 150],        // Orc
+    [3, 8, 4, 2, 5, 20, 5, 24, 7, 165],        // Orc Chieftain
+    [4, 13, 7, 6, 12, 40, 4, 0, 4, 300],       // Ogre
+    [4, 13, 7, 6, 12, 60, 5, 0, 4, 400],       // Ogre Mage
+    [5, 17, 11, 11, 15, 60, 7, 0, 3, 600],     // Roc
+    [5, 18, 12, 11, 15, 60, 11, 0, 3, 700],    // Thunderbird
+    [6, 17, 13, 16, 20, 70, 6, 16, 2, 750],    // Cyclops
+    [6, 17, 13, 16, 20, 70, 8, 24, 2, 1100],   // Cyclops King
+    [7, 17, 17, 30, 50, 160, 6, 0, 1, 1500],   // Behemoth
+    [7, 19, 19, 30, 50, 300, 9, 0, 1, 3000],   // Ancient Behemoth
+    // Fortress
+    [1, 3, 5, 2, 3, 6, 4, 0, 12, 50],          // Gnoll
+    [1, 4, 6, 2, 3, 6, 5, 0, 12, 70],          // Gnoll Marauder
+    [2, 5, 6, 1, 3, 14, 4, 12, 9, 110],        // Lizardman
+    [2, 6, 8, 2, 3, 15, 5, 24, 9, 140],        // Lizard Warrior
+    [5, 10, 14, 12, 16, 70, 5, 0, 3, 525],     // Gorgon
+    [5, 11, 16, 12, 16, 70, 6, 0, 3, 600],     // Mighty Gorgon
+    [3, 7, 9, 2, 5, 20, 9, 0, 8, 220],         // Serpent Fly
+    [3, 8, 10, 2, 5, 20, 13, 0, 8, 240],       // Dragon Fly
+    [4, 11, 11, 6, 10, 35, 5, 0, 4, 325],      // Basilisk
+    [4, 12, 12, 6, 10, 40, 7, 0, 4, 400],      // Greater Basilisk
+    [6, 14, 14, 14, 18, 70, 7, 0, 2, 800],     // Wyvern
+    [6, 14, 14, 18, 22, 70, 11, 0, 2, 1100],   // Wyvern Monarch
+    [7, 16, 18, 25, 45, 175, 5, 0, 1, 2200],   // Hydra
+    [7, 18, 20, 25, 45, 250, 7, 0, 1, 3500],   // Chaos Hydra
+    // Conflux elementals and neutral golems
+    [2, 9, 9, 2, 8, 25, 7, 0, 6, 250],         // Air Elemental
+    [5, 10, 10, 4, 8, 40, 4, 0, 4, 400],       // Earth Elemental
+    [4, 10, 8, 4, 6, 35, 6, 0, 5, 350],        // Fire Elemental
+    [3, 8, 10, 3, 7, 30, 5, 0, 6, 300],        // Water Elemental
+    [5, 11, 12, 8, 10, 50, 5, 0, 3, 500],      // Gold Golem
+    [6, 13, 12, 10, 14, 60, 5, 0, 2, 750],     // Diamond Golem
+    // Conflux
+    [1, 2, 2, 1, 2, 3, 7, 0, 20, 25],          // Pixie
+    [1, 2, 2, 1, 3, 3, 9, 0, 20, 30],          // Sprite
+    [6, 15, 13, 10, 20, 75, 7, 0, 2, 750],     // Psychic Elemental
+    [6, 15, 13, 15, 25, 80, 9, 0, 2, 800],     // Magic Elemental
+    [0; 10],
+    [3, 8, 10, 3, 7, 30, 6, 24, 6, 375],       // Ice Elemental
+    [0; 10],
+    [5, 11, 11, 6, 10, 40, 6, 0, 4, 500],      // Magma Elemental
+    [0; 10],
+    [2, 9, 9, 2, 8, 25, 8, 24, 6, 275],        // Storm Elemental
+    [0; 10],
+    [4, 12, 8, 4, 6, 35, 8, 0, 5, 400],        // Energy Elemental
+    [7, 18, 18, 30, 40, 150, 15, 0, 2, 1500],  // Firebird
+    [7, 21, 18, 30, 40, 200, 21, 0, 2, 2000],  // Phoenix
+    // neutrals
+    [7, 50, 50, 70, 80, 1000, 19, 0, 1, 30000], // Azure Dragon
+    [7, 40, 40, 60, 75, 800, 16, 0, 1, 20000], // Crystal Dragon
+    [7, 20, 20, 20, 30, 500, 15, 0, 1, 10000], // Faerie Dragon
+    [7, 30, 30, 50, 50, 750, 17, 0, 1, 15000], // Rust Dragon
+    [6, 17, 12, 14, 14, 30, 9, 32, 2, 1500],   // Enchanter
+    [4, 12, 10, 8, 10, 15, 9, 32, 4, 400],     // Sharpshooter
+    [1, 4, 2, 1, 3, 4, 5, 24, 15, 50],         // Halfling
+    [1, 1, 1, 1, 1, 1, 3, 0, 25, 10],          // Peasant
+    [2, 6, 5, 2, 3, 15, 6, 0, 8, 150],         // Boar
+    [3, 7, 7, 3, 5, 30, 5, 0, 7, 300],         // Mummy
+    [3, 9, 8, 2, 6, 30, 7, 0, 7, 200],         // Nomad
+    [2, 8, 3, 2, 4, 10, 6, 0, 8, 100],         // Rogue
+    [5, 14, 7, 10, 15, 40, 7, 0, 3, 500],      // Troll
+];
@@ -0,0 +1,696 @@
+use crate::rng::Rng;
+use resources::map_structs::*;
+
+// Damage of a strike, following the original game:
+// - every creature of the stack rolls its damage range, a stack of more than
+//   10 rolls 10 times and scales the sum to its size; a blessed stack deals
+//   its maximum and a cursed one its minimum,
+// - attack above defense adds 5% a point up to 300%, defense above attack
+//   takes 2.5% a point up to 70%,
+// - Offense or Archery, good luck, double damage and hate add to that bonus,
+// - Armorer, Shield or Air Shield and the shooting penalties each take a
+//   share of what is left, and a strike deals at least 1 damage.
+
+pub const ARCHERY: SecSkillId = SecSkillId(1);
+pub const OFFENSE: SecSkillId = SecSkillId(22);
+pub const ARMORER: SecSkillId = SecSkillId(23);
+
+/// Creatures dealing half damage more to the others of the pair
+const HATES: [(&[u16], &[u16]); 6] = [
+    // Angels and Devils
+    (&[12, 13], &[54, 55]),
+    (&[54, 55], &[12, 13]),
+    // Genies and Efreet
+    (&[36, 37], &[52, 53]),
+    (&[52, 53], &[36, 37]),
+    // Giants and Black Dragons
+    (&[40, 41], &[83]),
+    (&[83], &[40, 41]),
+];
+
+const MAX_ATTACK_BONUS: u32 = 300;
+const MAX_DEFENSE_REDUCTION: u32 = 70;
+
+/// Everything the damage of a strike depends on. Attack and defense already
+/// include the hero's skills and the defend bonus; spells are given by the
+/// mastery they were cast with, casting without the school skill counts as
+/// Basic.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Strike {
+    /// creatures in the attacking stack
+    pub count: u32,
+    /// damage range of one creature
+    pub damage: (u32, u32),
+    pub attack: u32,
+    pub defense: u32,
+    pub ranged: bool,
+    /// a shot beyond the shooting range
+    pub far: bool,
+    /// a shot through an obstacle or a wall
+    pub obstructed: bool,
+    /// a shooter hitting in melee
+    pub shooter_in_melee: bool,
+    /// skills of the attacker's hero, Offense counts for melee and Archery for shots
+    pub offense: Option<SecSkillLevel>,
+    pub archery: Option<SecSkillLevel>,
+    /// skill of the defender's hero
+    pub armorer: Option<SecSkillLevel>,
+    pub lucky: bool,
+    pub double_damage: bool,
+    pub hates: bool,
+    /// percent of the defense the attacker ignores
+    pub ignore_defense: u32,
+    /// spells on the attacker
+    pub bless: Option<SecSkillLevel>,
+    pub curse: Option<SecSkillLevel>,
+    /// spells on the target, Shield against melee and Air Shield against shots
+    pub shield: Option<SecSkillLevel>,
+    pub air_shield: Option<SecSkillLevel>,
+    pub stone_skin: Option<SecSkillLevel>,
+}
+
+/// Damage a strike can deal and what it kills, for the combat tooltip
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Estimate {
+    pub min: u32,
+    pub max: u32,
+    pub expected: u32,
+    pub min_kills: u32,
+    pub max_kills: u32,
+}
+
+/// The creature hates the target and deals it half damage more
+pub fn hates(creature: CreatureId, target: CreatureId) -> bool {
+    HATES
+        .iter()
+        .any(|(a, t)| a.contains(&creature.0) && t.contains(&target.0))
+}
+
+/// Percent of the target's defense the creature ignores
+pub fn ignored_defense(creature: CreatureId) -> u32 {
+    match creature.0 {
+        // Behemoth, Ancient Behemoth
+        96 => 40,
+        97 => 80,
+        _ => 0,
+    }
+}
+
+/// Percent chance the creature deals double damage, the Dread Knight's death blow
+pub fn double_damage_chance(creature: CreatureId) -> u32 {
+    match creature.0 {
+        67 => 20,
+        _ => 0,
+    }
+}
+
+fn stone_skin_bonus(level: Option<SecSkillLevel>) -> u32 {
+    match level {
+        None => 0,
+        Some(SecSkillLevel::Basic) => 3,
+        Some(_) => 6,
+    }
+}
+
+impl Strike {
+    /// Defense left after Stone Skin and the ignored share
+    pub fn effective_defense(&self) -> u32 {
+        let defense = self.defense + stone_skin_bonus(self.stone_skin);
+        defense * (100 - self.ignore_defense.min(100)) / 100
+    }
+
+    /// Damage range of one creature with Bless and Curse, in hundredths
+    fn creature_damage(&self) -> (u64, u64) {
+        let (min, max) = (self.damage.0 as u64 * 100, self.damage.1 as u64 * 100);
+        match (self.bless, self.curse) {
+            (Some(SecSkillLevel::Basic), _) => (max, max),
+            (Some(_), _) => (max * 103 / 100, max * 103 / 100),
+            (None, Some(SecSkillLevel::Basic)) => (min, min),
+            (None, Some(_)) => (min * 80 / 100, min * 80 / 100),
+            (None, None) => (min, max),
+        }
+    }
+
+    /// Percent added to the damage
+    fn bonus(&self) -> u32 {
+        let attack = self.attack;
+        let defense = self.effective_defense();
+        let mut ret = if attack > defense {
+            (5 * (attack - defense)).min(MAX_ATTACK_BONUS)
+        } else {
+            0
+        };
+        ret += match (self.ranged, self.archery, self.offense) {
+            (true, Some(SecSkillLevel::Basic), _) => 10,
+            (true, Some(SecSkillLevel::Advanced), _) => 25,
+            (true, Some(SecSkillLevel::Expert), _) => 50,
+            (false, _, Some(SecSkillLevel::Basic)) => 10,
+            (false, _, Some(SecSkillLevel::Advanced)) => 20,
+            (false, _, Some(SecSkillLevel::Expert)) => 30,
+            _ => 0,
+        };
+        if self.lucky {
+            ret += 100;
+        }
+        if self.double_damage {
+            ret += 100;
+        }
+        if self.hates {
+            ret += 50;
+        }
+        ret
+    }
+
+    /// Tenths of a percent taken from the damage, one after the other
+    fn reductions(&self) -> Vec<u32> {
+        let mut ret = Vec::new();
+        let (attack, defense) = (self.attack, self.effective_defense());
+        if defense > attack {
+            ret.push((25 * (defense - attack)).min(MAX_DEFENSE_REDUCTION * 10));
+        }
+        let armorer = match self.armorer {
+            None => 0,
+            Some(SecSkillLevel::Basic) => 5,
+            Some(SecSkillLevel::Advanced) => 10,
+            Some(SecSkillLevel::Expert) => 15,
+        };
+        let shield = match (self.ranged, self.shield, self.air_shield) {
+            (false, Some(SecSkillLevel::Basic), _) => 15,
+            (false, Some(_), _) => 30,
+            (true, _, Some(SecSkillLevel::Basic)) => 25,
+            (true, _, Some(_)) => 50,
+            _ => 0,
+        };
+        let halved = [
+            self.ranged && self.far,
+            self.ranged && self.obstructed,
+            !self.ranged && self.shooter_in_melee,
+        ];
+        ret.extend([armorer, shield].iter().filter(|r| **r > 0).map(|r| r * 10));
+        ret.extend(halved.iter().filter(|h| **h).map(|_| 500));
+        ret
+    }
+
+    /// Apply the modifiers to a base damage in hundredths
+    fn modify(&self, base: u64) -> u32 {
+        let mut damage = base * (100 + self.bonus() as u64) / 100;
+        for r in self.reductions() {
+            damage = damage * (1000 - r as u64) / 1000;
+        }
+        ((damage / 100) as u32).max(1)
+    }
+
+    pub fn min_damage(&self) -> u32 {
+        self.modify(self.creature_damage().0 * self.count as u64)
+    }
+
+    pub fn max_damage(&self) -> u32 {
+        self.modify(self.creature_damage().1 * self.count as u64)
+    }
+
+    /// Damage of the average roll
+    pub fn expected_damage(&self) -> u32 {
+        let (min, max) = self.creature_damage();
+        self.modify((min + max) * self.count as u64 / 2)
+    }
+
+    /// Roll the damage of the strike
+    pub fn roll(&self, rng: &mut Rng) -> u32 {
+        let (min, max) = self.creature_damage();
+        let rolls = self.count.min(10);
+        let roll = |rng: &mut Rng| {
+            if min == max {
+                min
+            } else {
+                // whole points of damage, Bless and Curse don't roll
+                rng.range((min / 100) as u32, (max / 100) as u32) as u64 * 100
+            }
+        };
+        let mut base: u64 = (0..rolls).map(|_| roll(rng)).sum();
+        if self.count > 10 {
+            base = base * self.count as u64 / 10;
+        }
+        self.modify(base)
+    }
+
+    /// Damage range and kills against a stack of `count` creatures of
+    /// `health`, the top one having `top_health` left
+    pub fn estimate(&self, count: u32, health: u32, top_health: u32) -> Estimate {
+        let (min, max) = (self.min_damage(), self.max_damage());
+        Estimate {
+            min,
+            max,
+            expected: self.expected_damage(),
+            min_kills: kills(min, count, health, top_health),
+            max_kills: kills(max, count, health, top_health),
+        }
+    }
+}
+
+/// Creatures killed by the damage in a stack of `count` creatures of
+/// `health`, the top one having `top_health` left
+pub fn kills(damage: u32, count: u32, health: u32, top_health: u32) -> u32 {
+    if count == 0 || damage < top_health {
+        return 0;
+    }
+    let rest = damage - top_health;
+    (1 + rest / health.max(1)).min(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SecSkillLevel::*;
+
+    const ANGEL: CreatureId = CreatureId(12);
+    const DEVIL: CreatureId = CreatureId(54);
+    const EFREET: CreatureId = CreatureId(52);
+    const BEHEMOTH: CreatureId = CreatureId(96);
+    const ANCIENT_BEHEMOTH: CreatureId = CreatureId(97);
+
+    // 10 creatures dealing 10 each, attack and defense even: 100 damage
+    const BASE: Strike = Strike {
+        count: 10,
+        damage: (10, 10),
+        attack: 10,
+        defense: 10,
+        ranged: false,
+        far: false,
+        obstructed: false,
+        shooter_in_melee: false,
+        offense: None,
+        archery: None,
+        armorer: None,
+        lucky: false,
+        double_damage: false,
+        hates: false,
+        ignore_defense: 0,
+        bless: None,
+        curse: None,
+        shield: None,
+        air_shield: None,
+        stone_skin: None,
+    };
+
+    const SHOT: Strike = Strike {
+        ranged: true,
+        ..BASE
+    };
+
+    // 100 creatures dealing 1-3 each
+    const RANGE: Strike = Strike {
+        count: 100,
+        damage: (1, 3),
+        ..BASE
+    };
+
+    #[test]
+    fn damage_table() {
+        let cases = [
+            ("base", BASE, [100, 100, 100]),
+            ("damage range", RANGE, [100, 300, 200]),
+            (
+                "more attack",
+                Strike { attack: 30, ..BASE },
+                [200, 200, 200],
+            ),
+            ("attack cap", Strike { attack: 70, ..BASE }, [400, 400, 400]),
+            (
+                "above the attack cap",
+                Strike {
+                    attack: 100,
+                    defense: 0,
+                    ..BASE
+                },
+                [400, 400, 400],
+            ),
+            (
+                "more defense",
+                Strike {
+                    defense: 20,
+                    ..BASE
+                },
+                [75, 75, 75],
+            ),
+            (
+                "defense cap",
+                Strike {
+                    attack: 0,
+                    defense: 28,
+                    ..BASE
+                },
+                [30, 30, 30],
+            ),
+            (
+                "above the defense cap",
+                Strike {
+                    attack: 0,
+                    defense: 100,
+                    ..BASE
+                },
+                [30, 30, 30],
+            ),
+            (
+                "basic offense",
+                Strike {
+                    offense: Some(Basic),
+                    ..BASE
+                },
+                [110, 110, 110],
+            ),
+            (
+                "advanced offense",
+                Strike {
+                    offense: Some(Advanced),
+                    ..BASE
+                },
+                [120, 120, 120],
+            ),
+            (
+                "expert offense",
+                Strike {
+                    offense: Some(Expert),
+                    ..BASE
+                },
+                [130, 130, 130],
+            ),
+            (
+                "offense on a shot",
+                Strike {
+                    offense: Some(Expert),
+                    ..SHOT
+                },
+                [100, 100, 100],
+            ),
+            (
+                "basic archery",
+                Strike {
+                    archery: Some(Basic),
+                    ..SHOT
+                },
+                [110, 110, 110],
+            ),
+            (
+                "advanced archery",
+                Strike {
+                    archery: Some(Advanced),
+                    ..SHOT
+                },
+                [125, 125, 125],
+            ),
+            (
+                "expert archery",
+                Strike {
+                    archery: Some(Expert),
+                    ..SHOT
+                },
+                [150, 150, 150],
+            ),
+            (
+                "archery in melee",
+                Strike {
+                    archery: Some(Expert),
+                    ..BASE
+                },
+                [100, 100, 100],
+            ),
+            (
+                "basic armorer",
+                Strike {
+                    armorer: Some(Basic),
+                    ..BASE
+                },
+                [95, 95, 95],
+            ),
+            (
+                "advanced armorer",
+                Strike {
+                    armorer: Some(Advanced),
+                    ..BASE
+                },
+                [90, 90, 90],
+            ),
+            (
+                "expert armorer",
+                Strike {
+                    armorer: Some(Expert),
+                    ..BASE
+                },
+                [85, 85, 85],
+            ),
+            (
+                "luck",
+                Strike {
+                    lucky: true,
+                    ..BASE
+                },
+                [200, 200, 200],
+            ),
+            (
+                "double damage",
+                Strike {
+                    double_damage: true,
+                    ..BASE
+                },
+                [200, 200, 200],
+            ),
+            (
+                "hate",
+                Strike {
+                    hates: hates(ANGEL, DEVIL),
+                    ..BASE
+                },
+                [150, 150, 150],
+            ),
+            (
+                "behemoth",
+                Strike {
+                    defense: 20,
+                    ignore_defense: ignored_defense(BEHEMOTH),
+                    ..BASE
+                },
+                [95, 95, 95],
+            ),
+            (
+                "ancient behemoth",
+                Strike {
+                    defense: 20,
+                    ignore_defense: ignored_defense(ANCIENT_BEHEMOTH),
+                    ..BASE
+                },
+                [130, 130, 130],
+            ),
+            (
+                "basic bless",
+                Strike {
+                    bless: Some(Basic),
+                    ..RANGE
+                },
+                [300, 300, 300],
+            ),
+            (
+                "expert bless",
+                Strike {
+                    bless: Some(Expert),
+                    ..RANGE
+                },
+                [309, 309, 309],
+            ),
+            (
+                "basic curse",
+                Strike {
+                    curse: Some(Basic),
+                    ..RANGE
+                },
+                [100, 100, 100],
+            ),
+            (
+                "expert curse",
+                Strike {
+                    curse: Some(Expert),
+                    ..RANGE
+                },
+                [80, 80, 80],
+            ),
+            (
+                "bless wins over curse",
+                Strike {
+                    bless: Some(Basic),
+                    curse: Some(Basic),
+                    ..RANGE
+                },
+                [300, 300, 300],
+            ),
+            (
+                "basic shield",
+                Strike {
+                    shield: Some(Basic),
+                    ..BASE
+                },
+                [85, 85, 85],
+            ),
+            (
+                "expert shield",
+                Strike {
+                    shield: Some(Expert),
+                    ..BASE
+                },
+                [70, 70, 70],
+            ),
+            (
+                "shield against a shot",
+                Strike {
+                    shield: Some(Expert),
+                    ..SHOT
+                },
+                [100, 100, 100],
+            ),
+            (
+                "basic air shield",
+                Strike {
+                    air_shield: Some(Basic),
+                    ..SHOT
+                },
+                [75, 75, 75],
+            ),
+            (
+                "expert air shield",
+                Strike {
+                    air_shield: Some(Expert),
+                    ..SHOT
+                },
+                [50, 50, 50],
+            ),
+            (
+                "air shield in melee",
+                Strike {
+                    air_shield: Some(Expert),
+                    ..BASE
+                },
+                [100, 100, 100],
+            ),
+            (
+                "basic stone skin",
+                Strike {
+                    stone_skin: Some(Basic),
+                    ..BASE
+                },
+                [92, 92, 92],
+            ),
+            (
+                "expert stone skin",
+                Strike {
+                    stone_skin: Some(Expert),
+                    ..BASE
+                },
+                [85, 85, 85],
+            ),
+            ("far shot", Strike { far: true, ..SHOT }, [50, 50, 50]),
+            (
+                "obstructed shot",
+                Strike {
+                    obstructed: true,
+                    ..SHOT
+                },
+                [50, 50, 50],
+            ),
+            (
+                "far and obstructed",
+                Strike {
+                    far: true,
+                    obstructed: true,
+                    ..SHOT
+                },
+                [25, 25, 25],
+            ),
+            (
+                "shooter in melee",
+                Strike {
+                    shooter_in_melee: true,
+                    ..BASE
+                },
+                [50, 50, 50],
+            ),
+            (
+                "far only counts for shots",
+                Strike { far: true, ..BASE },
+                [100, 100, 100],
+            ),
+            (
+                "reductions one after the other",
+                Strike {
+                    armorer: Some(Expert),
+                    shield: Some(Expert),
+                    ..BASE
+                },
+                [59, 59, 59],
+            ),
+            (
+                "at least 1",
+                Strike {
+                    count: 1,
+                    damage: (1, 1),
+                    attack: 0,
+                    defense: 100,
+                    ..BASE
+                },
+                [1, 1, 1],
+            ),
+        ];
+        for (name, strike, [min, max, expected]) in cases {
+            assert_eq!(
+                [
+                    strike.min_damage(),
+                    strike.max_damage(),
+                    strike.expected_damage()
+                ],
+                [min, max, expected],
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn hated_creatures() {
+        assert!(hates(ANGEL, DEVIL));
+        assert!(hates(DEVIL, ANGEL));
+        assert!(!hates(ANGEL, EFREET));
+        assert_eq!(ignored_defense(ANGEL), 0);
+    }
+
+    #[test]
+    fn kills_table() {
+        // damage, count, health, top_health, killed
+        let cases = [
+            (0, 10, 30, 30, 0),
+            (29, 10, 30, 30, 0),
+            (30, 10, 30, 30, 1),
+            (100, 10, 30, 30, 3),
+            (100, 10, 30, 10, 4),
+            (1000, 5, 30, 30, 5),
+            (100, 0, 30, 30, 0),
+        ];
+        for (damage, count, health, top_health, killed) in cases {
+            assert_eq!(
+                kills(damage, count, health, top_health),
+                killed,
+                "{damage} damage on {count}x{health}"
+            );
+        }
+    }
+
+    #[test]
+    fn estimate_kills() {
+        let estimate = RANGE.estimate(20, 25, 10);
+        assert_eq!(
+            estimate,
+            Estimate {
+                min: 100,
+                max: 300,
+                expected: 200,
+                min_kills: 4,
+                max_kills: 12,
+            }
+        );
+    }
+}
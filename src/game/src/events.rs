@@ -0,0 +1,142 @@
+use crate::buildings;
+use crate::state::*;
+use crate::turn::DWELLINGS;
+use resources::map_buildings::Buildings;
+use resources::map_structs::*;
+
+// Timed events placed by the map author, following the original game:
+// - map events give resources to the players they are set for,
+// - town events also give buildings and creatures to the town, and only
+//   happen while the town belongs to one of these players,
+// - an event happens on its first day and then every `next_occurrence` days,
+//   its message waits for the player until the interface shows it.
+
+/// Message of an event, waiting to be shown to the player
+#[derive(Debug, Clone)]
+pub struct EventMessage {
+    pub player: Player,
+    /// the town of a town event
+    pub town: Option<ObjectId>,
+    pub name: String,
+    pub message: String,
+}
+
+/// The event happens on the date. The first day of events is counted from 0,
+/// the repeat interval is 0 for events happening once.
+pub fn occurs_on(first_occurrence_at: u16, next_occurrence: u8, date: Date) -> bool {
+    let first = first_occurrence_at as u32 + 1;
+    if date.day == first {
+        return true;
+    }
+    next_occurrence != 0
+        && date.day > first
+        && (date.day - first).is_multiple_of(next_occurrence as u32)
+}
+
+impl PlayerState {
+    /// Add the resources, an event taking more than the player has leaves 0
+    pub fn give_resources(&mut self, pack: &ResourcePack) {
+        for (a, b) in self.resources.0.iter_mut().zip(pack.0) {
+            *a = (*a + b).max(0);
+        }
+    }
+}
+
+impl GameState {
+    fn affected_by_event(&self, player: Player, players: &[Player], human: bool, ai: bool) -> bool {
+        players.contains(&player)
+            && self
+                .player(player)
+                .is_some_and(|p| !p.eliminated && if p.human { human } else { ai })
+    }
+
+    /// Fire the map and town events of the current day
+    pub(crate) fn run_events(&mut self) {
+        let date = self.date;
+        let mut fired = Vec::new();
+        for e in self.events.iter() {
+            if !occurs_on(e.first_occurrence_at, e.next_occurrence, date) {
+                continue;
+            }
+            for p in self.players.iter() {
+                if self.affected_by_event(
+                    p.player,
+                    &e.players,
+                    e.human_affected,
+                    e.computer_affected,
+                ) {
+                    fired.push((p.player, e.resources, e.name.clone(), e.message.clone()));
+                }
+            }
+        }
+        for (player, resources, name, message) in fired {
+            if let Some(p) = self.player_mut(player) {
+                p.give_resources(&resources);
+            }
+            self.messages.push(EventMessage {
+                player,
+                town: None,
+                name,
+                message,
+            });
+        }
+
+        let mut fired = Vec::new();
+        for town in self.towns.values() {
+            let Some(owner) = town.owner else {
+                continue;
+            };
+            for e in town.events.iter() {
+                if occurs_on(e.first_occurrence_at, e.next_occurrence, date)
+                    && self.affected_by_event(
+                        owner,
+                        &e.players,
+                        e.human_affected,
+                        e.computer_affected,
+                    )
+                {
+                    fired.push((town.object, owner, e.clone()));
+                }
+            }
+        }
+        for (id, owner, e) in fired {
+            if let Some(p) = self.player_mut(owner) {
+                p.give_resources(&e.resources);
+            }
+            if let Some(town) = self.towns.get_mut(&id) {
+                let faction = town.faction;
+                for b in e.new_buildings.iter() {
+                    let b = faction.map_or(*b, |f| b.for_faction(f));
+                    if b != Buildings::Default && !town.buildings.contains(&b) {
+                        town.buildings.push(b);
+                    }
+                }
+                buildings::add_included(&mut town.buildings);
+                // creatures only come to the dwellings the town has
+                for (level, amount) in e.new_creatures_at.iter() {
+                    let Some((basic, upgraded)) = DWELLINGS.get(*level as usize) else {
+                        continue;
+                    };
+                    if town.buildings.contains(basic) || town.buildings.contains(upgraded) {
+                        town.available[*level as usize] += *amount as u32;
+                    }
+                }
+            }
+            self.messages.push(EventMessage {
+                player: owner,
+                town: Some(id),
+                name: e.name,
+                message: e.message,
+            });
+        }
+    }
+
+    /// Remove and return the event messages waiting for the player
+    pub fn take_messages(&mut self, player: Player) -> Vec<EventMessage> {
+        let (ret, rest) = std::mem::take(&mut self.messages)
+            .into_iter()
+            .partition(|m| m.player == player);
+        self.messages = rest;
+        ret
+    }
+}
@@ -0,0 +1,14 @@
+pub mod buildings;
+pub mod combat;
+pub mod creatures;
+pub mod damage;
+pub mod events;
+pub mod movement;
+pub mod pathfinding;
+pub mod recruit;
+pub mod rng;
+pub mod spells;
+pub mod state;
+#[cfg(test)]
+mod testing;
+pub mod turn;
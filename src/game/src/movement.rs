@@ -0,0 +1,299 @@
+use crate::creatures;
+use resources::map_structs::*;
+
+// Daily movement points of a hero, following the original game:
+// - on land they depend on the speed of the slowest creature in the army,
+//   on sea they are always the same,
+// - Logistics and Navigation add a percentage of that base,
+// - artifacts, Stables and Lighthouses add fixed amounts on top.
+
+/// Land movement points by the speed of the slowest creature, faster ones get the last value
+const LAND_MOVEMENT_BY_SPEED: [u32; 12] = [
+    1500, 1500, 1500, 1500, 1560, 1630, 1700, 1760, 1830, 1900, 1960, 2000,
+];
+
+pub const SEA_MOVEMENT: u32 = 1500;
+
+pub const LOGISTICS: SecSkillId = SecSkillId(2);
+pub const NAVIGATION: SecSkillId = SecSkillId(5);
+
+pub const EQUESTRIANS_GLOVES: ArtifactId = ArtifactId(70);
+pub const NECKLACE_OF_OCEAN_GUIDANCE: ArtifactId = ArtifactId(71);
+pub const BOOTS_OF_SPEED: ArtifactId = ArtifactId(98);
+pub const SEA_CAPTAINS_HAT: ArtifactId = ArtifactId(123);
+/// combines the Sea Captain's Hat and the Necklace of Ocean Guidance
+pub const ADMIRALS_HAT: ArtifactId = ArtifactId(136);
+
+const STABLES_BONUS: u32 = 400;
+const LIGHTHOUSE_BONUS: u32 = 500;
+
+/// Everything the daily movement points of a hero depend on
+#[derive(Debug, Default, Copy, Clone)]
+pub struct HeroMovementState<'a> {
+    pub army: &'a [CreatureSlot],
+    pub skills: &'a [SecSkill],
+    /// equipped artifacts
+    pub artifacts: &'a [ArtifactId],
+    /// the hero visited Stables this week
+    pub stables: bool,
+    /// Lighthouses owned by the hero's player
+    pub lighthouses: u32,
+}
+
+impl HeroMovementState<'_> {
+    fn skill_level(&self, skill: SecSkillId) -> Option<SecSkillLevel> {
+        self.skills.iter().find(|s| s.id == skill).map(|s| s.level)
+    }
+
+    fn has_artifact(&self, artifact: ArtifactId) -> bool {
+        self.artifacts.contains(&artifact)
+    }
+
+    fn artifact_bonus(&self, bonuses: &[(ArtifactId, u32)]) -> u32 {
+        bonuses
+            .iter()
+            .filter(|(a, _)| self.has_artifact(*a))
+            .map(|(_, bonus)| bonus)
+            .sum()
+    }
+}
+
+/// Speed of the slowest creature in the army, None for an empty army
+pub fn slowest_speed(army: &[CreatureSlot]) -> Option<u32> {
+    army.iter()
+        .filter(|s| s.amount > 0)
+        .filter_map(|s| s.creature.and_then(creatures::creature_stats))
+        .map(|s| s.speed)
+        .min()
+}
+
+fn percent(value: u32, bonus: u32) -> u32 {
+    value * (100 + bonus) / 100
+}
+
+/// Movement points a hero gets every day on land
+pub fn land_movement_points(hero: &HeroMovementState) -> u32 {
+    let speed = slowest_speed(hero.army).unwrap_or(0) as usize;
+    let base = LAND_MOVEMENT_BY_SPEED[speed.min(LAND_MOVEMENT_BY_SPEED.len() - 1)];
+    let logistics = match hero.skill_level(LOGISTICS) {
+        None => 0,
+        Some(SecSkillLevel::Basic) => 10,
+        Some(SecSkillLevel::Advanced) => 20,
+        Some(SecSkillLevel::Expert) => 30,
+    };
+    let mut ret = percent(base, logistics);
+    ret += hero.artifact_bonus(&[(BOOTS_OF_SPEED, 600), (EQUESTRIANS_GLOVES, 300)]);
+    if hero.stables {
+        ret += STABLES_BONUS;
+    }
+    ret
+}
+
+/// Movement points a hero gets every day on sea
+pub fn sea_movement_points(hero: &HeroMovementState) -> u32 {
+    let navigation = match hero.skill_level(NAVIGATION) {
+        None => 0,
+        Some(SecSkillLevel::Basic) => 50,
+        Some(SecSkillLevel::Advanced) => 100,
+        Some(SecSkillLevel::Expert) => 150,
+    };
+    let mut ret = percent(SEA_MOVEMENT, navigation);
+    ret += hero.artifact_bonus(&[
+        (SEA_CAPTAINS_HAT, 500),
+        (NECKLACE_OF_OCEAN_GUIDANCE, 1000),
+        (ADMIRALS_HAT, 1500),
+    ]);
+    ret + hero.lighthouses * LIGHTHOUSE_BONUS
+}
+
+/// Terrain the whole army moves on without penalty. Neutral creatures don't
+/// matter, creatures of towns with different native terrains cancel it.
+pub fn army_native_terrain(army: &[CreatureSlot]) -> Option<Surface> {
+    let mut ret = None;
+    for s in army.iter().filter(|s| s.amount > 0) {
+        let Some(town) = s
+            .creature
+            .and_then(creatures::creature_stats)
+            .and_then(|c| c.town)
+        else {
+            continue;
+        };
+        let terrain = creatures::native_terrain(town);
+        match ret {
+            None => ret = Some(terrain),
+            Some(t) if t != terrain => return None,
+            Some(_) => {}
+        }
+    }
+    ret
+}
+
+/// Movement points left after boarding or leaving a boat with a step of the given cost.
+/// It takes all movement points, unless the hero wears the Admiral's Hat: then
+/// the rest is converted between the land and sea movement points.
+pub fn after_boat_step(
+    hero: &HeroMovementState,
+    remaining: u32,
+    step_cost: u32,
+    boarding: bool,
+) -> u32 {
+    if !hero.has_artifact(ADMIRALS_HAT) {
+        return 0;
+    }
+    let (land, sea) = (land_movement_points(hero), sea_movement_points(hero));
+    let (from, to) = if boarding { (land, sea) } else { (sea, land) };
+    (remaining.saturating_sub(step_cost) as u64 * to as u64 / from as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one stack of a creature with each speed
+    fn army(speeds: &[u32]) -> Vec<CreatureSlot> {
+        speeds
+            .iter()
+            .enumerate()
+            .map(|(i, speed)| {
+                let creature = (0..200)
+                    .map(CreatureId)
+                    .find(|c| creatures::creature_stats(*c).is_some_and(|s| s.speed == *speed))
+                    .expect("no creature with that speed");
+                CreatureSlot {
+                    slot_num: i as u8,
+                    creature: Some(creature),
+                    amount: 1,
+                }
+            })
+            .collect()
+    }
+
+    fn skill(id: SecSkillId, level: SecSkillLevel) -> SecSkill {
+        SecSkill { id, level }
+    }
+
+    #[test]
+    fn land_movement_by_speed() {
+        let cases = [
+            (3, 1500),
+            (4, 1560),
+            (5, 1630),
+            (6, 1700),
+            (7, 1760),
+            (8, 1830),
+            (9, 1900),
+            (10, 1960),
+            (11, 2000),
+            (12, 2000),
+            (21, 2000),
+        ];
+        for (speed, expected) in cases {
+            let army = army(&[speed]);
+            let hero = HeroMovementState {
+                army: &army,
+                ..Default::default()
+            };
+            assert_eq!(land_movement_points(&hero), expected, "speed {speed}");
+        }
+    }
+
+    #[test]
+    fn slowest_creature_counts() {
+        let army = army(&[12, 5, 9]);
+        let hero = HeroMovementState {
+            army: &army,
+            ..Default::default()
+        };
+        assert_eq!(slowest_speed(&army), Some(5));
+        assert_eq!(land_movement_points(&hero), 1630);
+        assert_eq!(slowest_speed(&[]), None);
+    }
+
+    #[test]
+    fn land_movement_bonuses() {
+        let army = army(&[11]);
+        let cases = [
+            (Some(SecSkillLevel::Basic), &[][..], false, 2200),
+            (Some(SecSkillLevel::Advanced), &[], false, 2400),
+            (Some(SecSkillLevel::Expert), &[], false, 2600),
+            (None, &[BOOTS_OF_SPEED], false, 2600),
+            (None, &[EQUESTRIANS_GLOVES], false, 2300),
+            (None, &[], true, 2400),
+            // the percentage only applies to the base
+            (
+                Some(SecSkillLevel::Expert),
+                &[BOOTS_OF_SPEED, EQUESTRIANS_GLOVES],
+                true,
+                3900,
+            ),
+        ];
+        for (logistics, artifacts, stables, expected) in cases {
+            let skills = logistics
+                .map(|l| vec![skill(LOGISTICS, l)])
+                .unwrap_or_default();
+            let hero = HeroMovementState {
+                army: &army,
+                skills: &skills,
+                artifacts,
+                stables,
+                ..Default::default()
+            };
+            assert_eq!(
+                land_movement_points(&hero),
+                expected,
+                "{logistics:?} {artifacts:?} {stables}"
+            );
+        }
+    }
+
+    #[test]
+    fn sea_movement_bonuses() {
+        let cases = [
+            (None, &[][..], 0, 1500),
+            (Some(SecSkillLevel::Basic), &[], 0, 2250),
+            (Some(SecSkillLevel::Advanced), &[], 0, 3000),
+            (Some(SecSkillLevel::Expert), &[], 0, 3750),
+            (None, &[], 2, 2500),
+            (None, &[SEA_CAPTAINS_HAT], 0, 2000),
+            (None, &[NECKLACE_OF_OCEAN_GUIDANCE], 0, 2500),
+            (None, &[ADMIRALS_HAT], 0, 3000),
+            (Some(SecSkillLevel::Expert), &[ADMIRALS_HAT], 1, 5750),
+        ];
+        for (navigation, artifacts, lighthouses, expected) in cases {
+            let skills = navigation
+                .map(|l| vec![skill(NAVIGATION, l)])
+                .unwrap_or_default();
+            let hero = HeroMovementState {
+                skills: &skills,
+                artifacts,
+                lighthouses,
+                ..Default::default()
+            };
+            assert_eq!(
+                sea_movement_points(&hero),
+                expected,
+                "{navigation:?} {artifacts:?} {lighthouses}"
+            );
+        }
+    }
+
+    #[test]
+    fn boat_steps() {
+        let army = army(&[11]);
+        let hero = HeroMovementState {
+            army: &army,
+            ..Default::default()
+        };
+        assert_eq!(after_boat_step(&hero, 1800, 100, true), 0);
+        assert_eq!(after_boat_step(&hero, 1800, 100, false), 0);
+
+        // 2000 on land, 3000 on sea
+        let hero = HeroMovementState {
+            artifacts: &[ADMIRALS_HAT],
+            ..hero
+        };
+        assert_eq!(after_boat_step(&hero, 1100, 100, true), 1500);
+        assert_eq!(after_boat_step(&hero, 1600, 100, false), 1000);
+        assert_eq!(after_boat_step(&hero, 50, 100, true), 0);
+    }
+}
@@ -0,0 +1,444 @@
+use resources::map_passability::{self, Passability, TileState};
+use resources::map_structs::*;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap};
+
+// Hero movement on the adventure map, with the costs of the original game:
+// - a step costs the movement points of the surface the hero leaves,
+//   or of the slower road when both tiles have one,
+// - diagonal steps cost sqrt(2) times more; a diagonal step that doesn't
+//   fit into the points left but would fit as a straight one is made and
+//   takes all of them,
+// - Pathfinding reduces the rough terrain penalty, never below 100,
+//   on the native terrain of the army there is no penalty,
+// - visitable objects and the zone of control of monsters end the movement,
+// - points left at the end of a day are lost, a step that doesn't fit
+//   into the rest of the day is made the next day.
+
+/// Cost of a step on plain terrain, the minimum of any surface
+pub const BASE_MOVEMENT_COST: u32 = 100;
+
+fn pathfinding_discount(level: Option<SecSkillLevel>) -> u32 {
+    match level {
+        None => 0,
+        Some(SecSkillLevel::Basic) => 25,
+        Some(SecSkillLevel::Advanced) => 50,
+        Some(SecSkillLevel::Expert) => 75,
+    }
+}
+
+// straight steps first, of equally good paths the one found first wins
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (0, -1),
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (-1, -1),
+    (1, -1),
+    (1, 1),
+    (-1, 1),
+];
+
+fn is_diagonal(from: MapCoord, to: MapCoord) -> bool {
+    from.x != to.x && from.y != to.y
+}
+
+/// One step of a path
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub to: MapCoord,
+    /// movement points of the step
+    pub cost: u32,
+    /// 0 for the current day
+    pub turn: u32,
+    /// movement points left after the step
+    pub movement_left: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    pub start: MapCoord,
+    pub steps: Vec<Step>,
+}
+
+impl Path {
+    pub fn destination(&self) -> MapCoord {
+        self.steps.last().map_or(self.start, |s| s.to)
+    }
+
+    /// Days needed after the current one
+    pub fn turns(&self) -> u32 {
+        self.steps.last().map_or(0, |s| s.turn)
+    }
+
+    pub fn total_cost(&self) -> u32 {
+        self.steps.iter().map(|s| s.cost).sum()
+    }
+
+    /// The steps made on each day, starting with the current one.
+    /// A day without steps happens when the hero waits for full movement points.
+    pub fn segments(&self) -> Vec<&[Step]> {
+        let mut ret = Vec::new();
+        let mut rest = self.steps.as_slice();
+        for turn in 0..=self.turns() {
+            let n = rest.iter().take_while(|s| s.turn == turn).count();
+            let (segment, tail) = rest.split_at(n);
+            ret.push(segment);
+            rest = tail;
+        }
+        ret
+    }
+}
+
+/// Movement points of the hero for the path search
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Movement {
+    /// points the hero gets every day
+    pub daily: u32,
+    /// points left for the current day
+    pub remaining: u32,
+}
+
+// Search label: fewer days first, then more points left on the last one
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Label {
+    turn: u32,
+    left: u32,
+}
+
+impl Ord for Label {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.turn.cmp(&other.turn).then(other.left.cmp(&self.left))
+    }
+}
+
+impl PartialOrd for Label {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+type Tile = (usize, usize, usize);
+
+fn tile(c: MapCoord) -> Tile {
+    (c.z, c.x, c.y)
+}
+
+fn coord((z, x, y): Tile) -> MapCoord {
+    MapCoord { x, y, z }
+}
+
+pub struct Pathfinder<'a> {
+    map: &'a Map,
+    passability: Passability,
+    pathfinding: Option<SecSkillLevel>,
+    native_terrain: Option<Surface>,
+}
+
+impl<'a> Pathfinder<'a> {
+    pub fn new(map: &'a Map) -> Self {
+        Self {
+            map,
+            passability: Passability::from_map(map),
+            pathfinding: None,
+            native_terrain: None,
+        }
+    }
+
+    /// Level of the hero's Pathfinding skill
+    pub fn with_pathfinding(mut self, level: Option<SecSkillLevel>) -> Self {
+        self.pathfinding = level;
+        self
+    }
+
+    /// Terrain the hero's army moves on without penalty, see movement::army_native_terrain
+    pub fn with_native_terrain(mut self, terrain: Option<Surface>) -> Self {
+        self.native_terrain = terrain;
+        self
+    }
+
+    pub fn passability(&self) -> &Passability {
+        &self.passability
+    }
+
+    /// Movement points of a step to a neighbouring tile, None if the hero can't make it
+    pub fn step_cost(&self, from: MapCoord, to: MapCoord) -> Option<u32> {
+        self.step_costs(from, to).map(|(_, cost)| cost)
+    }
+
+    /// Movement points a step takes with `left` points, None if it has to wait
+    /// for the next day. A diagonal step that would fit as a straight one takes
+    /// all the points left.
+    pub fn step_cost_within(&self, from: MapCoord, to: MapCoord, left: u32) -> Option<u32> {
+        let (straight, cost) = self.step_costs(from, to)?;
+        if cost <= left {
+            Some(cost)
+        } else if straight <= left {
+            Some(left)
+        } else {
+            None
+        }
+    }
+
+    // costs of the step made straight and as it is
+    fn step_costs(&self, from: MapCoord, to: MapCoord) -> Option<(u32, u32)> {
+        if !self.map.contains(&from) || !self.map.contains(&to) {
+            return None;
+        }
+        if from.z != to.z || from.x.abs_diff(to.x) > 1 || from.y.abs_diff(to.y) > 1 || from == to {
+            return None;
+        }
+        if self.passability.get(to) == TileState::Blocked {
+            return None;
+        }
+        let a = &self.map.terrains[from.z][from.x][from.y];
+        let b = &self.map.terrains[to.z][to.x][to.y];
+        let cost = map_passability::step_cost(a, b, |surface| {
+            if self.native_terrain == Some(surface) {
+                return BASE_MOVEMENT_COST;
+            }
+            // leaving water or rock only happens from a boat or a start tile
+            map_passability::surface_cost(surface)
+                .unwrap_or(BASE_MOVEMENT_COST)
+                .saturating_sub(pathfinding_discount(self.pathfinding))
+                .max(BASE_MOVEMENT_COST)
+        });
+        Some(if is_diagonal(from, to) {
+            (cost, map_passability::diagonal_cost(cost))
+        } else {
+            (cost, cost)
+        })
+    }
+
+    // Tiles the hero can't walk through, only stop on
+    fn ends_movement(&self, c: MapCoord) -> bool {
+        matches!(
+            self.passability.get(c),
+            TileState::Visitable | TileState::Guarded
+        )
+    }
+
+    /// Cheapest path from `from` to `to`, split into days by the movement points.
+    /// The destination may be a visitable object or a guarded tile, entering it
+    /// ends the path; None if there is no way there.
+    pub fn find_path(&self, from: MapCoord, to: MapCoord, movement: Movement) -> Option<Path> {
+        if !self.map.contains(&from) || !self.map.contains(&to) {
+            return None;
+        }
+        if from == to {
+            return Some(Path {
+                start: from,
+                steps: Vec::new(),
+            });
+        }
+        let start = Label {
+            turn: 0,
+            left: movement.remaining,
+        };
+        let mut best: BTreeMap<Tile, (Label, Option<Step>, Tile)> = BTreeMap::new();
+        let mut queue = BinaryHeap::new();
+        best.insert(tile(from), (start, None, tile(from)));
+        queue.push(Reverse((start, tile(from))));
+        while let Some(Reverse((label, t))) = queue.pop() {
+            if best.get(&t).is_some_and(|(b, _, _)| *b != label) {
+                continue;
+            }
+            if t == tile(to) {
+                break;
+            }
+            let here = coord(t);
+            if here != from && self.ends_movement(here) {
+                continue;
+            }
+            for (dx, dy) in NEIGHBOURS {
+                let (Some(x), Some(y)) =
+                    (here.x.checked_add_signed(dx), here.y.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                let next = MapCoord { x, y, z: here.z };
+                let Some(full) = self.step_cost(here, next) else {
+                    continue;
+                };
+                let (cost, next_label) = match self.step_cost_within(here, next, label.left) {
+                    Some(cost) => (
+                        cost,
+                        Label {
+                            turn: label.turn,
+                            left: label.left - cost,
+                        },
+                    ),
+                    // a hero can always make one step a day, whatever it costs
+                    None => (
+                        full,
+                        Label {
+                            turn: label.turn + 1,
+                            left: movement.daily.saturating_sub(full),
+                        },
+                    ),
+                };
+                if best
+                    .get(&tile(next))
+                    .is_some_and(|(b, _, _)| *b <= next_label)
+                {
+                    continue;
+                }
+                let step = Step {
+                    to: next,
+                    cost,
+                    turn: next_label.turn,
+                    movement_left: next_label.left,
+                };
+                best.insert(tile(next), (next_label, Some(step), t));
+                queue.push(Reverse((next_label, tile(next))));
+            }
+        }
+
+        let mut steps = Vec::new();
+        let mut t = tile(to);
+        while let Some((_, Some(step), previous)) = best.get(&t) {
+            steps.push(*step);
+            t = *previous;
+        }
+        if steps.is_empty() {
+            return None;
+        }
+        steps.reverse();
+        Some(Path { start: from, steps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, map};
+    use resources::map_obj_type::{MonsterData, ObjectType};
+
+    fn road(map: &mut Map, x: usize, y: usize, road: RoadType) {
+        map.terrains[0][x][y].road_type = Some(road);
+    }
+
+    #[test]
+    fn path_split_into_days() {
+        let map = map(8, Surface::Grass);
+        let movement = Movement {
+            daily: 300,
+            remaining: 250,
+        };
+        let path = Pathfinder::new(&map)
+            .find_path(at(0, 0), at(5, 0), movement)
+            .unwrap();
+        assert_eq!(path.destination(), at(5, 0));
+        assert_eq!(path.total_cost(), 500);
+        assert_eq!(path.turns(), 1);
+        let days = path.segments().iter().map(|s| s.len()).collect::<Vec<_>>();
+        assert_eq!(days, [2, 3]);
+        // the 50 points left on the first day are lost
+        let left = path
+            .steps
+            .iter()
+            .map(|s| s.movement_left)
+            .collect::<Vec<_>>();
+        assert_eq!(left, [150, 50, 200, 100, 0]);
+    }
+
+    #[test]
+    fn step_costs() {
+        let mut map = map(8, Surface::Rough);
+        road(&mut map, 0, 0, RoadType::Cobblestone);
+        road(&mut map, 1, 0, RoadType::Cobblestone);
+        road(&mut map, 2, 0, RoadType::Dirt);
+        let finder = Pathfinder::new(&map);
+        assert_eq!(finder.step_cost(at(0, 0), at(1, 0)), Some(50));
+        // the slower road of the two
+        assert_eq!(finder.step_cost(at(1, 0), at(2, 0)), Some(75));
+        assert_eq!(finder.step_cost(at(2, 0), at(1, 0)), Some(75));
+        // a road on one tile only doesn't count
+        assert_eq!(finder.step_cost(at(2, 0), at(3, 0)), Some(125));
+        assert_eq!(finder.step_cost(at(3, 1), at(4, 1)), Some(125));
+        assert_eq!(finder.step_cost(at(3, 1), at(4, 2)), Some(176));
+        assert_eq!(finder.step_cost(at(3, 1), at(5, 1)), None);
+
+        let finder = Pathfinder::new(&map).with_pathfinding(Some(SecSkillLevel::Basic));
+        assert_eq!(finder.step_cost(at(3, 1), at(4, 1)), Some(100));
+        let finder = Pathfinder::new(&map).with_native_terrain(Some(Surface::Rough));
+        assert_eq!(finder.step_cost(at(3, 1), at(4, 1)), Some(100));
+    }
+
+    #[test]
+    fn road_preferred_over_rough() {
+        let mut map = map(8, Surface::Rough);
+        for x in 0..5 {
+            road(&mut map, x, 2, RoadType::Cobblestone);
+        }
+        road(&mut map, 2, 2, RoadType::Dirt);
+        let movement = Movement {
+            daily: 1500,
+            remaining: 1500,
+        };
+        let path = Pathfinder::new(&map)
+            .find_path(at(0, 2), at(4, 2), movement)
+            .unwrap();
+        assert!(path.steps.iter().all(|s| s.to.y == 2));
+        assert_eq!(path.total_cost(), 50 + 75 + 75 + 50);
+
+        let path = Pathfinder::new(&map)
+            .find_path(at(0, 5), at(4, 5), movement)
+            .unwrap();
+        assert_eq!(path.total_cost(), 4 * 125);
+    }
+
+    #[test]
+    fn diagonal_step_takes_the_rest() {
+        let map = map(8, Surface::Grass);
+        let finder = Pathfinder::new(&map);
+        assert_eq!(finder.step_cost(at(0, 0), at(1, 1)), Some(141));
+        assert_eq!(finder.step_cost_within(at(0, 0), at(1, 1), 200), Some(141));
+        assert_eq!(finder.step_cost_within(at(0, 0), at(1, 1), 120), Some(120));
+        assert_eq!(finder.step_cost_within(at(0, 0), at(1, 1), 90), None);
+        assert_eq!(finder.step_cost_within(at(0, 0), at(1, 0), 90), None);
+
+        let movement = Movement {
+            daily: 1500,
+            remaining: 120,
+        };
+        let path = finder.find_path(at(0, 0), at(1, 1), movement).unwrap();
+        assert_eq!(
+            path.steps,
+            [Step {
+                to: at(1, 1),
+                cost: 120,
+                turn: 0,
+                movement_left: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn monsters_stop_the_hero() {
+        let mut map = map(12, Surface::Grass);
+        map.objects.push(Object {
+            position: at(5, 5),
+            obj_templ_id: 0,
+            obj_type: ObjectType::Monster(MonsterData::default()),
+        });
+        let finder = Pathfinder::new(&map);
+        let movement = Movement {
+            daily: 1500,
+            remaining: 1500,
+        };
+        // around the zone of control
+        let path = finder.find_path(at(5, 2), at(5, 8), movement).unwrap();
+        let guarded = |c: MapCoord| (4..=6).contains(&c.x) && (4..=6).contains(&c.y);
+        assert!(path.steps.iter().all(|s| !guarded(s.to)));
+        assert!(path.total_cost() > 600);
+
+        // into it, the path ends on the first guarded tile
+        let path = finder.find_path(at(5, 2), at(5, 4), movement).unwrap();
+        assert_eq!(path.steps.len(), 2);
+        // the monster is attacked from a tile next to it
+        assert!(finder.find_path(at(5, 2), at(5, 5), movement).is_none());
+        let path = finder.find_path(at(5, 4), at(5, 5), movement).unwrap();
+        assert_eq!(path.steps.len(), 1);
+    }
+}
@@ -0,0 +1,489 @@
+use crate::creatures;
+use crate::state::*;
+use crate::turn::DWELLINGS;
+use resources::map_obj_type::{ObjectType, RandomDwellingData};
+use resources::map_structs::*;
+use std::fmt;
+
+// Recruiting creatures, following the original game:
+// - towns sell the creatures of their built dwellings, the upgraded creature
+//   needs the upgraded dwelling and both come from the pool of the level,
+// - adventure map dwellings sell to the heroes of the player who flagged them,
+//   a hero flags a dwelling by visiting it,
+// - an army has 7 slots, creatures join the slot of their kind or a free one.
+
+pub const ARMY_SLOTS: u8 = 7;
+
+/// A creature the town sells
+#[derive(Debug, Copy, Clone)]
+pub struct Recruit {
+    /// dwelling level 1-7
+    pub level: u8,
+    pub creature: CreatureId,
+    pub available: u32,
+    /// cost of one creature
+    pub cost: ResourcePack,
+}
+
+/// Army the recruited creatures join
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RecruitTarget {
+    Hero(ObjectId),
+    /// the garrison of a town
+    Garrison(ObjectId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecruitError {
+    UnknownDwelling,
+    UnknownTarget,
+    /// the dwelling or the army belongs to someone else
+    NotOwner,
+    /// the dwelling doesn't sell this creature
+    NotAvailable,
+    /// more creatures than available, or none at all
+    NotEnoughCreatures,
+    NotEnoughResources,
+    NoFreeSlot,
+}
+
+impl fmt::Display for RecruitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownDwelling => write!(f, "no such town or dwelling"),
+            Self::UnknownTarget => write!(f, "no such hero or town"),
+            Self::NotOwner => write!(f, "not owned by the player"),
+            Self::NotAvailable => write!(f, "the creature isn't sold here"),
+            Self::NotEnoughCreatures => write!(f, "not enough creatures to recruit"),
+            Self::NotEnoughResources => write!(f, "not enough resources"),
+            Self::NoFreeSlot => write!(f, "no free slot in the army"),
+        }
+    }
+}
+
+/// The creature can join the army: it has a slot of the creature or a free one
+pub fn can_join(army: &[CreatureSlot], creature: CreatureId) -> bool {
+    let used = army
+        .iter()
+        .filter(|s| s.amount > 0 && s.creature.is_some())
+        .collect::<Vec<_>>();
+    used.iter().any(|s| s.creature == Some(creature)) || used.len() < ARMY_SLOTS as usize
+}
+
+/// Add the creatures to the army: to the slot of the creature, to an empty
+/// slot or to the first free slot number. False when the army is full.
+pub fn add_to_army(army: &mut Vec<CreatureSlot>, creature: CreatureId, amount: u32) -> bool {
+    if !can_join(army, creature) {
+        return false;
+    }
+    if let Some(s) = army
+        .iter_mut()
+        .find(|s| s.amount > 0 && s.creature == Some(creature))
+    {
+        s.amount += amount;
+        return true;
+    }
+    if let Some(s) = army
+        .iter_mut()
+        .find(|s| s.amount == 0 || s.creature.is_none())
+    {
+        s.creature = Some(creature);
+        s.amount = amount;
+        return true;
+    }
+    let Some(slot_num) = (0..ARMY_SLOTS).find(|n| army.iter().all(|s| s.slot_num != *n)) else {
+        return false;
+    };
+    army.push(CreatureSlot {
+        slot_num,
+        creature: Some(creature),
+        amount,
+    });
+    true
+}
+
+/// Cost of the creatures, None for unknown creatures
+pub fn recruit_cost(creature: CreatureId, amount: u32) -> Option<ResourcePack> {
+    let cost = creatures::creature_stats(creature)?.cost;
+    Some(ResourcePack(cost.0.map(|c| c * amount as i32)))
+}
+
+impl GameState {
+    /// Creatures the town sells, by dwelling level
+    pub fn town_recruits(&self, town: ObjectId) -> Vec<Recruit> {
+        let mut ret = Vec::new();
+        let Some((t, faction)) = self
+            .towns
+            .get(&town)
+            .and_then(|t| t.faction.map(|f| (t, f)))
+        else {
+            return ret;
+        };
+        for (level, (basic, upgraded)) in DWELLINGS.iter().enumerate() {
+            let built = [basic, upgraded].map(|b| t.buildings.contains(b));
+            let kinds = creatures::town_creatures(faction, level as u8 + 1);
+            for (creature, built) in kinds.iter().zip(built) {
+                if !built {
+                    continue;
+                }
+                let Some(cost) = recruit_cost(*creature, 1) else {
+                    continue;
+                };
+                ret.push(Recruit {
+                    level: level as u8 + 1,
+                    creature: *creature,
+                    available: t.available[level],
+                    cost,
+                });
+            }
+        }
+        ret
+    }
+
+    fn army_mut(
+        &mut self,
+        target: RecruitTarget,
+    ) -> Option<(Option<Player>, &mut Vec<CreatureSlot>)> {
+        match target {
+            RecruitTarget::Hero(id) => self.heroes.get_mut(&id).map(|h| (h.owner, &mut h.army)),
+            RecruitTarget::Garrison(id) => {
+                self.towns.get_mut(&id).map(|t| (t.owner, &mut t.garrison))
+            }
+        }
+    }
+
+    // checks common to towns and dwellings, the cost of the creatures
+    fn check_recruit(
+        &mut self,
+        buyer: Player,
+        creature: CreatureId,
+        amount: u32,
+        available: u32,
+        target: RecruitTarget,
+    ) -> Result<ResourcePack, RecruitError> {
+        if amount == 0 || amount > available {
+            return Err(RecruitError::NotEnoughCreatures);
+        }
+        let cost = recruit_cost(creature, amount).ok_or(RecruitError::NotAvailable)?;
+        if !self.player(buyer).is_some_and(|p| p.can_afford(&cost)) {
+            return Err(RecruitError::NotEnoughResources);
+        }
+        let (owner, army) = self.army_mut(target).ok_or(RecruitError::UnknownTarget)?;
+        if owner != Some(buyer) {
+            return Err(RecruitError::NotOwner);
+        }
+        if !can_join(army, creature) {
+            return Err(RecruitError::NoFreeSlot);
+        }
+        Ok(cost)
+    }
+
+    fn pay_and_join(
+        &mut self,
+        buyer: Player,
+        cost: &ResourcePack,
+        creature: CreatureId,
+        amount: u32,
+        target: RecruitTarget,
+    ) {
+        if let Some(p) = self.player_mut(buyer) {
+            p.pay(cost);
+        }
+        if let Some((_, army)) = self.army_mut(target) {
+            add_to_army(army, creature, amount);
+        }
+    }
+
+    /// Recruit creatures of the town's dwellings for its owner
+    pub fn recruit_in_town(
+        &mut self,
+        town: ObjectId,
+        creature: CreatureId,
+        amount: u32,
+        target: RecruitTarget,
+    ) -> Result<(), RecruitError> {
+        let t = self.towns.get(&town).ok_or(RecruitError::UnknownDwelling)?;
+        let buyer = t.owner.ok_or(RecruitError::NotOwner)?;
+        let recruit = self
+            .town_recruits(town)
+            .into_iter()
+            .find(|r| r.creature == creature)
+            .ok_or(RecruitError::NotAvailable)?;
+        let cost = self.check_recruit(buyer, creature, amount, recruit.available, target)?;
+        if let Some(t) = self.towns.get_mut(&town) {
+            t.available[recruit.level as usize - 1] -= amount;
+        }
+        self.pay_and_join(buyer, &cost, creature, amount, target);
+        Ok(())
+    }
+
+    /// A hero visiting an adventure map dwelling flags it for the hero's player
+    pub fn visit_dwelling(
+        &mut self,
+        hero: ObjectId,
+        dwelling: ObjectId,
+    ) -> Result<(), RecruitError> {
+        let owner = self
+            .heroes
+            .get(&hero)
+            .ok_or(RecruitError::UnknownTarget)?
+            .owner;
+        if !self.dwellings.contains_key(&dwelling) {
+            return Err(RecruitError::UnknownDwelling);
+        }
+        let obj = self
+            .objects
+            .get_mut(&dwelling)
+            .ok_or(RecruitError::UnknownDwelling)?;
+        obj.owner = owner;
+        if let Some(o) = obj.obj_type.owner_mut() {
+            *o = owner.map(Ownership::Player);
+        }
+        Ok(())
+    }
+
+    /// Recruit creatures of an adventure map dwelling for the hero's army
+    pub fn recruit_in_dwelling(
+        &mut self,
+        dwelling: ObjectId,
+        creature: CreatureId,
+        amount: u32,
+        hero: ObjectId,
+    ) -> Result<(), RecruitError> {
+        let owner = self
+            .objects
+            .get(&dwelling)
+            .ok_or(RecruitError::UnknownDwelling)?
+            .owner;
+        let buyer = self
+            .heroes
+            .get(&hero)
+            .ok_or(RecruitError::UnknownTarget)?
+            .owner
+            .ok_or(RecruitError::NotOwner)?;
+        if owner != Some(buyer) {
+            return Err(RecruitError::NotOwner);
+        }
+        let available = self
+            .dwellings
+            .get(&dwelling)
+            .ok_or(RecruitError::UnknownDwelling)?
+            .creatures
+            .iter()
+            .find(|(c, _)| *c == creature)
+            .map(|(_, n)| *n)
+            .ok_or(RecruitError::NotAvailable)?;
+        let target = RecruitTarget::Hero(hero);
+        let cost = self.check_recruit(buyer, creature, amount, available, target)?;
+        if let Some(d) = self.dwellings.get_mut(&dwelling) {
+            for (_, n) in d.creatures.iter_mut().filter(|(c, _)| *c == creature) {
+                *n -= amount;
+            }
+        }
+        self.pay_and_join(buyer, &cost, creature, amount, target);
+        Ok(())
+    }
+
+    /// Turn a random dwelling into the one creature dwelling it stands for.
+    /// The faction is the one of the linked town, of the object subid or one
+    /// of the allowed factions; the level is the subid or within the range.
+    pub(crate) fn resolve_random_dwelling(
+        &mut self,
+        object: ObjectId,
+        d: &RandomDwellingData,
+        town: Option<Town>,
+    ) {
+        let Some(obj) = self.objects.get(&object) else {
+            return;
+        };
+        let (level, faction) = match obj.obj_type {
+            ObjectType::RandomDwellingLvl(_) => (Some(obj.subid), None),
+            ObjectType::RandomDwellingFaction(_) => {
+                (None, ALL_TOWNS.get(obj.subid as usize).copied())
+            }
+            _ => (None, None),
+        };
+        // there is no creature data for the factions added after Shadow of Death
+        let original = &ALL_TOWNS[..9];
+        let mut factions = match (town, faction, &d.factions) {
+            (Some(t), _, _) | (None, Some(t), _) => vec![t],
+            (None, None, Some(f)) => f.clone(),
+            _ => Vec::new(),
+        };
+        factions.retain(|f| original.contains(f));
+        if factions.is_empty() {
+            factions = original.to_vec();
+        }
+        let faction = *self.rng.choose(&factions).unwrap_or(&Town::Castle);
+        // the subid is 0 based, the range is stored as levels 1-7
+        let level = level.unwrap_or_else(|| {
+            let min = d.rnd_info_min_lev.unwrap_or(1).clamp(1, 7) as u32 - 1;
+            let max = d.rnd_info_max_lev.unwrap_or(7).clamp(1, 7) as u32 - 1;
+            self.rng.range(min, max)
+        });
+        let creature = creatures::town_creatures(faction, level.min(6) as u8 + 1)
+            .first()
+            .copied();
+        let subid = creature.and_then(creatures::dwelling_subid);
+        if let (Some(creature), Some(subid), Some(obj)) =
+            (creature, subid, self.objects.get_mut(&object))
+        {
+            obj.obj_type = ObjectType::CreatureGenerator1 { owner: d.owner };
+            obj.subid = subid;
+            self.add_dwelling(object, vec![creature]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_object, at, map};
+    use resources::map_obj_type::HeroData;
+
+    // creature of a Castle random dwelling with the level range of the file
+    fn resolve(min: u8, max: u8, seed: u64) -> CreatureId {
+        let mut map = map(8, Surface::Grass);
+        let d = RandomDwellingData {
+            rnd_info_min_lev: Some(min),
+            rnd_info_max_lev: Some(max),
+            ..Default::default()
+        };
+        let i = add_object(
+            &mut map,
+            at(2, 2),
+            218,
+            0,
+            ObjectType::RandomDwellingFaction(d),
+        );
+        let settings = GameSettings {
+            seed,
+            ..GameSettings::for_map(&map)
+        };
+        let state = GameState::from_map(&map, settings);
+        let id = ObjectId(i as u32);
+        assert!(matches!(
+            state.objects[&id].obj_type,
+            ObjectType::CreatureGenerator1 { .. }
+        ));
+        state.dwellings[&id].creatures[0].0
+    }
+
+    // slot number, creature, amount
+    type Slot = (u8, Option<u16>, u32);
+
+    fn army(slots: &[Slot]) -> Vec<CreatureSlot> {
+        slots
+            .iter()
+            .map(|(slot_num, creature, amount)| CreatureSlot {
+                slot_num: *slot_num,
+                creature: creature.map(CreatureId),
+                amount: *amount,
+            })
+            .collect()
+    }
+
+    fn slots(army: &[CreatureSlot]) -> Vec<Slot> {
+        army.iter()
+            .map(|s| (s.slot_num, s.creature.map(|c| c.0), s.amount))
+            .collect()
+    }
+
+    #[test]
+    fn join_the_slot_of_the_creature() {
+        let mut a = army(&[(0, Some(0), 5), (3, Some(2), 4)]);
+        assert!(add_to_army(&mut a, CreatureId(2), 6));
+        assert_eq!(slots(&a), [(0, Some(0), 5), (3, Some(2), 10)]);
+        // then the first free slot number
+        assert!(add_to_army(&mut a, CreatureId(4), 1));
+        assert_eq!(
+            slots(&a),
+            [(0, Some(0), 5), (3, Some(2), 10), (1, Some(4), 1)]
+        );
+    }
+
+    #[test]
+    fn reuse_empty_slots() {
+        let mut a = army(&[(0, Some(0), 5), (1, Some(2), 0), (2, None, 3)]);
+        assert!(add_to_army(&mut a, CreatureId(4), 2));
+        assert!(add_to_army(&mut a, CreatureId(6), 2));
+        assert_eq!(
+            slots(&a),
+            [(0, Some(0), 5), (1, Some(4), 2), (2, Some(6), 2)]
+        );
+    }
+
+    #[test]
+    fn full_army() {
+        let full = (0..ARMY_SLOTS)
+            .map(|i| (i, Some(2 * i as u16), 1))
+            .collect::<Vec<_>>();
+        let mut a = army(&full);
+        assert!(can_join(&a, CreatureId(6)));
+        assert!(!can_join(&a, CreatureId(14)));
+        assert!(!add_to_army(&mut a, CreatureId(14), 1));
+        assert_eq!(slots(&a), full);
+        assert!(add_to_army(&mut a, CreatureId(6), 1));
+        assert_eq!(slots(&a)[3], (3, Some(6), 2));
+
+        // an emptied slot makes room
+        a[5].amount = 0;
+        assert!(can_join(&a, CreatureId(14)));
+        assert!(add_to_army(&mut a, CreatureId(14), 1));
+        assert_eq!(slots(&a)[5], (5, Some(14), 1));
+    }
+
+    #[test]
+    fn recruit_none() {
+        let mut map = map(8, Surface::Grass);
+        map.players.push(PlayerInfo {
+            player: Player::Red,
+            can_be_human: true,
+            ..Default::default()
+        });
+        let hero = HeroData {
+            owner: Some(Ownership::Player(Player::Red)),
+            ..Default::default()
+        };
+        let hero = add_object(&mut map, at(3, 3), 34, 0, ObjectType::Hero(hero));
+        let dwelling = add_object(
+            &mut map,
+            at(3, 4),
+            17,
+            0,
+            ObjectType::CreatureGenerator1 { owner: None },
+        );
+        let mut state = GameState::from_map(&map, GameSettings::for_map(&map));
+        let (hero, dwelling) = (ObjectId(hero as u32), ObjectId(dwelling as u32));
+        state.visit_dwelling(hero, dwelling).unwrap();
+        let creature = state.dwellings[&dwelling].creatures[0].0;
+        assert_eq!(
+            state.recruit_in_dwelling(dwelling, creature, 0, hero),
+            Err(RecruitError::NotEnoughCreatures)
+        );
+        assert_eq!(
+            state.recruit_in_dwelling(dwelling, creature, 1, hero),
+            Ok(())
+        );
+        assert_eq!(slots(&state.heroes[&hero].army), [(0, Some(creature.0), 1)]);
+    }
+
+    #[test]
+    fn random_dwelling_levels() {
+        // Pikeman, Archer, Angel
+        assert_eq!(resolve(1, 1, 0), CreatureId(0));
+        assert_eq!(resolve(2, 2, 0), CreatureId(2));
+        assert_eq!(resolve(7, 7, 0), CreatureId(12));
+        // out of range values are clamped to 1-7
+        assert_eq!(resolve(0, 0, 0), CreatureId(0));
+        assert_eq!(resolve(9, 9, 0), CreatureId(12));
+
+        assert_eq!(resolve(1, 2, 7), resolve(1, 2, 7));
+        let rolled = (0..32).map(|seed| resolve(1, 2, seed)).collect::<Vec<_>>();
+        assert!(rolled
+            .iter()
+            .all(|c| [CreatureId(0), CreatureId(2)].contains(c)));
+        assert!(rolled.contains(&CreatureId(0)));
+        assert!(rolled.contains(&CreatureId(2)));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::creatures;
+use crate::rng::Rng;
+use resources::map_structs::*;
+use Area::*;
+use School::*;
+use SpellKind::{Buff, Debuff, Summon};
+
+// Combat spells, following the original game:
+// - every spell belongs to one or more of the four schools and is cast with
+//   the best mastery the hero has in them, without the school skill the spell
+//   is cast unskilled, which mostly works like Basic,
+// - damage spells deal a base damage by mastery plus a multiple of the spell
+//   power and Sorcery adds a share; buffs and debuffs last spell power rounds,
+//   most of them hit every stack of a side at Expert,
+// - summoned elementals come in spell power times 2, 3 or 4,
+// - magic resistance lets a creature shrug off a spell, some creatures are
+//   immune to a school or to low level spells and Golems take less damage,
+// - the map can ban spells, and a hero with Eagle Eye may learn the spells
+//   the enemy cast in the battle.
+
+pub const FIRE_MAGIC: SecSkillId = SecSkillId(14);
+pub const AIR_MAGIC: SecSkillId = SecSkillId(15);
+pub const WATER_MAGIC: SecSkillId = SecSkillId(16);
+pub const EARTH_MAGIC: SecSkillId = SecSkillId(17);
+pub const EAGLE_EYE: SecSkillId = SecSkillId(11);
+pub const SORCERY: SecSkillId = SecSkillId(25);
+pub const RESISTANCE: SecSkillId = SecSkillId(26);
+
+pub const MAGIC_ARROW: SpellId = SpellId(15);
+pub const ICE_BOLT: SpellId = SpellId(16);
+pub const LIGHTNING_BOLT: SpellId = SpellId(17);
+pub const IMPLOSION: SpellId = SpellId(18);
+pub const FROST_RING: SpellId = SpellId(20);
+pub const FIREBALL: SpellId = SpellId(21);
+pub const INFERNO: SpellId = SpellId(22);
+pub const METEOR_SHOWER: SpellId = SpellId(23);
+pub const DEATH_RIPPLE: SpellId = SpellId(24);
+pub const DESTROY_UNDEAD: SpellId = SpellId(25);
+pub const ARMAGEDDON: SpellId = SpellId(26);
+pub const SHIELD: SpellId = SpellId(27);
+pub const AIR_SHIELD: SpellId = SpellId(28);
+pub const BLESS: SpellId = SpellId(41);
+pub const CURSE: SpellId = SpellId(42);
+pub const BLOODLUST: SpellId = SpellId(43);
+pub const PRECISION: SpellId = SpellId(44);
+pub const WEAKNESS: SpellId = SpellId(45);
+pub const STONE_SKIN: SpellId = SpellId(46);
+pub const PRAYER: SpellId = SpellId(48);
+pub const HASTE: SpellId = SpellId(53);
+pub const SLOW: SpellId = SpellId(54);
+pub const FIRE_ELEMENTAL: SpellId = SpellId(66);
+pub const EARTH_ELEMENTAL: SpellId = SpellId(67);
+pub const WATER_ELEMENTAL: SpellId = SpellId(68);
+pub const AIR_ELEMENTAL: SpellId = SpellId(69);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum School {
+    Air,
+    Earth,
+    Fire,
+    Water,
+}
+
+impl School {
+    pub fn skill(self) -> SecSkillId {
+        match self {
+            School::Air => AIR_MAGIC,
+            School::Earth => EARTH_MAGIC,
+            School::Fire => FIRE_MAGIC,
+            School::Water => WATER_MAGIC,
+        }
+    }
+}
+
+/// Stacks a spell hits
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Area {
+    /// the stack on the target hex
+    Single,
+    /// the stacks within the radius of the target hex
+    Ball(u32),
+    /// the stacks around the target hex, not the one on it
+    Ring,
+    /// every stack on the battlefield the spell affects
+    All,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpellKind {
+    /// spell power times `per_power` plus the base damage by mastery
+    Damage { per_power: u32, base: [u32; 4] },
+    /// lasts on a friendly stack, on all of them at Expert
+    Buff,
+    /// lasts on an enemy stack, on all of them at Expert
+    Debuff,
+    /// a new stack of the creature on the caster's side
+    Summon(CreatureId),
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SpellInfo {
+    pub spell: SpellId,
+    /// spell level 1-5
+    pub level: u8,
+    pub schools: &'static [School],
+    /// mana unskilled and with the school skill
+    pub cost: (u32, u32),
+    pub kind: SpellKind,
+    pub area: Area,
+}
+
+const fn spell(
+    spell: SpellId,
+    level: u8,
+    schools: &'static [School],
+    cost: (u32, u32),
+    kind: SpellKind,
+    area: Area,
+) -> SpellInfo {
+    SpellInfo {
+        spell,
+        level,
+        schools,
+        cost,
+        kind,
+        area,
+    }
+}
+
+const fn damage(per_power: u32, base: [u32; 4]) -> SpellKind {
+    SpellKind::Damage { per_power, base }
+}
+
+const ALL_SCHOOLS: &[School] = &[Air, Earth, Fire, Water];
+
+#[rustfmt::skip]
+const SPELLS: [SpellInfo; 26] = [
+    spell(MAGIC_ARROW, 1, ALL_SCHOOLS, (5, 4), damage(10, [10, 10, 20, 30]), Single),
+    spell(ICE_BOLT, 2, &[Water], (8, 6), damage(20, [10, 10, 20, 50]), Single),
+    spell(LIGHTNING_BOLT, 2, &[Air], (10, 8), damage(25, [10, 10, 20, 50]), Single),
+    spell(IMPLOSION, 5, &[Earth], (30, 25), damage(75, [100, 100, 200, 300]), Single),
+    spell(FROST_RING, 3, &[Water], (12, 9), damage(10, [15, 15, 30, 60]), Ring),
+    spell(FIREBALL, 3, &[Fire], (15, 12), damage(10, [15, 15, 30, 60]), Ball(1)),
+    spell(INFERNO, 4, &[Fire], (16, 12), damage(10, [20, 20, 40, 80]), Ball(2)),
+    spell(METEOR_SHOWER, 4, &[Earth], (16, 12), damage(25, [25, 25, 50, 100]), Ball(1)),
+    spell(DEATH_RIPPLE, 2, &[Earth], (10, 8), damage(5, [10, 10, 20, 30]), All),
+    spell(DESTROY_UNDEAD, 3, &[Air], (15, 12), damage(10, [10, 10, 20, 50]), All),
+    spell(ARMAGEDDON, 4, &[Fire], (24, 20), damage(50, [30, 30, 60, 120]), All),
+    spell(SHIELD, 1, &[Earth], (5, 4), Buff, Single),
+    spell(AIR_SHIELD, 3, &[Air], (12, 9), Buff, Single),
+    spell(BLESS, 1, &[Water], (5, 4), Buff, Single),
+    spell(CURSE, 1, &[Fire], (6, 5), Debuff, Single),
+    spell(BLOODLUST, 1, &[Fire], (5, 4), Buff, Single),
+    spell(PRECISION, 2, &[Air], (8, 6), Buff, Single),
+    spell(WEAKNESS, 2, &[Water], (8, 6), Debuff, Single),
+    spell(STONE_SKIN, 1, &[Earth], (5, 4), Buff, Single),
+    spell(PRAYER, 4, &[Water], (16, 12), Buff, Single),
+    spell(HASTE, 1, &[Air], (6, 5), Buff, Single),
+    spell(SLOW, 1, &[Earth], (6, 5), Debuff, Single),
+    spell(FIRE_ELEMENTAL, 5, &[Fire], (25, 20), Summon(CreatureId(114)), Single),
+    spell(EARTH_ELEMENTAL, 5, &[Earth], (25, 20), Summon(CreatureId(113)), Single),
+    spell(WATER_ELEMENTAL, 5, &[Water], (25, 20), Summon(CreatureId(115)), Single),
+    spell(AIR_ELEMENTAL, 5, &[Air], (25, 20), Summon(CreatureId(112)), Single),
+];
+
+/// Spells cancelling each other, casting one removes the other
+const OPPOSITES: [(SpellId, SpellId); 2] = [(BLESS, CURSE), (HASTE, SLOW)];
+
+/// Combat spells this engine knows, None for the others
+pub fn spell_info(spell: SpellId) -> Option<SpellInfo> {
+    SPELLS.iter().find(|s| s.spell == spell).copied()
+}
+
+/// The map doesn't ban the spell, `AllowedSpells::spells` lists the banned ones
+pub fn map_allows(allowed: &AllowedSpells, spell: SpellId) -> bool {
+    !allowed.spells.contains(&spell)
+}
+
+/// 0 unskilled up to 3 for Expert, to index the tables by mastery
+pub fn mastery_index(mastery: Option<SecSkillLevel>) -> usize {
+    match mastery {
+        None => 0,
+        Some(SecSkillLevel::Basic) => 1,
+        Some(SecSkillLevel::Advanced) => 2,
+        Some(SecSkillLevel::Expert) => 3,
+    }
+}
+
+pub fn skill_level(skills: &[SecSkill], skill: SecSkillId) -> Option<SecSkillLevel> {
+    skills.iter().find(|s| s.id == skill).map(|s| s.level)
+}
+
+/// Best mastery of the hero in the schools of the spell
+pub fn mastery(skills: &[SecSkill], info: &SpellInfo) -> Option<SecSkillLevel> {
+    info.schools
+        .iter()
+        .map(|s| skill_level(skills, s.skill()))
+        .max_by_key(|m| mastery_index(*m))
+        .flatten()
+}
+
+impl SpellInfo {
+    pub fn cost(&self, mastery: Option<SecSkillLevel>) -> u32 {
+        match mastery {
+            None => self.cost.0,
+            Some(_) => self.cost.1,
+        }
+    }
+
+    /// Buffs and debuffs hit all stacks of a side at Expert
+    pub fn is_mass(&self, mastery: Option<SecSkillLevel>) -> bool {
+        matches!(self.kind, Buff | Debuff) && mastery == Some(SecSkillLevel::Expert)
+    }
+
+    /// Damage to one stack before its resistances, Sorcery included
+    pub fn damage(
+        &self,
+        power: u32,
+        mastery: Option<SecSkillLevel>,
+        sorcery: Option<SecSkillLevel>,
+    ) -> u32 {
+        let SpellKind::Damage { per_power, base } = self.kind else {
+            return 0;
+        };
+        let sorcery = match sorcery {
+            None => 0,
+            Some(SecSkillLevel::Basic) => 5,
+            Some(SecSkillLevel::Advanced) => 10,
+            Some(SecSkillLevel::Expert) => 15,
+        };
+        (per_power * power + base[mastery_index(mastery)]) * (100 + sorcery) / 100
+    }
+
+    /// Creatures summoned by the spell
+    pub fn summoned(&self, power: u32, mastery: Option<SecSkillLevel>) -> u32 {
+        power * [2, 2, 3, 4][mastery_index(mastery)]
+    }
+
+    /// The spell can hit the creature: it isn't immune and the spell doesn't
+    /// pick its targets
+    pub fn affects(&self, creature: CreatureId) -> bool {
+        let undead = creatures::is_undead(creature);
+        match self.spell {
+            DEATH_RIPPLE if undead || creatures::is_non_living(creature) => return false,
+            DESTROY_UNDEAD if !undead => return false,
+            _ => (),
+        }
+        match creature.0 {
+            // Black Dragon, Magic Elemental
+            83 | 121 => false,
+            // Green Dragon, Red Dragon
+            26 | 82 => self.level > 3,
+            // Gold Dragon
+            27 => self.level > 4,
+            // Efreeti, Efreet Sultan, Fire Elemental, Energy Elemental, Firebird, Phoenix
+            52 | 53 | 114 | 129 | 130 | 131 => {
+                !self.schools.contains(&Fire) || self.spell == MAGIC_ARROW
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Percent chance the creature shrugs off a hostile spell
+pub fn magic_resistance(creature: CreatureId) -> u32 {
+    match creature.0 {
+        // Dwarf, Battle Dwarf
+        16 => 20,
+        17 => 40,
+        _ => 0,
+    }
+}
+
+/// Percent chance the Resistance skill of the hero adds for the army
+pub fn resistance_bonus(level: Option<SecSkillLevel>) -> u32 {
+    match level {
+        None => 0,
+        Some(SecSkillLevel::Basic) => 5,
+        Some(SecSkillLevel::Advanced) => 10,
+        Some(SecSkillLevel::Expert) => 20,
+    }
+}
+
+/// Percent of spell damage the creature doesn't take
+pub fn spell_damage_reduction(creature: CreatureId) -> u32 {
+    match creature.0 {
+        // Stone Golem, Iron Golem, Gold Golem, Diamond Golem
+        32 => 50,
+        33 => 75,
+        116 => 85,
+        117 => 95,
+        _ => 0,
+    }
+}
+
+/// A buff or debuff lasting on a stack
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ActiveSpell {
+    pub spell: SpellId,
+    pub mastery: Option<SecSkillLevel>,
+    /// rounds left, including the current one
+    pub rounds: u32,
+}
+
+/// Put the spell on the stack's spells, replacing the same spell and its opposite
+pub fn add_active(spells: &mut Vec<ActiveSpell>, spell: ActiveSpell) {
+    let opposite = OPPOSITES.iter().find_map(|(a, b)| match spell.spell {
+        s if s == *a => Some(*b),
+        s if s == *b => Some(*a),
+        _ => None,
+    });
+    spells.retain(|s| s.spell != spell.spell && Some(s.spell) != opposite);
+    spells.push(spell);
+}
+
+/// Mastery of the spell on the stack, unskilled casting counts as Basic
+pub fn active_mastery(spells: &[ActiveSpell], spell: SpellId) -> Option<SecSkillLevel> {
+    spells
+        .iter()
+        .find(|s| s.spell == spell)
+        .map(|s| s.mastery.unwrap_or(SecSkillLevel::Basic))
+}
+
+fn bonus(spells: &[ActiveSpell], spell: SpellId, by_mastery: [i32; 4]) -> i32 {
+    spells
+        .iter()
+        .find(|s| s.spell == spell)
+        .map_or(0, |s| by_mastery[mastery_index(s.mastery)])
+}
+
+/// Attack added by Bloodlust or Precision, Prayer and Weakness
+pub fn attack_bonus(spells: &[ActiveSpell], ranged: bool) -> i32 {
+    let boost = if ranged {
+        bonus(spells, PRECISION, [3, 3, 6, 6])
+    } else {
+        bonus(spells, BLOODLUST, [3, 3, 6, 6])
+    };
+    boost + bonus(spells, PRAYER, [2, 2, 4, 4]) - bonus(spells, WEAKNESS, [3, 3, 6, 6])
+}
+
+/// Defense added by Prayer, Stone Skin counts in the damage
+pub fn defense_bonus(spells: &[ActiveSpell]) -> i32 {
+    bonus(spells, PRAYER, [2, 2, 4, 4])
+}
+
+/// Speed with Haste, Prayer and Slow
+pub fn speed(spells: &[ActiveSpell], speed: u32) -> u32 {
+    let added = bonus(spells, HASTE, [3, 3, 5, 5]) + bonus(spells, PRAYER, [2, 2, 4, 4]);
+    let speed = (speed as i32 + added).max(0) as u32;
+    let slowed = bonus(spells, SLOW, [25, 25, 50, 50]) as u32;
+    speed * (100 - slowed) / 100
+}
+
+/// Spells a hero with Eagle Eye learns out of the ones the enemy cast
+pub fn eagle_eye(
+    level: Option<SecSkillLevel>,
+    cast: &[SpellId],
+    known: &[SpellId],
+    rng: &mut Rng,
+) -> Vec<SpellId> {
+    let (chance, max_level) = match level {
+        None => return Vec::new(),
+        Some(SecSkillLevel::Basic) => (40, 2),
+        Some(SecSkillLevel::Advanced) => (50, 3),
+        Some(SecSkillLevel::Expert) => (60, 4),
+    };
+    let mut ret = Vec::new();
+    for spell in cast {
+        if known.contains(spell) || ret.contains(spell) {
+            continue;
+        }
+        if spell_info(*spell).is_some_and(|i| i.level <= max_level) && rng.percent(chance) {
+            ret.push(*spell);
+        }
+    }
+    ret
+}
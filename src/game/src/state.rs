@@ -0,0 +1,471 @@
+use crate::events::EventMessage;
+use crate::movement::{self, HeroMovementState};
+use crate::rng::Rng;
+use crate::turn::WeekKind;
+use resources::map_buildings::Buildings;
+use resources::map_obj_type::{HeroData, ObjectType, TownData, TownEvent};
+use resources::map_passability;
+use resources::map_structs::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Runtime model of a game started from a map. It owns its data, the map is
+// only read when the game starts, and knows nothing about rendering.
+
+/// Handle of an adventure map object, never reused within a game
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId(pub u32);
+
+/// Game date, day 1 is the first day of the first week of the first month
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub day: u32,
+}
+
+pub const DAYS_PER_WEEK: u32 = 7;
+pub const WEEKS_PER_MONTH: u32 = 4;
+
+impl Date {
+    pub fn first() -> Self {
+        Self { day: 1 }
+    }
+
+    /// Day of the week, 1-7
+    pub fn day_of_week(&self) -> u32 {
+        (self.day - 1) % DAYS_PER_WEEK + 1
+    }
+
+    /// Week of the month, 1-4
+    pub fn week_of_month(&self) -> u32 {
+        (self.day - 1) / DAYS_PER_WEEK % WEEKS_PER_MONTH + 1
+    }
+
+    /// Week since the start of the game, 1 based
+    pub fn week(&self) -> u32 {
+        (self.day - 1) / DAYS_PER_WEEK + 1
+    }
+
+    /// Month since the start of the game, 1 based
+    pub fn month(&self) -> u32 {
+        (self.day - 1) / (DAYS_PER_WEEK * WEEKS_PER_MONTH) + 1
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameSettings {
+    pub difficulty: Difficulty,
+    /// players controlled by humans, the others are played by the computer
+    pub humans: Vec<Player>,
+    /// the same seed plays out the same random events
+    pub seed: u64,
+}
+
+impl GameSettings {
+    /// The map's difficulty with humans playing every player they can
+    pub fn for_map(map: &Map) -> Self {
+        Self {
+            difficulty: map.info.difficulty,
+            humans: map
+                .players
+                .iter()
+                .filter(|p| p.can_be_human)
+                .map(|p| p.player)
+                .collect(),
+            seed: 0,
+        }
+    }
+}
+
+/// Resources a player starts with, wood, mercury, ore, sulfur, crystal, gems, gold
+pub fn starting_resources(difficulty: Difficulty, human: bool) -> ResourcePack {
+    use Difficulty::*;
+    let pack = match (human, difficulty) {
+        (true, Easy) => [30, 15, 30, 15, 15, 15, 30000],
+        (true, Normal) => [20, 10, 20, 10, 10, 10, 20000],
+        (true, Hard) => [15, 7, 15, 7, 7, 7, 15000],
+        (true, Expert) => [10, 4, 10, 4, 4, 4, 10000],
+        (true, Impossible) => [0; 7],
+        // the computer gets more on harder difficulties
+        (false, Easy) => [5, 2, 5, 2, 2, 2, 5000],
+        (false, Normal) => [10, 4, 10, 4, 4, 4, 7500],
+        (false, Hard | Expert | Impossible) => [15, 7, 15, 7, 7, 7, 10000],
+    };
+    ResourcePack(pack)
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerState {
+    pub player: Player,
+    pub human: bool,
+    pub team: u8,
+    pub resources: ResourcePack,
+    pub heroes: Vec<ObjectId>,
+    pub towns: Vec<ObjectId>,
+    pub main_town: Option<ObjectId>,
+    pub eliminated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeroState {
+    pub object: ObjectId,
+    /// None for random heroes not generated yet
+    pub hero_id: Option<HeroId>,
+    pub name: Option<String>,
+    pub owner: Option<Player>,
+    pub position: MapCoord,
+    pub experience: u32,
+    pub army: Vec<CreatureSlot>,
+    pub skills: Vec<SecSkill>,
+    /// equipped artifacts
+    pub artifacts: Vec<ArtifactId>,
+    pub backpack: Vec<ArtifactId>,
+    pub spells: Vec<SpellId>,
+    /// movement points left today, on land or on sea
+    pub movement_points: u32,
+    pub in_boat: bool,
+    /// Stables add movement points until the end of the week
+    pub visited_stables: bool,
+}
+
+impl PlayerState {
+    pub fn can_afford(&self, cost: &ResourcePack) -> bool {
+        self.resources
+            .0
+            .iter()
+            .zip(cost.0)
+            .all(|(have, need)| *have >= need)
+    }
+
+    pub fn pay(&mut self, cost: &ResourcePack) {
+        for (have, need) in self.resources.0.iter_mut().zip(cost.0) {
+            *have -= need;
+        }
+    }
+}
+
+impl HeroState {
+    /// Movement state without the player's Lighthouses, see GameState::hero_movement_points
+    pub fn movement_state(&self) -> HeroMovementState<'_> {
+        HeroMovementState {
+            army: &self.army,
+            skills: &self.skills,
+            artifacts: &self.artifacts,
+            stables: self.visited_stables,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TownState {
+    pub object: ObjectId,
+    /// None for random towns not generated yet
+    pub faction: Option<Town>,
+    pub name: Option<String>,
+    pub owner: Option<Player>,
+    pub position: MapCoord,
+    pub garrison: Vec<CreatureSlot>,
+    pub buildings: Vec<Buildings>,
+    /// creatures to recruit by dwelling level 1-7
+    pub available: [u32; 7],
+    pub events: Vec<TownEvent>,
+    /// buildings the map doesn't allow
+    pub forbidden: Vec<Buildings>,
+    /// a town builds once a day
+    pub built_today: bool,
+}
+
+/// Creatures to recruit in an external dwelling
+#[derive(Debug, Clone)]
+pub struct DwellingState {
+    pub object: ObjectId,
+    pub creatures: Vec<(CreatureId, u32)>,
+}
+
+/// An adventure map object as it is in the game
+#[derive(Debug, Clone)]
+pub struct GameObject {
+    pub position: MapCoord,
+    /// index into Map::object_templates
+    pub template: u32,
+    pub subid: u32,
+    pub obj_type: ObjectType,
+    pub owner: Option<Player>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameState {
+    pub date: Date,
+    pub settings: GameSettings,
+    pub players: Vec<PlayerState>,
+    pub objects: BTreeMap<ObjectId, GameObject>,
+    pub heroes: BTreeMap<ObjectId, HeroState>,
+    pub towns: BTreeMap<ObjectId, TownState>,
+    pub dwellings: BTreeMap<ObjectId, DwellingState>,
+    /// objects giving their bonus once a week, visited this week
+    pub visited_this_week: BTreeSet<ObjectId>,
+    pub week: WeekKind,
+    /// weeks and months of a creature and plagues, MapOptions::allow_special_months
+    pub special_months: bool,
+    pub rng: Rng,
+    /// map events, town events belong to their towns
+    pub events: Vec<Event>,
+    /// event messages not shown yet
+    pub messages: Vec<EventMessage>,
+    next_object: u32,
+}
+
+fn player_of(owner: Option<Ownership>) -> Option<Player> {
+    match owner {
+        Some(Ownership::Player(p)) => Some(p),
+        _ => None,
+    }
+}
+
+impl GameState {
+    /// Start a game on the map: players who can play it get their starting
+    /// resources, heroes and towns their owners and day 1 begins.
+    pub fn from_map(map: &Map, settings: GameSettings) -> Self {
+        let mut state = GameState {
+            date: Date::first(),
+            players: Vec::new(),
+            objects: BTreeMap::new(),
+            heroes: BTreeMap::new(),
+            towns: BTreeMap::new(),
+            dwellings: BTreeMap::new(),
+            visited_this_week: BTreeSet::new(),
+            week: WeekKind::Normal,
+            special_months: map.map_options.allow_special_months,
+            rng: Rng::new(settings.seed),
+            events: map.events.clone(),
+            messages: Vec::new(),
+            next_object: 0,
+            settings,
+        };
+
+        let mut next_team = map.team_info.teams.keys().max().map_or(0, |t| t + 1);
+        for info in map
+            .players
+            .iter()
+            .filter(|p| p.can_be_human || p.can_be_computer)
+        {
+            let human = info.can_be_human && state.settings.humans.contains(&info.player);
+            // players without a team play alone
+            let team = match map
+                .team_info
+                .teams
+                .iter()
+                .find(|(_, players)| players.contains(&info.player))
+            {
+                Some((team, _)) => *team,
+                None => {
+                    let team = next_team;
+                    next_team += 1;
+                    team
+                }
+            };
+            state.players.push(PlayerState {
+                player: info.player,
+                human,
+                team,
+                resources: starting_resources(state.settings.difficulty, human),
+                heroes: Vec::new(),
+                towns: Vec::new(),
+                main_town: None,
+                eliminated: false,
+            });
+        }
+
+        let mut object_ids = Vec::with_capacity(map.objects.len());
+        let mut town_ids = Vec::new();
+        let mut random_dwellings = Vec::new();
+        for obj in map.objects.iter() {
+            let subid = map
+                .object_templates
+                .get(obj.obj_templ_id as usize)
+                .map_or(0, |t| t.subid);
+            let id = state.add_object(GameObject {
+                position: obj.position,
+                template: obj.obj_templ_id,
+                subid,
+                obj_type: obj.obj_type.clone(),
+                owner: player_of(obj.obj_type.owner()),
+            });
+            object_ids.push(id);
+            match &obj.obj_type {
+                ObjectType::Hero(h) | ObjectType::RandomHero(h) => {
+                    state.add_hero(id, obj.position, h);
+                }
+                ObjectType::Town(t) | ObjectType::RandomTown(t) => {
+                    // the subid of towns is the faction
+                    let faction = match obj.obj_type {
+                        ObjectType::Town(_) => ALL_TOWNS.get(subid as usize).copied(),
+                        _ => None,
+                    };
+                    state.add_town(id, obj.position, faction, t);
+                    town_ids.push((t.id, id));
+                }
+                ObjectType::CreatureGenerator1 { .. } => {
+                    let creatures = crate::creatures::dwelling_creature(subid);
+                    state.add_dwelling(id, creatures.into_iter().collect());
+                }
+                ObjectType::CreatureGenerator4 { .. } => {
+                    let creatures = crate::creatures::multi_dwelling_creatures(subid);
+                    state.add_dwelling(id, creatures);
+                }
+                ObjectType::RandomDwelling(d)
+                | ObjectType::RandomDwellingLvl(d)
+                | ObjectType::RandomDwellingFaction(d) => {
+                    random_dwellings.push((id, d));
+                }
+                _ => {}
+            }
+        }
+        // random dwellings may take the faction of a town placed after them
+        for (id, d) in random_dwellings {
+            let town = d
+                .rnd_info_id
+                .filter(|i| *i != 0)
+                .and_then(|i| town_ids.iter().find(|(t, _)| *t == i))
+                .and_then(|(_, o)| state.towns.get(o))
+                .and_then(|t| t.faction);
+            state.resolve_random_dwelling(id, d, town);
+        }
+
+        for info in map.players.iter() {
+            let Some(position) = info.main_town_position else {
+                continue;
+            };
+            // stored by the town's entrance
+            let town = map_passability::objects_at(map, position)
+                .into_iter()
+                .map(|i| object_ids[i])
+                .find(|id| state.towns.contains_key(id));
+            if let Some(p) = state.player_mut(info.player) {
+                p.main_town = town;
+            }
+        }
+        // the first week's creatures are there on day 1
+        state.weekly_growth();
+        state.run_events();
+        state
+    }
+
+    fn add_object(&mut self, object: GameObject) -> ObjectId {
+        let id = ObjectId(self.next_object);
+        self.next_object += 1;
+        self.objects.insert(id, object);
+        id
+    }
+
+    pub(crate) fn add_dwelling(&mut self, object: ObjectId, creatures: Vec<CreatureId>) {
+        let creatures = creatures.into_iter().map(|c| (c, 0)).collect();
+        self.dwellings
+            .insert(object, DwellingState { object, creatures });
+    }
+
+    fn add_hero(&mut self, object: ObjectId, position: MapCoord, h: &HeroData) {
+        let owner = player_of(h.owner);
+        let mut hero = HeroState {
+            object,
+            hero_id: h.hero_id,
+            name: h.name.clone(),
+            owner,
+            position,
+            experience: h.experience.unwrap_or(0),
+            army: h.garison.clone(),
+            skills: h.secondary_skills.clone(),
+            artifacts: h.artifacts.iter().map(|a| a.artifact_id).collect(),
+            backpack: h.artifacts_in_bag.clone(),
+            spells: h.custom_spells.clone(),
+            movement_points: 0,
+            in_boat: false,
+            visited_stables: false,
+        };
+        hero.movement_points = movement::land_movement_points(&hero.movement_state());
+        self.heroes.insert(object, hero);
+        if let Some(p) = owner.and_then(|o| self.player_mut(o)) {
+            p.heroes.push(object);
+        }
+    }
+
+    fn add_town(
+        &mut self,
+        object: ObjectId,
+        position: MapCoord,
+        faction: Option<Town>,
+        t: &TownData,
+    ) {
+        let owner = player_of(t.owner);
+        let for_faction = |b: &Buildings| faction.map_or(*b, |f| b.for_faction(f));
+        let mut buildings: Vec<_> = t.built_buildings.iter().map(for_faction).collect();
+        if let Some(i) = buildings.iter().position(|b| *b == Buildings::Default) {
+            buildings.remove(i);
+            buildings.extend([
+                Buildings::VillageHall,
+                Buildings::Tavern,
+                Buildings::DwellLvl1,
+            ]);
+            if self.rng.percent(50) {
+                buildings.push(Buildings::DwellLvl2);
+            }
+        }
+        if !buildings.contains(&Buildings::VillageHall) {
+            buildings.push(Buildings::VillageHall);
+        }
+        crate::buildings::add_included(&mut buildings);
+        self.towns.insert(
+            object,
+            TownState {
+                object,
+                faction,
+                name: t.name.clone(),
+                owner,
+                position,
+                garrison: t.guards.clone(),
+                buildings,
+                available: [0; 7],
+                events: t.events.clone(),
+                forbidden: t.forbidden_buildings.iter().map(for_faction).collect(),
+                built_today: false,
+            },
+        );
+        if let Some(p) = owner.and_then(|o| self.player_mut(o)) {
+            p.towns.push(object);
+        }
+    }
+
+    pub fn player(&self, player: Player) -> Option<&PlayerState> {
+        self.players.iter().find(|p| p.player == player)
+    }
+
+    pub fn player_mut(&mut self, player: Player) -> Option<&mut PlayerState> {
+        self.players.iter_mut().find(|p| p.player == player)
+    }
+
+    /// Players of the same team, a player is its own ally
+    pub fn allies(&self, a: Player, b: Player) -> bool {
+        match (self.player(a), self.player(b)) {
+            (Some(a), Some(b)) => a.team == b.team,
+            _ => a == b,
+        }
+    }
+
+    pub fn object(&self, id: ObjectId) -> Option<&GameObject> {
+        self.objects.get(&id)
+    }
+
+    /// Remove an object from the map, its handle stays unused
+    pub fn remove_object(&mut self, id: ObjectId) -> Option<GameObject> {
+        self.heroes.remove(&id);
+        self.towns.remove(&id);
+        self.dwellings.remove(&id);
+        for p in self.players.iter_mut() {
+            p.heroes.retain(|h| *h != id);
+            p.towns.retain(|t| *t != id);
+            if p.main_town == Some(id) {
+                p.main_town = None;
+            }
+        }
+        self.objects.remove(&id)
+    }
+}
@@ -0,0 +1,84 @@
+use resources::map_obj_type::ObjectType;
+use resources::map_structs::*;
+
+// Maps built in memory for the tests, one level of a single surface.
+
+pub fn map(n: usize, surface: Surface) -> Map {
+    let tile = TerrainTile {
+        surface_type: surface,
+        ..Default::default()
+    };
+    Map {
+        info: Info {
+            format: Format::SOD,
+            any_players: true,
+            map_dimension: n as i32,
+            two_levels: false,
+            name: String::new(),
+            description: String::new(),
+            difficulty: Difficulty::Normal,
+            hero_level_limit: None,
+        },
+        players: Vec::new(),
+        win_loss_cond: WinLossCond {
+            allow_normal_victory: true,
+            victory_cond_applies_to_comp: true,
+            special_victory_cond: None,
+            special_loss_cond: None,
+        },
+        team_info: TeamInfo::new(),
+        heroes_def: HeroesDef {
+            allowed_heroes: Vec::new(),
+            reserved_for_campaign: Vec::new(),
+            disposed_heroes: Vec::new(),
+        },
+        map_options: MapOptions {
+            allow_special_months: false,
+            round_limit: None,
+        },
+        allowed_artifacts: AllowedArtifacts {
+            artifacts: Vec::new(),
+        },
+        allowed_spells: AllowedSpells {
+            spells: Vec::new(),
+            skills: Vec::new(),
+        },
+        rumors: Rumors { rumors: Vec::new() },
+        predefined_heroes: Vec::new(),
+        terrains: vec![vec![vec![tile; n]; n]],
+        object_templates: Vec::new(),
+        objects: Vec::new(),
+        events: Vec::new(),
+    }
+}
+
+pub fn at(x: usize, y: usize) -> MapCoord {
+    MapCoord { x, y, z: 0 }
+}
+/// Add an object with a template of its own, visited on its position.
+/// Returns its index in Map::objects.
+pub fn add_object(
+    map: &mut Map,
+    position: MapCoord,
+    id: u32,
+    subid: u32,
+    obj_type: ObjectType,
+) -> usize {
+    let mut transit_matrix = [[TileTransitProperty::Transitable; 8]; 6];
+    transit_matrix[0][0] = TileTransitProperty::Visitable;
+    map.object_templates.push(ObjectTemplate {
+        animation_file: String::new(),
+        transit_matrix,
+        allowed_terrains: Vec::new(),
+        id,
+        subid,
+        obj_kind: ObjectKind::Unknown(0),
+        render_priority: 0,
+    });
+    map.objects.push(Object {
+        position,
+        obj_templ_id: map.object_templates.len() as u32 - 1,
+        obj_type,
+    });
+    map.objects.len() - 1
+}
@@ -0,0 +1,324 @@
+use crate::buildings;
+use crate::creatures;
+use crate::movement;
+use crate::state::*;
+use resources::map_buildings::Buildings;
+use resources::map_obj_type::ObjectType;
+use resources::map_structs::*;
+
+// End of day processing, following the original game:
+// - every day players get the income of their mines and towns and heroes
+//   get their movement points back,
+// - every week dwellings grow, objects giving a weekly bonus can be visited
+//   again and Stables bonuses expire: the first hero to visit a Windmill,
+//   Water Wheel or Mystical Garden in a week collects it,
+// - with special months allowed, weeks and months may favour a creature
+//   and a month may bring the plague.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum WeekKind {
+    #[default]
+    Normal,
+    /// the creature's dwellings grow by 5 more
+    WeekOf(CreatureId),
+    /// the creature's dwellings grow twice as much
+    MonthOf(CreatureId),
+    /// no growth, dwellings lose half of their creatures
+    Plague,
+}
+
+#[derive(Debug, Clone)]
+pub struct DayReport {
+    /// the day that has begun
+    pub date: Date,
+    pub new_week: bool,
+    pub new_month: bool,
+    pub week: WeekKind,
+    pub income: Vec<(Player, ResourcePack)>,
+}
+
+// chances out of 100 of the special weeks and months
+const WEEK_OF_CREATURE_CHANCE: u32 = 25;
+const MONTH_OF_CREATURE_CHANCE: u32 = 40;
+const PLAGUE_CHANCE: u32 = 10;
+const WEEK_OF_CREATURE_BONUS: u32 = 5;
+
+/// Daily income of a mine by its subid: sawmill, alchemist's lab, ore pit,
+/// sulfur dune, crystal cavern, gem pond, gold mine
+const MINE_INCOME: [(Resource, i32); 7] = [
+    (Resource::Wood, 2),
+    (Resource::Mercury, 1),
+    (Resource::Ore, 2),
+    (Resource::Sulfur, 1),
+    (Resource::Crystal, 1),
+    (Resource::Gems, 1),
+    (Resource::Gold, 1000),
+];
+
+/// Resources a Windmill gives 3 to 6 of
+const WINDMILL_RESOURCES: [Resource; 5] = [
+    Resource::Mercury,
+    Resource::Ore,
+    Resource::Sulfur,
+    Resource::Crystal,
+    Resource::Gems,
+];
+
+pub(crate) const DWELLINGS: [(Buildings, Buildings); 7] = [
+    (Buildings::DwellLvl1, Buildings::DwellLvl1Up),
+    (Buildings::DwellLvl2, Buildings::DwellLvl2Up),
+    (Buildings::DwellLvl3, Buildings::DwellLvl3Up),
+    (Buildings::DwellLvl4, Buildings::DwellLvl4Up),
+    (Buildings::DwellLvl5, Buildings::DwellLvl5Up),
+    (Buildings::DwellLvl6, Buildings::DwellLvl6Up),
+    (Buildings::DwellLvl7, Buildings::DwellLvl7Up),
+];
+
+fn add(pack: &mut ResourcePack, resource: Resource, amount: i32) {
+    pack.0[resource as usize] += amount;
+}
+
+fn add_pack(pack: &mut ResourcePack, other: &ResourcePack) {
+    for (a, b) in pack.0.iter_mut().zip(other.0) {
+        *a += b;
+    }
+}
+
+/// Daily income of a town from its hall, Resource Silo and Grail
+pub fn town_income(town: &TownState) -> ResourcePack {
+    let mut ret = ResourcePack::default();
+    let has = |b: Buildings| town.buildings.contains(&b);
+    let hall = if has(Buildings::Capitol) {
+        4000
+    } else if has(Buildings::CityHall) {
+        2000
+    } else if has(Buildings::TownHall) {
+        1000
+    } else if has(Buildings::VillageHall) {
+        500
+    } else {
+        0
+    };
+    add(&mut ret, Resource::Gold, hall);
+    if has(Buildings::Grail) {
+        add(&mut ret, Resource::Gold, 5000);
+    }
+    if has(Buildings::ResourceSilo) {
+        use Town::*;
+        let silo: &[Resource] = match town.faction {
+            Some(Castle | Necropolis | Stronghold | Fortress) => &[Resource::Wood, Resource::Ore],
+            Some(Rampart) => &[Resource::Crystal],
+            Some(Tower) => &[Resource::Gems],
+            Some(Inferno | Conflux) => &[Resource::Mercury],
+            Some(Dungeon) => &[Resource::Sulfur],
+            _ => &[],
+        };
+        for r in silo {
+            add(&mut ret, *r, 1);
+        }
+    }
+    ret
+}
+
+/// Weekly growth of a town dwelling level 0-6 with the Citadel and Castle
+/// bonuses and the horde buildings, 0 when the dwelling isn't built
+pub(crate) fn dwelling_growth(town: &TownState, level: usize) -> u32 {
+    let (Some(faction), Some((basic, upgraded))) = (town.faction, DWELLINGS.get(level)) else {
+        return 0;
+    };
+    if !town.buildings.contains(basic) && !town.buildings.contains(upgraded) {
+        return 0;
+    }
+    let base = creatures::town_creatures(faction, level as u8 + 1)
+        .first()
+        .and_then(|c| creatures::creature_stats(*c))
+        .map_or(0, |s| s.growth);
+    let fort = if town.buildings.contains(&Buildings::Castle) {
+        base * 2
+    } else if town.buildings.contains(&Buildings::Citadel) {
+        base * 3 / 2
+    } else {
+        base
+    };
+    fort + buildings::horde_growth(town, level)
+}
+
+// creatures affected by the week, growth including the week's bonus
+fn grow(week: WeekKind, creatures: &[CreatureId], available: u32, growth: u32) -> u32 {
+    match week {
+        WeekKind::Plague => available / 2,
+        WeekKind::WeekOf(c) if creatures.contains(&c) => {
+            available + growth + WEEK_OF_CREATURE_BONUS
+        }
+        WeekKind::MonthOf(c) if creatures.contains(&c) => available + growth * 2,
+        _ => available + growth,
+    }
+}
+
+impl GameState {
+    /// End the current day and begin the next one
+    pub fn end_day(&mut self) -> DayReport {
+        self.date.day += 1;
+        let new_week = self.date.day_of_week() == 1;
+        let new_month = new_week && self.date.week_of_month() == 1;
+
+        let mut income = Vec::new();
+        for p in self.players.iter().filter(|p| !p.eliminated) {
+            income.push((p.player, self.player_income(p.player)));
+        }
+        for (player, pack) in income.iter() {
+            if let Some(p) = self.player_mut(*player) {
+                add_pack(&mut p.resources, pack);
+            }
+        }
+
+        for town in self.towns.values_mut() {
+            town.built_today = false;
+        }
+        if new_week {
+            self.visited_this_week.clear();
+            for h in self.heroes.values_mut() {
+                h.visited_stables = false;
+            }
+            self.week = self.roll_week(new_month);
+            self.weekly_growth();
+        }
+        self.run_events();
+
+        let points = self
+            .heroes
+            .values()
+            .map(|h| (h.object, self.hero_movement_points(h)))
+            .collect::<Vec<_>>();
+        for (id, mp) in points {
+            if let Some(h) = self.heroes.get_mut(&id) {
+                h.movement_points = mp;
+            }
+        }
+
+        DayReport {
+            date: self.date,
+            new_week,
+            new_month,
+            week: self.week,
+            income,
+        }
+    }
+
+    /// Daily income of the player's mines and towns
+    pub fn player_income(&self, player: Player) -> ResourcePack {
+        let mut ret = ResourcePack::default();
+        for obj in self.objects.values().filter(|o| o.owner == Some(player)) {
+            // abandoned mines have to be cleared before they produce anything
+            if let ObjectType::Mine(_) = obj.obj_type {
+                if let Some((r, amount)) = MINE_INCOME.get(obj.subid as usize) {
+                    add(&mut ret, *r, *amount);
+                }
+            }
+        }
+        for town in self.towns.values().filter(|t| t.owner == Some(player)) {
+            add_pack(&mut ret, &town_income(town));
+        }
+        ret
+    }
+
+    /// Daily movement points of the hero, on land or on sea
+    pub fn hero_movement_points(&self, hero: &HeroState) -> u32 {
+        let mut state = hero.movement_state();
+        if hero.in_boat {
+            state.lighthouses = self
+                .objects
+                .values()
+                .filter(|o| matches!(o.obj_type, ObjectType::Lighthouse { .. }))
+                .filter(|o| o.owner.is_some() && o.owner == hero.owner)
+                .count() as u32;
+            movement::sea_movement_points(&state)
+        } else {
+            movement::land_movement_points(&state)
+        }
+    }
+
+    /// A hero visits a Windmill, a Water Wheel or a Mystical Garden, the
+    /// player gets its bonus unless it has already been given this week.
+    /// Returns what was given.
+    pub fn visit_weekly_object(
+        &mut self,
+        hero: ObjectId,
+        object: ObjectId,
+    ) -> Option<(Resource, i32)> {
+        let player = self.heroes.get(&hero)?.owner?;
+        if self.visited_this_week.contains(&object) {
+            return None;
+        }
+        let bonus = match self.objects.get(&object)?.obj_type {
+            ObjectType::Windmill => {
+                let resource = *self.rng.choose(&WINDMILL_RESOURCES)?;
+                (resource, self.rng.range(3, 6) as i32)
+            }
+            // half of it in the first week
+            ObjectType::WaterWheel if self.date.week() == 1 => (Resource::Gold, 500),
+            ObjectType::WaterWheel => (Resource::Gold, 1000),
+            ObjectType::MysticalGarden if self.rng.percent(50) => (Resource::Gems, 5),
+            ObjectType::MysticalGarden => (Resource::Gold, 500),
+            _ => return None,
+        };
+        self.visited_this_week.insert(object);
+        let p = self.player_mut(player)?;
+        add(&mut p.resources, bonus.0, bonus.1);
+        Some(bonus)
+    }
+
+    fn roll_week(&mut self, new_month: bool) -> WeekKind {
+        if !self.special_months {
+            return WeekKind::Normal;
+        }
+        // creatures of the dwellings below level 7 of the original towns
+        let candidates = ALL_TOWNS[..9]
+            .iter()
+            .flat_map(|t| (1..=6).filter_map(|l| creatures::town_creatures(*t, l).first().copied()))
+            .collect::<Vec<_>>();
+        let roll = self.rng.range(0, 99);
+        if new_month {
+            if roll < PLAGUE_CHANCE {
+                WeekKind::Plague
+            } else if roll < PLAGUE_CHANCE + MONTH_OF_CREATURE_CHANCE {
+                self.rng
+                    .choose(&candidates)
+                    .map_or(WeekKind::Normal, |c| WeekKind::MonthOf(*c))
+            } else {
+                WeekKind::Normal
+            }
+        } else if roll < WEEK_OF_CREATURE_CHANCE {
+            self.rng
+                .choose(&candidates)
+                .map_or(WeekKind::Normal, |c| WeekKind::WeekOf(*c))
+        } else {
+            WeekKind::Normal
+        }
+    }
+
+    /// New creatures in town and adventure map dwellings, they keep the
+    /// creatures nobody recruited
+    pub(crate) fn weekly_growth(&mut self) {
+        let week = self.week;
+        for town in self.towns.values_mut() {
+            let Some(faction) = town.faction else {
+                continue;
+            };
+            for level in 0..DWELLINGS.len() {
+                let growth = dwelling_growth(town, level);
+                if growth == 0 {
+                    continue;
+                }
+                let creatures = creatures::town_creatures(faction, level as u8 + 1);
+                town.available[level] = grow(week, &creatures, town.available[level], growth);
+            }
+        }
+        for dwelling in self.dwellings.values_mut() {
+            for (creature, available) in dwelling.creatures.iter_mut() {
+                let growth = creatures::creature_stats(*creature).map_or(0, |s| s.growth);
+                *available = grow(week, &[*creature], *available, growth);
+            }
+        }
+    }
+}
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use resources::lod_reader;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(dir) = lod_reader::parse_lod_directory(data) {
+        for entry in dir.entries.iter() {
+            let _ = lod_reader::read_lod_entry(data, entry);
+        }
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use resources::map_reader;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = map_reader::parse_h3m_bytes(data);
+    let _ = map_reader::parse_h3m_strict(data);
+});
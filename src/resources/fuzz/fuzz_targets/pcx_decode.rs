@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use resources::pcx2bmp;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = pcx2bmp::decode_pcx(data);
+});
@@ -0,0 +1,174 @@
+// Writes synthetic seed files for the fuzz targets, real game files can't be
+// distributed. Run from the fuzz directory: cargo run --bin gen_seeds
+use std::fs;
+use std::path::Path;
+
+#[derive(Default)]
+struct Bytes(Vec<u8>);
+
+impl Bytes {
+    fn u8(&mut self, v: u8) -> &mut Self {
+        self.0.push(v);
+        self
+    }
+
+    fn u16(&mut self, v: u16) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u32(&mut self, v: u32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn zeros(&mut self, n: usize) -> &mut Self {
+        self.0.resize(self.0.len() + n, 0);
+        self
+    }
+
+    fn string(&mut self, s: &str) -> &mut Self {
+        self.u32(s.len() as u32);
+        self.0.extend_from_slice(s.as_bytes());
+        self
+    }
+}
+
+const ROE: u32 = 0x0e;
+const SOD: u32 = 0x1c;
+const GRASS: u8 = 2;
+const WATER: u8 = 8;
+const CAMPFIRE: u32 = 12;
+
+// a map without players, with a rumor and an event; `campfire` adds one object
+fn map(format: u32, size: u32, two_levels: bool, campfire: bool) -> Vec<u8> {
+    let sod = format == SOD;
+    let mut b = Bytes::default();
+    b.u32(format).u8(1).u32(size).u8(two_levels as u8);
+    b.string("Seed").string("Synthetic map for fuzzing");
+    b.u8(1); // difficulty
+    if sod {
+        b.u8(0); // hero level limit
+    }
+    for _ in 0..8 {
+        // inactive player
+        b.u8(0).u8(0).zeros(if sod { 13 } else { 6 });
+    }
+    b.u8(0xff).u8(0xff); // no special victory or loss condition
+    b.u8(0); // no teams
+    b.zeros(if sod { 20 } else { 16 }); // allowed heroes
+    if sod {
+        b.u32(0); // reserved heroes
+        b.u8(0); // disposed heroes
+    }
+    b.zeros(31); // map options
+    if sod {
+        b.zeros(18); // allowed artifacts
+        b.zeros(9).zeros(4); // allowed spells and skills
+    }
+    b.u32(1).string("Rumor").string("Fuzzers were here");
+    if sod {
+        b.zeros(156); // no customized heroes
+    }
+    for level in 0..(1 + two_levels as u32) {
+        for y in 0..size {
+            for x in 0..size {
+                let surface = if level == 0 && (x == 0 || y == 0) {
+                    WATER
+                } else {
+                    GRASS
+                };
+                b.u8(surface).u8(((x + y) % 20) as u8).zeros(4).u8(0);
+            }
+        }
+    }
+    if campfire {
+        b.u32(1).string("ADCFRA.DEF");
+        // rows from the top, a cleared bit is blocked, the last bit of the last row is the anchor
+        b.u32(0xffff_ffff).u8(0xff).u8(0x7f);
+        b.zeros(5).u8(0x80); // visitable anchor
+        b.u16(0xff)
+            .u16(0xff)
+            .u32(CAMPFIRE)
+            .u32(0)
+            .u8(2)
+            .u8(0)
+            .zeros(16);
+        b.u32(1).u8(5).u8(5).u8(0).u32(0).zeros(5);
+    } else {
+        b.u32(0).u32(0);
+    }
+    // one global event
+    b.u32(1).string("Event").string("Happy new week");
+    for r in 0..7 {
+        b.u32(r);
+    }
+    b.u8(0xff);
+    if sod {
+        b.u8(1);
+    }
+    b.u8(0).u16(7).u8(7).zeros(17);
+    b.0
+}
+
+fn lod(files: &[(&str, &[u8])]) -> Vec<u8> {
+    const HEADER_SIZE: usize = 92;
+    const ENTRY_SIZE: usize = 32;
+    let mut b = Bytes::default();
+    b.u32(0x00444f4c).u32(200).u32(files.len() as u32).zeros(80);
+    let mut offset = HEADER_SIZE + files.len() * ENTRY_SIZE;
+    for (name, data) in files {
+        let mut raw_name = [0u8; 16];
+        raw_name[..name.len()].copy_from_slice(name.as_bytes());
+        b.0.extend_from_slice(&raw_name);
+        b.u32(offset as u32).u32(data.len() as u32).u32(2).u32(0);
+        offset += data.len();
+    }
+    for (_, data) in files {
+        b.0.extend_from_slice(data);
+    }
+    b.0
+}
+
+fn pcx_8bit(width: u32, height: u32) -> Vec<u8> {
+    let mut b = Bytes::default();
+    b.u32(width * height).u32(width).u32(height);
+    for i in 0..width * height {
+        b.u8(i as u8);
+    }
+    for i in 0..=255u8 {
+        b.u8(i).u8(255 - i).u8(i / 2);
+    }
+    b.0
+}
+
+fn pcx_24bit(width: u32, height: u32) -> Vec<u8> {
+    let mut b = Bytes::default();
+    b.u32(3 * width * height).u32(width).u32(height);
+    for i in 0..3 * width * height {
+        b.u8((i * 40) as u8);
+    }
+    b.0
+}
+
+fn write(target: &str, name: &str, data: &[u8]) {
+    let dir = Path::new("corpus").join(target);
+    fs::create_dir_all(&dir).expect("can't create the corpus directory");
+    fs::write(dir.join(name), data).expect("can't write a seed");
+}
+
+fn main() {
+    write("parse_map", "roe_small.h3m", &map(ROE, 36, false, false));
+    write("parse_map", "sod_small.h3m", &map(SOD, 36, false, true));
+    write("parse_map", "sod_two_levels.h3m", &map(SOD, 36, true, true));
+
+    write("lod_directory", "empty.lod", &lod(&[]));
+    write(
+        "lod_directory",
+        "two_files.lod",
+        &lod(&[("README.TXT", b"seed"), ("EMPTY.TXT", b"")]),
+    );
+
+    write("pcx_decode", "palette.pcx", &pcx_8bit(4, 3));
+    write("pcx_decode", "rgb.pcx", &pcx_24bit(3, 2));
+}
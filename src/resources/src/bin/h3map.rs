@@ -1,16 +1,36 @@
+use resources::map_balance;
+use resources::map_compat;
+use resources::map_corpus;
 use resources::map_reader;
+use resources::map_structs::Format;
+use resources::map_teleports;
 use std::env;
 
 const USAGE: &str = "Usage: h3map [OPTIONS] [COMMAND] <input> <output>
 
 Commands:
   show      Display some information about the .h3m file.
+  stats     Summarize the .h3m file: size, objects and the teleport network.
+  balance   Compare what each player can reach from the main town without a fight.
+  check     Parse the .h3m file strictly and report suspicious data.
+  convert   Dry run: report what downgrading the map to an older format changes,
+            nothing is written as there is no .h3m writer yet.
+  corpus    Parse every .h3m file in a directory tree and summarize the failures.
 
 Options:
   -h, --help     Show this help message and exit.
+  --days <n>     balance: days of movement to consider, 7 by default.
+  --to <format>  Target format of convert: roe, ab or sod.
+  --strict       corpus: parse in strict mode.
+  --json <file>  corpus: save the summary as JSON.
 
 Examples:
   h3map show ./input/res.h3m
+  h3map stats ./input/res.h3m
+  h3map balance --days 3 ./input/res.h3m
+  h3map check ./input/res.h3m
+  h3map convert --to sod ./input/res.h3m
+  h3map corpus --json summary.json ./maps
  
 Description:
   This tool allows you to interact with Heroes 3 map files in the .h3m format.
@@ -28,8 +48,207 @@ fn main() {
             Ok(map) => println!("Map: {map:?}"),
             Err(e) => panic!("Cant load map {}: {e}", &args[2]),
         },
+        "stats" => stats(&args[2]),
+        "balance" => balance(&args[2..]),
+        "check" => check(&args[2]),
+        "convert" => convert(&args[2..]),
+        "corpus" => corpus(&args[2..]),
         s => {
             panic!("Unknown subcommand {s}");
         }
     }
 }
+
+fn stats(input: &str) {
+    let map = match map_reader::load_h3m(std::path::Path::new(input)) {
+        Ok(map) => map,
+        Err(e) => panic!("Cant load map {input}: {e}"),
+    };
+    println!("Name: {}", map.info.name);
+    println!("Format: {}", map.info.format.nice_str());
+    println!("Size: {0}x{0}, {1} level(s)", map.dimension(), map.levels());
+    println!("Players: {}", map.players.len());
+    println!("Objects: {}", map.objects.len());
+    let conditions = map.resolve_conditions();
+    for (title, cond) in [("Victory", conditions.victory), ("Loss", conditions.loss)] {
+        if let Some(c) = cond {
+            println!("{title}: {}", c.objective);
+            for i in c.issues {
+                println!("  warning: {i}");
+            }
+        }
+    }
+
+    let network = map_teleports::teleport_network(&map);
+    println!("Teleports:");
+    for c in network.channels.iter() {
+        if c.kind == map_teleports::TeleportKind::OneWayMonolith {
+            println!(
+                "  {} {}: {} entrance(s), {} exit(s)",
+                c.kind.name(),
+                c.subid,
+                c.entrances.len(),
+                c.exits.len()
+            );
+        } else {
+            println!("  {} {}: {}", c.kind.name(), c.subid, c.entrances.len());
+        }
+    }
+    println!("  subterranean gate pairs: {}", network.gates.len());
+    for i in network.issues.iter() {
+        println!("  warning: {i}");
+    }
+}
+
+fn balance(args: &[String]) {
+    let (days, input) = match args {
+        [input] => (7, input),
+        [opt, days, input] if opt == "--days" => match days.parse() {
+            Ok(days) => (days, input),
+            Err(e) => panic!("Invalid number of days {days}: {e}"),
+        },
+        _ => {
+            println!("{USAGE}");
+            std::process::exit(1);
+        }
+    };
+    let map = match map_reader::load_h3m(std::path::Path::new(input)) {
+        Ok(map) => map,
+        Err(e) => panic!("Cant load map {input}: {e}"),
+    };
+    let report = map_balance::analyze_balance(&map, days);
+    println!(
+        "Reachable within {} day(s), {} movement points a day:",
+        report.days,
+        map_balance::MOVEMENT_POINTS_PER_DAY
+    );
+    for p in report.players.iter() {
+        let counts = map_balance::ALL_CATEGORIES
+            .iter()
+            .map(|c| format!("{} {}", p.count(*c), c.name()))
+            .collect::<Vec<_>>();
+        println!(
+            "  {:?} from ({}, {}, {}): {} tiles, {}",
+            p.player,
+            p.start.x,
+            p.start.y,
+            p.start.z,
+            p.tiles,
+            counts.join(", ")
+        );
+    }
+    for p in report.without_town.iter() {
+        println!("  {p:?}: no main town");
+    }
+    for i in report.imbalances.iter() {
+        println!("  warning: {i}");
+    }
+}
+
+fn check(input: &str) {
+    let data = match std::fs::read(input) {
+        Ok(data) => data,
+        Err(e) => panic!("Cant read {input}: {e}"),
+    };
+    match map_reader::parse_h3m_strict(&data) {
+        Ok((_, report)) => {
+            println!("Trailing bytes: {}", report.trailing_bytes);
+            println!("Non-zero padding: {}", report.nonzero_padding.len());
+            for p in report.nonzero_padding {
+                println!("  {:08x}: {:02x?}", p.offset, p.bytes);
+            }
+        }
+        Err(e) => panic!("Cant parse map {input}: {e}"),
+    }
+}
+
+// only reports the changes, the converted map is dropped
+fn convert(args: &[String]) {
+    let (target, input) = match args {
+        [opt, target, input] if opt == "--to" => (target, input),
+        _ => {
+            println!("{USAGE}");
+            std::process::exit(1);
+        }
+    };
+    let target = match target.to_lowercase().as_str() {
+        "roe" => Format::ROE,
+        "ab" => Format::AB,
+        "sod" => Format::SOD,
+        t => panic!("Unsupported target format {t}"),
+    };
+    let mut map = match map_reader::load_h3m(std::path::Path::new(input)) {
+        Ok(map) => map,
+        Err(e) => panic!("Cant load map {input}: {e}"),
+    };
+    let from = map.info.format;
+    match map_compat::downgrade(&mut map, target) {
+        Ok(issues) => {
+            println!(
+                "{} -> {}: {} change(s)",
+                from.nice_str(),
+                target.nice_str(),
+                issues.len()
+            );
+            for i in issues {
+                println!("  {i}");
+            }
+            println!("Dry run, {input} has not been modified");
+        }
+        Err(e) => panic!("Cant convert map {input}: {e}"),
+    }
+}
+
+fn corpus(args: &[String]) {
+    let mut options = map_corpus::CorpusOptions::default();
+    let mut json = None;
+    let mut dir = None;
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--strict" => options.strict = true,
+            "--roundtrip" => {
+                eprintln!("There is no .h3m writer yet, --roundtrip is not supported");
+                std::process::exit(1);
+            }
+            "--json" => json = args.next(),
+            _ => dir = Some(a),
+        }
+    }
+    let Some(dir) = dir else {
+        println!("{USAGE}");
+        std::process::exit(1);
+    };
+    // panics are reported per map, don't flood the output with them
+    std::panic::set_hook(Box::new(|_| {}));
+    let summary = match map_corpus::run_corpus(std::path::Path::new(dir), &options) {
+        Ok(s) => s,
+        Err(e) => panic!("Cant read corpus {dir}: {e}"),
+    };
+    let _ = std::panic::take_hook();
+    println!(
+        "{} maps: {} ok, {} failed, {} panicked, {} roundtrip failed",
+        summary.entries.len(),
+        summary.count("ok"),
+        summary.count("failed"),
+        summary.count("panicked"),
+        summary.count("roundtrip_failed")
+    );
+    for g in summary.failure_groups() {
+        let format = g.format.map_or("unknown format", |f| f.nice_str());
+        println!(
+            "{format} (HotA version {}), {}: {}",
+            g.hota_version,
+            g.section,
+            g.entries.len()
+        );
+        for e in g.entries {
+            println!("  {}", e.path.display());
+        }
+    }
+    if let Some(file) = json {
+        if let Err(e) = std::fs::write(file, summary.to_json()) {
+            panic!("Cant write {file}: {e}");
+        }
+    }
+}
@@ -34,26 +34,32 @@ fn main() {
     }
     match args[1].as_str() {
         "show" => {
-            lod_reader::load_lod(std::path::Path::new(&args[2]), None, true);
+            if let Err(e) = lod_reader::load_lod(std::path::Path::new(&args[2]), None, true) {
+                panic!("Cant load {}: {e}", &args[2]);
+            }
         }
         "dump" => {
             if args.len() < 4 {
                 panic!("Missing destination directory");
             }
-            lod_reader::load_lod(
+            if let Err(e) = lod_reader::load_lod(
                 std::path::Path::new(&args[2]),
                 Some(std::path::Path::new(&args[3])),
                 false,
-            );
+            ) {
+                panic!("Cant dump {}: {e}", &args[2]);
+            }
         }
         "pcx2bmp" => {
             if args.len() < 4 {
                 panic!("Missing output bitmap file name");
             }
-            pcx2bmp::convert_file(
+            if let Err(e) = pcx2bmp::convert_file(
                 std::path::Path::new(&args[2]),
                 std::path::Path::new(&args[3]),
-            );
+            ) {
+                panic!("Cant convert {}: {e}", &args[2]);
+            }
         }
         s => {
             panic!("Unknown subcommand {s}");
@@ -3,6 +3,19 @@ pub mod pcx2bmp;
 pub mod bmp;
 pub mod map_reader;
 pub mod map_structs;
+pub mod map_ids;
 pub mod map_obj_type;
 pub mod map_buildings;
+pub mod map_edit;
+pub mod map_roads;
+pub mod map_tiling;
+pub mod map_transform;
+pub mod map_passability;
+pub mod map_compat;
+pub mod map_corpus;
+pub mod map_templates;
+pub mod map_text;
+pub mod map_teleports;
+pub mod map_conditions;
+pub mod map_balance;
 mod reader;
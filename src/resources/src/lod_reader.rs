@@ -1,101 +1,150 @@
 use libz_sys::{uncompress, Z_BUF_ERROR, Z_DATA_ERROR, Z_MEM_ERROR, Z_OK};
 use std::ffi::CStr;
 use std::fs;
+use std::io;
 use std::path::Path;
 
 const LOD_MAGIC: u32 = 0x00444f4c;
 
-#[repr(C, packed)]
-#[derive(Debug, Copy, Clone)]
-struct H3File {
-    name: [u8; 16],
-    offset: u32,
-    orig_size: u32,
+const HEADER_SIZE: usize = 92;
+const ENTRY_SIZE: usize = 32;
+const MAX_FILES: usize = 10000;
+// best compression ratio zlib can achieve, a bigger size means the entry is broken
+const MAX_ZLIB_RATIO: usize = 1032;
+
+// LOD layout, all integers little endian:
+//   header: magic u32, version u32, files_num u32, 80 unknown bytes
+//   directory of 10000 entries: name [u8; 16], offset u32, orig_size u32, ftype u32, compr_size u32
+//   file data
+
+/// Directory entry of a LOD archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LodEntry {
+    pub name: String,
+    pub offset: u32,
+    pub orig_size: u32,
     /// Seems like 1: h3c, 2: txt
-    ftype: u32,
-    compr_size: u32,
+    pub ftype: u32,
+    /// 0 if the file is stored uncompressed
+    pub compr_size: u32,
 }
 
-#[repr(C, packed)]
-#[derive(Debug, Copy, Clone)]
-struct Header {
-    magic: u32,
-    version: u32,
-    files_num: u32,
-    unknown: [u8; 80],
-    h3file: [H3File; 10000],
+#[derive(Debug, Clone)]
+pub struct LodDirectory {
+    pub version: u32,
+    pub entries: Vec<LodEntry>,
 }
 
-pub fn load_lod(filename: &Path, dump_to: Option<&Path>, verbose: bool) {
-    if !filename.exists() {
-        panic!("load_lod: file {filename:?} does not exist");
-    }
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
 
-    if !filename.is_file() {
-        panic!("load_lod: {filename:?} is not a file");
+fn error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parse the header and the directory of a LOD archive
+pub fn parse_lod_directory(bytes: &[u8]) -> io::Result<LodDirectory> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(error(format!(
+            "LOD header needs {HEADER_SIZE} bytes, got {}",
+            bytes.len()
+        )));
+    }
+    if read_u32(bytes, 0) != LOD_MAGIC {
+        return Err(error("not a LOD archive (magic check failed)".to_string()));
     }
+    let version = read_u32(bytes, 4);
+    let files_num = read_u32(bytes, 8) as usize;
+    if files_num > MAX_FILES {
+        return Err(error(format!(
+            "LOD archive claims {files_num} files, at most {MAX_FILES} are possible"
+        )));
+    }
+    if bytes.len() < HEADER_SIZE + files_num * ENTRY_SIZE {
+        return Err(error(format!(
+            "LOD directory of {files_num} files goes past the end of the archive"
+        )));
+    }
+    let mut entries = Vec::with_capacity(files_num);
+    for i in 0..files_num {
+        let at = HEADER_SIZE + i * ENTRY_SIZE;
+        let name = &bytes[at..at + 16];
+        let name = match CStr::from_bytes_until_nul(name) {
+            Ok(c_str) => c_str.to_string_lossy().into_owned(),
+            Err(_) => return Err(error(format!("LOD entry {i}: name is not terminated"))),
+        };
+        if name.is_empty() {
+            break;
+        }
+        entries.push(LodEntry {
+            name,
+            offset: read_u32(bytes, at + 16),
+            orig_size: read_u32(bytes, at + 20),
+            ftype: read_u32(bytes, at + 24),
+            compr_size: read_u32(bytes, at + 28),
+        });
+    }
+    Ok(LodDirectory { version, entries })
+}
 
-    let bytes = match fs::read(&filename) {
-        Ok(bytes) => bytes,
-        Err(e) => panic!("load_lod: reading file {filename:?} failure: {e}"),
+/// Contents of the file stored in the archive, uncompressed
+pub fn read_lod_entry(bytes: &[u8], entry: &LodEntry) -> io::Result<Vec<u8>> {
+    let name = &entry.name;
+    let stored = if entry.compr_size != 0 {
+        entry.compr_size
+    } else {
+        entry.orig_size
+    } as usize;
+    let from = entry.offset as usize;
+    let to = match from.checked_add(stored) {
+        Some(to) if to <= bytes.len() => to,
+        _ => {
+            return Err(error(format!(
+                "{name}: data at offset {from}, {stored} bytes, points outside the archive"
+            )))
+        }
     };
-
-    if std::mem::size_of::<Header>() > bytes.len() {
-        panic!("load_lod: file {filename:?} seems to be smaller than LOD header. Corrupted file?");
+    if entry.compr_size == 0 {
+        return Ok(bytes[from..to].to_vec());
     }
-
-    let header: Header = unsafe { std::ptr::read(bytes.as_ptr() as *const _) };
-
-    if header.magic != LOD_MAGIC {
-        panic!("load_lod: file {filename:?} seems to be broken or not valid LOD format (magic check failed)");
+    let orig_size = entry.orig_size as usize;
+    if orig_size > stored.saturating_mul(MAX_ZLIB_RATIO) {
+        return Err(error(format!(
+            "{name}: {stored} compressed bytes can't hold {orig_size} bytes"
+        )));
     }
+    uncompress_h3file(&bytes[from..to], orig_size).map_err(|e| error(format!("{name}: {e}")))
+}
 
-    let Header {
-        magic,
-        version,
-        files_num,
-        ..
-    } = header;
+pub fn load_lod(filename: &Path, dump_to: Option<&Path>, verbose: bool) -> io::Result<()> {
+    let bytes = fs::read(filename)?;
+    let dir = parse_lod_directory(&bytes)?;
     if verbose {
-        println!("File {filename:?}, magic={magic}, version={version}, files_num={files_num}");
+        println!(
+            "File {filename:?}, magic={LOD_MAGIC}, version={}, files_num={}",
+            dir.version,
+            dir.entries.len()
+        );
     }
 
-    for (f, i) in header.h3file.iter().zip(0..files_num) {
-        let c_str = CStr::from_bytes_until_nul(&f.name[..]).unwrap();
-        let name = c_str.to_str().unwrap();
-        if name.len() == 0 {
-            break;
-        }
-        let H3File {
+    for (i, f) in dir.entries.iter().enumerate() {
+        let LodEntry {
+            name,
             offset,
             orig_size,
             ftype,
             compr_size,
-            ..
-        } = *f;
+        } = f;
         if verbose {
-            let comp_msg = if compr_size != 0 { "" } else { " UNCOMPRESSED" };
+            let comp_msg = if *compr_size != 0 {
+                ""
+            } else {
+                " UNCOMPRESSED"
+            };
             println!("\t{i}  {name:16}\toffset{offset:9}\t\torig_size{orig_size:9}\ttype={ftype}\tcompr_size{compr_size:9}\t{comp_msg}");
         }
-
-        // sanity check
-        let from = offset as usize;
-        let to = from + compr_size as usize;
-        if from > to {
-            panic!("load_lod: file {filename:?} contains invalid offset for {name} resource");
-        }
-        if to > bytes.len() {
-            panic!("load_lod: file {filename:?} contains a reference pointing outside the file for {name}");
-        }
-
-        let fbytes = if compr_size != 0 {
-            uncompress_h3file(&bytes[from..to], orig_size as usize)
-        } else {
-            bytes[from..from + orig_size as usize]
-                .iter()
-                .map(|b| *b)
-                .collect()
-        };
+        let fbytes = read_lod_entry(&bytes, f)?;
         // dumping this file to disk?
         if let Some(path) = dump_to {
             let dest_path = path.join(Path::new(name));
@@ -104,20 +153,25 @@ pub fn load_lod(filename: &Path, dump_to: Option<&Path>, verbose: bool) {
             }
         }
     }
+    Ok(())
 }
 
-fn uncompress_h3file(bytes: &[u8], orig_size: usize) -> Vec<u8> {
-    let mut out = Vec::with_capacity(orig_size);
-    out.resize(orig_size, 0);
+fn uncompress_h3file(bytes: &[u8], orig_size: usize) -> Result<Vec<u8>, String> {
+    let mut out = vec![0; orig_size];
     let mut dest_len = out.len() as u64;
     let compr_len = bytes.len() as u64;
     let res = unsafe { uncompress(out.as_mut_ptr(), &mut dest_len, bytes.as_ptr(), compr_len) };
     match res {
-        Z_OK =>{}
-        Z_BUF_ERROR => panic!("load_h3file: the buffer dest {orig_size} was not large enough to hold the uncompressed data."),
-        Z_MEM_ERROR => panic!("load_h3file: insufficient memory"),
-        Z_DATA_ERROR => panic!("load_h3file: the compressed data (referenced by source) was corrupted."),
-        _ => panic!("load_h3file: unknown zlib error"),
+        Z_OK => {}
+        Z_BUF_ERROR => {
+            return Err(format!(
+                "the buffer dest {orig_size} was not large enough to hold the uncompressed data"
+            ))
+        }
+        Z_MEM_ERROR => return Err("insufficient memory".to_string()),
+        Z_DATA_ERROR => return Err("the compressed data was corrupted".to_string()),
+        _ => return Err("unknown zlib error".to_string()),
     }
-    out
+    out.truncate(dest_len as usize);
+    Ok(out)
 }
@@ -0,0 +1,341 @@
+use crate::map_obj_type::ObjectType;
+use crate::map_passability::{self, Passability, TileState};
+use crate::map_structs::*;
+use crate::map_teleports;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt;
+
+// What each player can pick up without a fight: heroes walk from the main
+// town, monsters and their zones of control are walls. Visitable objects end
+// the walk, except teleports, which continue from their destinations.
+
+/// Movement points of a hero whose slowest creature has speed 3 or less, without skills
+pub const MOVEMENT_POINTS_PER_DAY: u32 = 1500;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    Resources,
+    Mines,
+    Artifacts,
+    Dwellings,
+}
+
+pub const ALL_CATEGORIES: [Category; 4] = [
+    Category::Resources,
+    Category::Mines,
+    Category::Artifacts,
+    Category::Dwellings,
+];
+
+impl Category {
+    pub fn from(obj_type: &ObjectType) -> Option<Self> {
+        use ObjectType::*;
+        match obj_type {
+            Resource(_) | RandomResource(_) => Some(Self::Resources),
+            Mine(_) | AbandonedMine(_) => Some(Self::Mines),
+            Artifact(_) | RandomArt(_) | RandomTreasureArt(_) | RandomMinorArt(_)
+            | RandomMajorArt(_) | RandomRelicArt(_) => Some(Self::Artifacts),
+            CreatureGenerator1 { .. }
+            | CreatureGenerator2 { .. }
+            | CreatureGenerator3 { .. }
+            | CreatureGenerator4 { .. }
+            | RandomDwelling(_)
+            | RandomDwellingLvl(_)
+            | RandomDwellingFaction(_) => Some(Self::Dwellings),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Resources => "resources",
+            Self::Mines => "mines",
+            Self::Artifacts => "artifacts",
+            Self::Dwellings => "dwellings",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReachedObject {
+    /// index into Map::objects
+    pub object: usize,
+    pub category: Category,
+    /// movement points needed to visit it
+    pub cost: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerReach {
+    pub player: Player,
+    pub start: MapCoord,
+    /// tiles the hero can stand on
+    pub tiles: usize,
+    /// sorted by cost
+    pub objects: Vec<ReachedObject>,
+}
+
+impl PlayerReach {
+    pub fn count(&self, category: Category) -> usize {
+        self.objects
+            .iter()
+            .filter(|o| o.category == category)
+            .count()
+    }
+}
+
+/// One player reaches much less of a category than another one
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Imbalance {
+    pub category: Category,
+    pub best: Player,
+    pub best_count: usize,
+    pub worst: Player,
+    pub worst_count: usize,
+}
+
+impl fmt::Display for Imbalance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:?} reaches {}, {:?} only {}",
+            self.category.name(),
+            self.best,
+            self.best_count,
+            self.worst,
+            self.worst_count
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BalanceReport {
+    pub days: u32,
+    pub players: Vec<PlayerReach>,
+    /// players who can play but have no main town to start from
+    pub without_town: Vec<Player>,
+    pub imbalances: Vec<Imbalance>,
+}
+
+// the worst player is flagged below 3/4 of the best one and at least 2 objects behind
+const IMBALANCE_RATIO: (usize, usize) = (3, 4);
+const IMBALANCE_MIN_DIFFERENCE: usize = 2;
+
+type Tile = (usize, usize, usize);
+
+fn tile(c: MapCoord) -> Tile {
+    (c.z, c.x, c.y)
+}
+
+fn coord((z, x, y): Tile) -> MapCoord {
+    MapCoord { x, y, z }
+}
+
+// Cost of a step between neighbouring tiles, for a hero without skills
+fn step_cost(map: &Map, from: MapCoord, to: MapCoord) -> u32 {
+    let a = &map.terrains[from.z][from.x][from.y];
+    let b = &map.terrains[to.z][to.x][to.y];
+    let cost =
+        map_passability::step_cost(a, b, |s| map_passability::surface_cost(s).unwrap_or(100));
+    if from.x != to.x && from.y != to.y {
+        map_passability::diagonal_cost(cost)
+    } else {
+        cost
+    }
+}
+
+// Dijkstra state. A tile is kept twice: entered to visit an object there,
+// and stood on to walk further.
+struct Search {
+    budget: u32,
+    best: [BTreeMap<Tile, u32>; 2],
+    queue: BinaryHeap<Reverse<(u32, Tile, bool)>>,
+}
+
+impl Search {
+    fn push(&mut self, t: Tile, cost: u32, walk_on: bool) {
+        if cost > self.budget {
+            return;
+        }
+        let slot = self.best[walk_on as usize].entry(t).or_insert(u32::MAX);
+        if cost < *slot {
+            *slot = cost;
+            self.queue.push(Reverse((cost, t, walk_on)));
+        }
+    }
+
+    fn pop(&mut self) -> Option<(u32, Tile, bool)> {
+        while let Some(Reverse((cost, t, walk_on))) = self.queue.pop() {
+            if self.best[walk_on as usize].get(&t) == Some(&cost) {
+                return Some((cost, t, walk_on));
+            }
+        }
+        None
+    }
+}
+
+struct Walker<'a> {
+    map: &'a Map,
+    passability: &'a Passability,
+    network: &'a map_teleports::TeleportNetwork,
+    /// visitable tile -> objects visited there
+    visitable: BTreeMap<Tile, Vec<usize>>,
+}
+
+impl Walker<'_> {
+    fn new<'a>(
+        map: &'a Map,
+        passability: &'a Passability,
+        network: &'a map_teleports::TeleportNetwork,
+    ) -> Walker<'a> {
+        let mut visitable: BTreeMap<Tile, Vec<usize>> = BTreeMap::new();
+        for (i, obj) in map.objects.iter().enumerate() {
+            for c in map_passability::object_tiles(map, obj, TileTransitProperty::Visitable) {
+                visitable.entry(tile(c)).or_default().push(i);
+            }
+        }
+        Walker {
+            map,
+            passability,
+            network,
+            visitable,
+        }
+    }
+
+    fn visitable_tiles(&self, object: usize) -> Vec<MapCoord> {
+        map_passability::object_tiles(
+            self.map,
+            &self.map.objects[object],
+            TileTransitProperty::Visitable,
+        )
+    }
+
+    // Returns the cheapest cost of every visited object and the number of
+    // tiles to stand on within the budget
+    fn walk(&self, start: &[MapCoord], budget: u32) -> (BTreeMap<usize, u32>, usize) {
+        let mut visited: BTreeMap<usize, u32> = BTreeMap::new();
+        let mut search = Search {
+            budget,
+            best: Default::default(),
+            queue: BinaryHeap::new(),
+        };
+        for c in start {
+            search.push(tile(*c), 0, true);
+        }
+        while let Some((cost, t, walk_on)) = search.pop() {
+            let here = coord(t);
+            for &object in self.visitable.get(&t).into_iter().flatten() {
+                let entry = visited.entry(object).or_insert(cost);
+                *entry = (*entry).min(cost);
+            }
+            if !walk_on {
+                let teleports = self.visitable.get(&t).into_iter().flatten();
+                for &object in teleports {
+                    for &dest in self.network.destinations(object) {
+                        for c in self.visitable_tiles(dest) {
+                            search.push(tile(c), cost, true);
+                        }
+                    }
+                }
+                continue;
+            }
+            for (dx, dy) in crate::map_tiling::NEIGHBOURS {
+                let (Some(x), Some(y)) =
+                    (here.x.checked_add_signed(dx), here.y.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                let next = MapCoord { x, y, z: here.z };
+                let walk_on = match self.passability.get(next) {
+                    TileState::Free => true,
+                    TileState::Visitable => false,
+                    TileState::Blocked | TileState::Guarded => continue,
+                };
+                let next_cost = cost + step_cost(self.map, here, next);
+                search.push(tile(next), next_cost, walk_on);
+            }
+        }
+        (visited, search.best[1].len())
+    }
+}
+
+// the main town's entrances, or the position itself without a town there;
+// the position is the one stored for the main town, its entrance
+fn start_tiles(map: &Map, position: MapCoord) -> Vec<MapCoord> {
+    let town = map_passability::objects_at(map, position)
+        .into_iter()
+        .map(|i| &map.objects[i])
+        .find(|o| matches!(o.obj_type, ObjectType::Town(_) | ObjectType::RandomTown(_)));
+    let tiles = town.map_or(Vec::new(), |t| {
+        map_passability::object_tiles(map, t, TileTransitProperty::Visitable)
+    });
+    if tiles.is_empty() && map.contains(&position) {
+        vec![position]
+    } else {
+        tiles
+    }
+}
+
+/// Compare what the players can reach from their main towns within the days
+pub fn analyze_balance(map: &Map, days: u32) -> BalanceReport {
+    let passability = Passability::from_map(map);
+    let network = map_teleports::teleport_network(map);
+    let walker = Walker::new(map, &passability, &network);
+    let budget = days.saturating_mul(MOVEMENT_POINTS_PER_DAY);
+
+    let mut report = BalanceReport {
+        days,
+        ..Default::default()
+    };
+    for info in map
+        .players
+        .iter()
+        .filter(|p| p.can_be_human || p.can_be_computer)
+    {
+        let Some(start) = info.main_town_position else {
+            report.without_town.push(info.player);
+            continue;
+        };
+        let (visited, tiles) = walker.walk(&start_tiles(map, start), budget);
+        let mut objects = visited
+            .into_iter()
+            .filter_map(|(object, cost)| {
+                Category::from(&map.objects[object].obj_type).map(|category| ReachedObject {
+                    object,
+                    category,
+                    cost,
+                })
+            })
+            .collect::<Vec<_>>();
+        objects.sort_by_key(|o| (o.cost, o.object));
+        report.players.push(PlayerReach {
+            player: info.player,
+            start,
+            tiles,
+            objects,
+        });
+    }
+
+    for category in ALL_CATEGORIES {
+        let counts = report.players.iter().map(|p| (p.player, p.count(category)));
+        let (Some(best), Some(worst)) = (
+            counts.clone().max_by_key(|(_, n)| *n),
+            counts.min_by_key(|(_, n)| *n),
+        ) else {
+            continue;
+        };
+        if worst.1 * IMBALANCE_RATIO.1 < best.1 * IMBALANCE_RATIO.0
+            && best.1 - worst.1 >= IMBALANCE_MIN_DIFFERENCE
+        {
+            report.imbalances.push(Imbalance {
+                category,
+                best: best.0,
+                best_count: best.1,
+                worst: worst.0,
+                worst_count: worst.1,
+            });
+        }
+    }
+    report
+}
@@ -1,4 +1,6 @@
-#[derive(Debug, Default, Copy, Clone)]
+use crate::map_structs::Town;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub enum Buildings {
     #[default]
     Default, // means standard buildings built by default
@@ -32,8 +34,8 @@ pub enum Buildings {
     Horde1,
     Horde1Upgr,
     Ship,
-    // Special2,
-    // Special3,
+    Special2,
+    Special3,
     Special4,
     Horde2,
     Horde2Upgr,
@@ -62,10 +64,10 @@ pub enum Buildings {
     DwellLvl1Up,
     DwellLvl7Up,
     // DwellUp2First,
-    //Special buildings for towns.
-    CastleGate,       //Inferno
-    FreelancersGuild, //Stronghold
-    ArtifactMerchant,
+    // specials with a meaning of their own, see for_faction
+    CastleGate,       // Inferno Special2
+    FreelancersGuild, // Stronghold Special2
+    ArtifactMerchant, // Tower, Dungeon and Conflux Special1
     DecodeError,
 }
 
@@ -100,15 +102,12 @@ impl Buildings {
             14 => Marketplace,
             15 => ResourceSilo,
             16 => Blacksmith,
-            // 17 => Special1,
-            17 => ArtifactMerchant,
+            17 => Special1,
             18 => Horde1,
             19 => Horde1Upgr,
             20 => Ship,
-            // 21 => Special2,
-            21 => FreelancersGuild, // Stringhold
-            // 22 => Special3,
-            22 => CastleGate, // inferno
+            21 => Special2,
+            22 => Special3,
             23 => Special4,
             24 => Horde2,
             25 => Horde2Upgr,
@@ -148,4 +147,16 @@ impl Buildings {
             _ => DecodeError,
         }
     }
+
+    /// The special buildings share their codes between factions, name the
+    /// ones the faction has a building of its own for
+    pub fn for_faction(self, town: Town) -> Self {
+        use Buildings::*;
+        match (self, town) {
+            (Special1, Town::Tower | Town::Dungeon | Town::Conflux) => ArtifactMerchant,
+            (Special2, Town::Inferno) => CastleGate,
+            (Special2, Town::Stronghold) => FreelancersGuild,
+            _ => self,
+        }
+    }
 }
@@ -0,0 +1,596 @@
+use crate::map_obj_type::*;
+use crate::map_reader::ParsingContext;
+use crate::map_structs::*;
+use crate::map_tiling;
+use std::fmt;
+
+// Compatibility with older map formats. downgrade() rewrites everything a
+// format doesn't know about and reports what has been done, the analysis is
+// the same downgrade done on a copy of the map.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    Removed,
+    Substituted(String),
+    /// can't be fixed automatically
+    Kept,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompatIssue {
+    pub feature: String,
+    pub position: Option<MapCoord>,
+    pub resolution: Resolution,
+}
+
+impl fmt::Display for CompatIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.feature)?;
+        if let Some(c) = self.position {
+            write!(f, " at ({},{},{})", c.x, c.y, c.z)?;
+        }
+        match &self.resolution {
+            Resolution::Removed => write!(f, ": removed"),
+            Resolution::Substituted(with) => write!(f, ": replaced with {with}"),
+            Resolution::Kept => write!(f, ": can't be converted"),
+        }
+    }
+}
+
+/// Features of the map the target format doesn't support and what a downgrade would do with them
+pub fn check_compatibility(map: &Map, target: Format) -> Result<Vec<CompatIssue>, String> {
+    let mut copy = map.clone();
+    downgrade(&mut copy, target)
+}
+
+/// Convert the map to an older format, substituting or removing unsupported content
+pub fn downgrade(map: &mut Map, target: Format) -> Result<Vec<CompatIssue>, String> {
+    if ![Format::ROE, Format::AB, Format::SOD].contains(&target) {
+        return Err(format!(
+            "downgrade: {} is not a valid target format",
+            target.nice_str()
+        ));
+    }
+    let mut d = Downgrade {
+        ctx: ParsingContext::from(target, 0),
+        issues: Vec::new(),
+    };
+    d.info(map);
+    d.terrain(map);
+    d.players(map);
+    d.win_loss_cond(map);
+    d.heroes_def(map);
+    d.map_options(map);
+    d.allowed(map);
+    d.predefined_heroes(map);
+    d.objects(map);
+    map.info.format = target;
+    Ok(d.issues)
+}
+
+struct Downgrade {
+    ctx: ParsingContext,
+    issues: Vec<CompatIssue>,
+}
+
+impl Downgrade {
+    fn note(&mut self, feature: String, position: Option<MapCoord>, resolution: Resolution) {
+        self.issues.push(CompatIssue {
+            feature,
+            position,
+            resolution,
+        });
+    }
+
+    fn supports(&self, required: Format) -> bool {
+        match required {
+            Format::ROE => true,
+            Format::AB => self.ctx.level_AB,
+            Format::SOD => self.ctx.level_SOD,
+            Format::HOTA => self.ctx.level_HOTA0,
+            Format::WOG => self.ctx.level_WOG,
+            Format::VCMI => false,
+        }
+    }
+
+    fn creature_ok(&self, c: CreatureId) -> bool {
+        (c.0 as usize) < self.ctx.creatures_count
+    }
+
+    fn artifact_ok(&self, a: ArtifactId) -> bool {
+        (a.0 as usize) < self.ctx.artifacts_count
+    }
+
+    fn hero_ok(&self, id: HeroId) -> bool {
+        (id.0 as usize) < self.ctx.heroes_count
+    }
+
+    fn skill_ok(&self, id: SecSkillId) -> bool {
+        (id.0 as usize) < self.ctx.skills_count
+    }
+
+    fn info(&mut self, map: &mut Map) {
+        if map.dimension() > MapSize::XL.dimension() && !self.ctx.level_HOTA0 {
+            let size = map.dimension();
+            self.note(format!("map size {size}x{size}"), None, Resolution::Kept);
+        }
+        if !self.ctx.level_AB && map.info.hero_level_limit.take().is_some() {
+            self.note("hero level limit".to_string(), None, Resolution::Removed);
+        }
+    }
+
+    fn terrain(&mut self, map: &mut Map) {
+        if self.ctx.level_HOTA0 {
+            return;
+        }
+        let mut changed = false;
+        for (z, terrain) in map.terrains.iter_mut().enumerate() {
+            for (x, column) in terrain.iter_mut().enumerate() {
+                for (y, tile) in column.iter_mut().enumerate() {
+                    let substitute = match tile.surface_type {
+                        Surface::Highlands => Surface::Rough,
+                        Surface::Wasteland => Surface::Dirt,
+                        _ => continue,
+                    };
+                    self.issues.push(CompatIssue {
+                        feature: format!("{:?} terrain", tile.surface_type),
+                        position: Some(MapCoord { x, y, z }),
+                        resolution: Resolution::Substituted(format!("{substitute:?}")),
+                    });
+                    tile.surface_type = substitute;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            map_tiling::retile_map(map);
+        }
+    }
+
+    fn players(&mut self, map: &mut Map) {
+        for p in map.players.iter_mut() {
+            let player = p.player;
+            match &mut p.faction {
+                Faction::Some(t) if !self.ctx.factions.contains(t) => {
+                    let t = *t;
+                    p.faction = Faction::RandomAll;
+                    self.note(
+                        format!("{player:?} player's faction {t:?}"),
+                        None,
+                        Resolution::Substituted("random faction".to_string()),
+                    );
+                }
+                Faction::RandomSome(towns) => {
+                    let before = towns.len();
+                    towns.retain(|t| self.ctx.factions.contains(t));
+                    if towns.len() != before {
+                        self.note(
+                            format!("{player:?} player's allowed factions"),
+                            None,
+                            Resolution::Removed,
+                        );
+                    }
+                }
+                _ => {}
+            }
+            if p.lead_hero
+                .as_ref()
+                .is_some_and(|h| !self.hero_ok(h.id))
+            {
+                p.lead_hero = None;
+                self.note(
+                    format!("{player:?} player's lead hero"),
+                    p.main_town_position,
+                    Resolution::Removed,
+                );
+            }
+            let before = p.other_heroes.len();
+            p.other_heroes.retain(|h| self.hero_ok(h.id));
+            if p.other_heroes.len() != before {
+                self.note(
+                    format!("{player:?} player's heroes"),
+                    None,
+                    Resolution::Removed,
+                );
+            }
+        }
+    }
+
+    fn win_loss_cond(&mut self, map: &mut Map) {
+        use SpecialVictoryCondition::*;
+        let cond = &mut map.win_loss_cond;
+        let supported = match &cond.special_victory_cond {
+            Some(EliminateAllMonsters) | Some(SurviveNDays { .. }) => self.ctx.level_HOTA0,
+            Some(AcquireArtifact { artifact_code }) => self.artifact_ok(*artifact_code),
+            Some(TransportArtifact { artifact_code, .. }) => self.artifact_ok(*artifact_code),
+            Some(AccumulateCreatures { unit_code, .. }) => self.creature_ok(*unit_code),
+            _ => true,
+        };
+        if !supported {
+            if let Some(v) = cond.special_victory_cond.take() {
+                self.note(
+                    format!("victory condition {v:?}"),
+                    v.coord(),
+                    Resolution::Removed,
+                );
+            }
+        }
+    }
+
+    fn heroes_def(&mut self, map: &mut Map) {
+        let heroes_count = self.ctx.heroes_count;
+        let def = &mut map.heroes_def;
+        let before = def.allowed_heroes.len() + def.reserved_for_campaign.len();
+        def.allowed_heroes.retain(|h| (h.0 as usize) < heroes_count);
+        def.reserved_for_campaign
+            .retain(|h| (h.0 as usize) < heroes_count);
+        if def.allowed_heroes.len() + def.reserved_for_campaign.len() != before {
+            self.note("allowed heroes".to_string(), None, Resolution::Removed);
+        }
+        let before = def.disposed_heroes.len();
+        def.disposed_heroes
+            .retain(|(h, _)| (h.id.0 as usize) < heroes_count);
+        if !self.ctx.level_SOD {
+            def.disposed_heroes.clear();
+        }
+        if def.disposed_heroes.len() != before {
+            self.note("disposed heroes".to_string(), None, Resolution::Removed);
+        }
+    }
+
+    fn map_options(&mut self, map: &mut Map) {
+        if self.ctx.level_HOTA0 {
+            return;
+        }
+        let opt = &mut map.map_options;
+        if opt.allow_special_months {
+            opt.allow_special_months = false;
+            self.note("special months".to_string(), None, Resolution::Removed);
+        }
+        if opt.round_limit.take().is_some() {
+            self.note("round limit".to_string(), None, Resolution::Removed);
+        }
+    }
+
+    fn allowed(&mut self, map: &mut Map) {
+        let before = map.allowed_artifacts.artifacts.len();
+        map.allowed_artifacts
+            .artifacts
+            .retain(|a| (a.0 as usize) < self.ctx.artifacts_count);
+        if map.allowed_artifacts.artifacts.len() != before {
+            self.note("allowed artifacts".to_string(), None, Resolution::Removed);
+        }
+        let before = map.allowed_spells.skills.len();
+        map.allowed_spells
+            .skills
+            .retain(|s| (s.0 as usize) < self.ctx.skills_count);
+        if map.allowed_spells.skills.len() != before {
+            self.note("allowed skills".to_string(), None, Resolution::Removed);
+        }
+    }
+
+    fn predefined_heroes(&mut self, map: &mut Map) {
+        if !self.ctx.level_SOD {
+            if !map.predefined_heroes.is_empty() {
+                map.predefined_heroes.clear();
+                self.note("customized heroes".to_string(), None, Resolution::Removed);
+            }
+            return;
+        }
+        let mut heroes = std::mem::take(&mut map.predefined_heroes);
+        let before = heroes.len();
+        heroes.retain(|h| self.hero_ok(h.id));
+        if heroes.len() != before {
+            self.note("customized heroes".to_string(), None, Resolution::Removed);
+        }
+        for h in heroes.iter_mut() {
+            let feature = format!("customized hero {}", h.id);
+            self.skills(&mut h.secondary_skills, &feature, None);
+            self.hero_artifacts(&mut h.artifacts, &mut h.artifacts_in_bag, &feature, None);
+        }
+        map.predefined_heroes = heroes;
+    }
+
+    fn skills(&mut self, skills: &mut Vec<SecSkill>, owner: &str, pos: Option<MapCoord>) {
+        let before = skills.len();
+        skills.retain(|s| (s.id.0 as usize) < self.ctx.skills_count);
+        if skills.len() != before {
+            self.note(
+                format!("{owner}: secondary skills"),
+                pos,
+                Resolution::Removed,
+            );
+        }
+    }
+
+    fn artifacts(&mut self, artifacts: &mut Vec<ArtifactId>, owner: &str, pos: Option<MapCoord>) {
+        let before = artifacts.len();
+        artifacts.retain(|a| (a.0 as usize) < self.ctx.artifacts_count);
+        if artifacts.len() != before {
+            self.note(format!("{owner}: artifacts"), pos, Resolution::Removed);
+        }
+    }
+
+    fn hero_artifacts(
+        &mut self,
+        worn: &mut Vec<HeroesArtifact>,
+        bag: &mut Vec<ArtifactId>,
+        owner: &str,
+        pos: Option<MapCoord>,
+    ) {
+        let before = worn.len();
+        worn.retain(|a| self.artifact_ok(a.artifact_id));
+        if worn.len() != before {
+            self.note(format!("{owner}: artifacts"), pos, Resolution::Removed);
+        }
+        // the fifth misc slot came with SoD
+        let slots = self.ctx.artifact_slots_count as u32;
+        if worn.iter().any(|a| a.slot_id.0 >= slots) {
+            bag.extend(
+                worn.iter()
+                    .filter(|a| a.slot_id.0 >= slots)
+                    .map(|a| a.artifact_id),
+            );
+            worn.retain(|a| a.slot_id.0 < slots);
+            self.note(
+                format!("{owner}: artifact slot"),
+                pos,
+                Resolution::Substituted("backpack".to_string()),
+            );
+        }
+        self.artifacts(bag, owner, pos);
+    }
+
+    fn creatures(&mut self, slots: &mut [CreatureSlot], owner: &str, pos: Option<MapCoord>) {
+        let mut removed = false;
+        for s in slots.iter_mut() {
+            if s.creature.is_some_and(|c| !self.creature_ok(c)) {
+                s.creature = None;
+                s.amount = 0;
+                removed = true;
+            }
+        }
+        if removed {
+            self.note(format!("{owner}: creatures"), pos, Resolution::Removed);
+        }
+    }
+
+    fn creature_list(
+        &mut self,
+        list: &mut Vec<(CreatureId, u32)>,
+        owner: &str,
+        pos: Option<MapCoord>,
+    ) {
+        let before = list.len();
+        list.retain(|(c, _)| (c.0 as usize) < self.ctx.creatures_count);
+        if list.len() != before {
+            self.note(format!("{owner}: creatures"), pos, Resolution::Removed);
+        }
+    }
+
+    fn guard(&mut self, guard: &mut Option<CreatureGuard>, owner: &str, pos: Option<MapCoord>) {
+        if let Some(g) = guard.as_mut() {
+            self.creatures(&mut g.slot, owner, pos);
+        }
+    }
+
+    fn box_content(&mut self, b: &mut BoxContent, owner: &str, pos: Option<MapCoord>) {
+        self.guard(&mut b.guards, owner, pos);
+        self.skills(&mut b.reward_secondary_skills, owner, pos);
+        self.artifacts(&mut b.reward_artifacts, owner, pos);
+        self.creature_list(&mut b.reward_creatures, owner, pos);
+    }
+
+    // false if the mission can't be expressed in the target format
+    fn quest(&mut self, q: &mut QuestMission, owner: &str, pos: Option<MapCoord>) -> bool {
+        use QuestMissionType::*;
+        match &mut q.mission_type {
+            HOTAMulti | Keymaster | HOTAHeroClass(_) | HOTAReachDate(_) => self.ctx.level_HOTA0,
+            Artifact(v) => {
+                self.artifacts(v, owner, pos);
+                !v.is_empty()
+            }
+            Army(v) => {
+                self.creature_list(v, owner, pos);
+                !v.is_empty() && self.ctx.level_AB
+            }
+            Hero(id) => self.hero_ok(*id) && self.ctx.level_AB,
+            NoMission => true,
+            _ => self.ctx.level_AB,
+        }
+    }
+
+    fn seer_hut_reward(
+        &mut self,
+        reward: &mut SeerHutRewardType,
+        owner: &str,
+        pos: Option<MapCoord>,
+    ) {
+        use SeerHutRewardType::*;
+        let empty = match reward {
+            SecondarySkills(v) => {
+                self.skills(v, owner, pos);
+                v.is_empty()
+            }
+            Artifact(v) => {
+                self.artifacts(v, owner, pos);
+                v.is_empty()
+            }
+            Creature(v) => {
+                self.creature_list(v, owner, pos);
+                v.is_empty()
+            }
+            _ => false,
+        };
+        if empty {
+            *reward = Nothing;
+        }
+    }
+
+    // format required by the object itself, not counting its content
+    fn required_format(obj_id: u32, subid: u32, obj_type: &ObjectType) -> Format {
+        match obj_type {
+            ObjectType::Unknown(_) => Format::HOTA,
+            ObjectType::PyramidOrWogObject if subid > 0 => Format::WOG,
+            ObjectType::Resource(_) if subid == Resource::Mithril as u32 => Format::WOG,
+            ObjectType::HeroPlaceholder { .. } => Format::SOD,
+            _ if (162..=164).contains(&obj_id) || (212..=231).contains(&obj_id) => Format::AB,
+            _ => Format::ROE,
+        }
+    }
+
+    fn objects(&mut self, map: &mut Map) {
+        let mut objects = std::mem::take(&mut map.objects);
+        let mut keep = Vec::with_capacity(objects.len());
+        for obj in objects.iter_mut() {
+            let (obj_id, subid) = match map.object_templates.get(obj.obj_templ_id as usize) {
+                Some(t) => (t.id, t.subid),
+                None => (0, 0),
+            };
+            keep.push(self.object(map, obj, obj_id, subid));
+        }
+        let mut keep = keep.into_iter();
+        objects.retain(|_| keep.next().unwrap_or(true));
+        map.objects = objects;
+    }
+
+    // returns false if the object has to be removed
+    fn object(&mut self, map: &mut Map, obj: &mut Object, obj_id: u32, subid: u32) -> bool {
+        use ObjectType::*;
+        let pos = Some(obj.position);
+        let name = object_name(&obj.obj_type);
+        if !self.supports(Self::required_format(obj_id, subid, &obj.obj_type)) {
+            self.note(name, pos, Resolution::Removed);
+            return false;
+        }
+        match &mut obj.obj_type {
+            Monster(m) => {
+                if !self.creature_ok(CreatureId(subid as u16)) {
+                    self.note(format!("{name} {subid}"), pos, Resolution::Removed);
+                    return false;
+                }
+                if m.artifact.is_some_and(|a| !self.artifact_ok(a)) {
+                    m.artifact = None;
+                    self.note(format!("{name}: artifact"), pos, Resolution::Removed);
+                }
+            }
+            Artifact(guards) => {
+                if !self.artifact_ok(ArtifactId(subid)) {
+                    self.note(format!("{name} {subid}"), pos, Resolution::Removed);
+                    return false;
+                }
+                self.guard(guards, &name, pos);
+            }
+            RandomArt(guards)
+            | RandomTreasureArt(guards)
+            | RandomMinorArt(guards)
+            | RandomMajorArt(guards)
+            | RandomRelicArt(guards) => self.guard(guards, &name, pos),
+            SpellScroll(s) => self.guard(&mut s.guards, &name, pos),
+            Resource(r) | RandomResource(r) => self.guard(&mut r.guards, &name, pos),
+            Hero(h) | RandomHero(h) | Prison(h) => {
+                if let Some(id) = h.hero_id.filter(|id| !self.hero_ok(*id)) {
+                    self.note(format!("{name} {id}"), pos, Resolution::Removed);
+                    return false;
+                }
+                self.skills(&mut h.secondary_skills, &name, pos);
+                self.creatures(&mut h.garison, &name, pos);
+                self.hero_artifacts(&mut h.artifacts, &mut h.artifacts_in_bag, &name, pos);
+            }
+            Town(d) => {
+                self.creatures(&mut d.guards, &name, pos);
+                if subid as usize >= self.ctx.factions.len() {
+                    let data = std::mem::take(d);
+                    obj.obj_type = RandomTown(data);
+                    obj.obj_templ_id = random_town_template(map, obj.obj_templ_id);
+                    self.note(
+                        format!("{name} of faction {subid}"),
+                        pos,
+                        Resolution::Substituted("random town".to_string()),
+                    );
+                }
+            }
+            RandomTown(d) => self.creatures(&mut d.guards, &name, pos),
+            Garrison(d) | Garrison2(d) => self.creatures(&mut d.guards, &name, pos),
+            Event(ev) => {
+                if let Some(b) = ev.box_content.as_mut() {
+                    self.box_content(b, &name, pos);
+                }
+            }
+            PandorasBox(b) => self.box_content(b, &name, pos),
+            SeerHut(quests) => {
+                if quests.len() > 1 && !self.ctx.level_HOTA0 {
+                    quests.truncate(1);
+                    self.note(
+                        format!("{name}: additional quests"),
+                        pos,
+                        Resolution::Removed,
+                    );
+                }
+                for q in quests.iter_mut() {
+                    if !self.quest(&mut q.mission, &name, pos) {
+                        self.note(format!("{name} quest"), pos, Resolution::Removed);
+                        return false;
+                    }
+                    self.seer_hut_reward(&mut q.reward, &name, pos);
+                }
+            }
+            QuestGuard(q) => {
+                let supported = self.quest(q, &name, pos);
+                if !supported {
+                    self.note(format!("{name} quest"), pos, Resolution::Removed);
+                    return false;
+                }
+            }
+            WitchHut { secondary_skills } => {
+                let before = secondary_skills.len();
+                secondary_skills.retain(|s| (s.0 as usize) < self.ctx.skills_count);
+                if secondary_skills.len() != before {
+                    self.note(
+                        format!("{name}: secondary skills"),
+                        pos,
+                        Resolution::Removed,
+                    );
+                }
+            }
+            Scholar(ScholarBonus::SecondarySkill(id)) if !self.skill_ok(*id) => {
+                obj.obj_type = Scholar(ScholarBonus::Random);
+                self.note(
+                    format!("{name}: secondary skill"),
+                    pos,
+                    Resolution::Substituted("random bonus".to_string()),
+                );
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+fn object_name(obj_type: &ObjectType) -> String {
+    // variant name without the data
+    let debug = format!("{obj_type:?}");
+    match debug.find(['(', ' ', '{']) {
+        Some(i) => debug[..i].to_string(),
+        None => debug,
+    }
+}
+
+// template of a random town with the same shape as the given town template
+fn random_town_template(map: &mut Map, town_templ_id: u32) -> u32 {
+    const RANDOM_TOWN_ID: u32 = 77;
+    const RANDOM_TOWN_DEF: &str = "AVCRANT0.DEF";
+    if let Some(i) = map
+        .object_templates
+        .iter()
+        .position(|t| t.id == RANDOM_TOWN_ID)
+    {
+        return i as u32;
+    }
+    let mut template = map.object_templates[town_templ_id as usize].clone();
+    template.id = RANDOM_TOWN_ID;
+    template.subid = 0;
+    template.animation_file = RANDOM_TOWN_DEF.to_string();
+    map.object_templates.push(template);
+    (map.object_templates.len() - 1) as u32
+}
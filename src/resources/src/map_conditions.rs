@@ -0,0 +1,280 @@
+use crate::map_obj_type::*;
+use crate::map_passability;
+use crate::map_structs::*;
+use crate::map_text;
+use std::fmt;
+
+// Special victory and loss conditions refer to their targets by position.
+// A town is stored by its entrance, the visitable tile two tiles left of its
+// anchor, heroes and monsters by the tile they stand on, so a target is looked
+// up among the objects the position is a visitable tile of, then the ones
+// anchored there.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TargetKind {
+    Town,
+    Hero,
+    Monster,
+}
+
+impl TargetKind {
+    fn matches(&self, obj_type: &ObjectType) -> bool {
+        use ObjectType::*;
+        match self {
+            TargetKind::Town => matches!(obj_type, Town(_) | RandomTown(_)),
+            TargetKind::Hero => matches!(obj_type, Hero(_) | RandomHero(_)),
+            TargetKind::Monster => matches!(
+                obj_type,
+                Monster(_)
+                    | RandomMonster(_)
+                    | RandomMonsterL1(_)
+                    | RandomMonsterL2(_)
+                    | RandomMonsterL3(_)
+                    | RandomMonsterL4(_)
+                    | RandomMonsterL5(_)
+                    | RandomMonsterL6(_)
+                    | RandomMonsterL7(_)
+            ),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            TargetKind::Town => "town",
+            TargetKind::Hero => "hero",
+            TargetKind::Monster => "monster",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionIssue {
+    OutsideMap {
+        position: MapCoord,
+    },
+    NoObject {
+        position: MapCoord,
+        expected: TargetKind,
+    },
+    /// there is another object at the position
+    WrongObject {
+        position: MapCoord,
+        expected: TargetKind,
+        object: usize,
+    },
+    /// losing a town or a hero that no player owns
+    NotOwned {
+        position: MapCoord,
+        object: usize,
+    },
+}
+
+fn coord(p: &MapCoord) -> String {
+    format!("({},{},{})", p.x, p.y, p.z)
+}
+
+impl fmt::Display for ConditionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OutsideMap { position } => {
+                write!(f, "{} is outside of the map", coord(position))
+            }
+            Self::NoObject { position, expected } => {
+                write!(f, "no {} at {}", expected.name(), coord(position))
+            }
+            Self::WrongObject {
+                position,
+                expected,
+                object,
+            } => write!(
+                f,
+                "object {object} at {} is not a {}",
+                coord(position),
+                expected.name()
+            ),
+            Self::NotOwned { position, object } => write!(
+                f,
+                "object {object} at {} isn't owned by a player",
+                coord(position)
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvedCondition {
+    /// index into Map::objects of the condition target
+    pub object: Option<usize>,
+    /// e.g. "Capture the town of Steadwick at (12,40,0)"
+    pub objective: String,
+    pub issues: Vec<ConditionIssue>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvedConditions {
+    pub victory: Option<ResolvedCondition>,
+    pub loss: Option<ResolvedCondition>,
+}
+
+fn hall_name(b: Building) -> &'static str {
+    match b {
+        Building::Town => "Town Hall",
+        Building::City => "City Hall",
+        Building::Capitol => "Capitol",
+        Building::Fort => "Fort",
+        Building::Citadel => "Citadel",
+        Building::Castle => "Castle",
+    }
+}
+
+impl Map {
+    /// Find the objects the special victory and loss conditions refer to
+    /// and describe the objectives
+    pub fn resolve_conditions(&self) -> ResolvedConditions {
+        let cond = &self.win_loss_cond;
+        ResolvedConditions {
+            victory: cond
+                .special_victory_cond
+                .as_ref()
+                .map(|c| self.resolve_victory(c)),
+            loss: cond
+                .special_loss_cond
+                .as_ref()
+                .map(|c| self.resolve_loss(c)),
+        }
+    }
+
+    fn resolve_victory(&self, cond: &SpecialVictoryCondition) -> ResolvedCondition {
+        use SpecialVictoryCondition::*;
+        let mut ret = ResolvedCondition::default();
+        ret.objective = match cond {
+            AcquireArtifact { artifact_code } => format!("Acquire the {artifact_code}"),
+            AccumulateCreatures { unit_code, amount } => {
+                format!("Accumulate {}", map_text::count_stack(*unit_code, *amount))
+            }
+            AccumulateResources { resource, amount } => {
+                format!("Accumulate {amount} {}", map_text::resource_name(*resource))
+            }
+            UpgradeTown {
+                town_coord,
+                hall_level,
+                castle_level,
+            } => {
+                let town = self.target(&mut ret, *town_coord, TargetKind::Town, false);
+                format!(
+                    "Build a {} and a {} in {town}",
+                    hall_name(*hall_level),
+                    hall_name(*castle_level)
+                )
+            }
+            // a position outside of the map stands for any town
+            BuildGrail { town_coord } if !self.contains(town_coord) => {
+                "Build the Grail structure in any town".to_string()
+            }
+            BuildGrail { town_coord } => {
+                let town = self.target(&mut ret, *town_coord, TargetKind::Town, false);
+                format!("Build the Grail structure in {town}")
+            }
+            DefeatHero { hero_coord } => {
+                let hero = self.target(&mut ret, *hero_coord, TargetKind::Hero, false);
+                format!("Defeat {hero}")
+            }
+            CaptureTown { town_coord } => {
+                let town = self.target(&mut ret, *town_coord, TargetKind::Town, false);
+                format!("Capture {town}")
+            }
+            DefeatMonster { monster_coord } => {
+                let monster = self.target(&mut ret, *monster_coord, TargetKind::Monster, false);
+                format!("Defeat {monster}")
+            }
+            FlagAllCreatureDwellings => "Flag all creature dwellings".to_string(),
+            FlagAllMines => "Flag all mines".to_string(),
+            TransportArtifact {
+                artifact_code,
+                artifact_coord,
+            } => {
+                let town = self.target(&mut ret, *artifact_coord, TargetKind::Town, false);
+                format!("Transport the {artifact_code} to {town}")
+            }
+            EliminateAllMonsters => "Defeat all monsters".to_string(),
+            SurviveNDays { limit_days } => format!("Survive for {limit_days} days"),
+        };
+        ret
+    }
+
+    fn resolve_loss(&self, cond: &SpecialLossCondition) -> ResolvedCondition {
+        use SpecialLossCondition::*;
+        let mut ret = ResolvedCondition::default();
+        ret.objective = match cond {
+            LossTown { town_coord } => {
+                let town = self.target(&mut ret, *town_coord, TargetKind::Town, true);
+                format!("Lose {town}")
+            }
+            LossHero { hero_coord } => {
+                let hero = self.target(&mut ret, *hero_coord, TargetKind::Hero, true);
+                format!("Lose {hero}")
+            }
+            TimeExpires { limit_days } => format!("Fail to win within {limit_days} days"),
+        };
+        ret
+    }
+
+    // find the target, record problems and name it for the objective
+    fn target(
+        &self,
+        ret: &mut ResolvedCondition,
+        position: MapCoord,
+        kind: TargetKind,
+        must_be_owned: bool,
+    ) -> String {
+        let at = coord(&position);
+        let unknown = match kind {
+            TargetKind::Town => format!("the town at {at}"),
+            TargetKind::Hero => format!("the hero at {at}"),
+            TargetKind::Monster => format!("the monsters at {at}"),
+        };
+        if !self.contains(&position) {
+            ret.issues.push(ConditionIssue::OutsideMap { position });
+            return unknown;
+        }
+        let at_position = map_passability::objects_at(self, position);
+        let Some((object, obj)) = at_position
+            .iter()
+            .map(|i| (*i, &self.objects[*i]))
+            .find(|(_, o)| kind.matches(&o.obj_type))
+        else {
+            ret.issues.push(match at_position.first() {
+                Some(&object) => ConditionIssue::WrongObject {
+                    position,
+                    expected: kind,
+                    object,
+                },
+                None => ConditionIssue::NoObject {
+                    position,
+                    expected: kind,
+                },
+            });
+            return unknown;
+        };
+        ret.object = Some(object);
+        if must_be_owned && !matches!(obj.obj_type.owner(), Some(Ownership::Player(_))) {
+            ret.issues
+                .push(ConditionIssue::NotOwned { position, object });
+        }
+        use ObjectType::*;
+        match &obj.obj_type {
+            Town(d) | RandomTown(d) => match &d.name {
+                Some(name) => format!("the town of {name} at {at}"),
+                None => unknown,
+            },
+            Hero(h) | RandomHero(h) => format!("{} at {at}", map_text::hero_name(h)),
+            // the amount is random when 0
+            Monster(MonsterData {
+                creature: Some(c),
+                amount,
+                ..
+            }) if *amount > 0 => format!("{} at {at}", map_text::describe_stack(*c, *amount)),
+            _ => unknown,
+        }
+    }
+}
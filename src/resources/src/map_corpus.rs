@@ -0,0 +1,276 @@
+use crate::map_reader::{self, MapParseError, ParsePosition};
+use crate::map_structs::*;
+use crate::reader::BinaryDataReader;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+// Regression run over a directory of maps. Every map is parsed on its own,
+// panics included, and the results are summarized in a JSON document that
+// is stable between runs (sorted, no timestamps) so two runs can be diffed.
+
+/// Serializes a map back to .h3m data, used by the roundtrip check
+pub type MapWriter = dyn Fn(&Map) -> io::Result<Vec<u8>>;
+
+#[derive(Default)]
+pub struct CorpusOptions<'a> {
+    /// parse in strict mode, see map_reader::parse_h3m_strict
+    pub strict: bool,
+    /// write every parsed map, parse it again and compare
+    pub roundtrip: Option<&'a MapWriter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Parsed,
+    Failed { message: String },
+    Panicked { message: String },
+    RoundtripFailed { message: String },
+}
+
+impl Outcome {
+    fn status(&self) -> &'static str {
+        match self {
+            Outcome::Parsed => "ok",
+            Outcome::Failed { .. } => "failed",
+            Outcome::Panicked { .. } => "panicked",
+            Outcome::RoundtripFailed { .. } => "roundtrip_failed",
+        }
+    }
+
+    fn message(&self) -> Option<&str> {
+        match self {
+            Outcome::Parsed => None,
+            Outcome::Failed { message }
+            | Outcome::Panicked { message }
+            | Outcome::RoundtripFailed { message } => Some(message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorpusEntry {
+    /// relative to the corpus directory
+    pub path: PathBuf,
+    /// where the parser stopped, the last section for parsed maps
+    pub position: ParsePosition,
+    pub outcome: Outcome,
+    /// strict mode only
+    pub trailing_bytes: usize,
+    /// strict mode only
+    pub nonzero_padding: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct CorpusSummary {
+    /// sorted by path
+    pub entries: Vec<CorpusEntry>,
+}
+
+/// Failures sharing the section and the format
+#[derive(Debug)]
+pub struct FailureGroup<'a> {
+    pub section: &'static str,
+    pub format: Option<Format>,
+    pub hota_version: usize,
+    pub entries: Vec<&'a CorpusEntry>,
+}
+
+/// Parse every .h3m file in the directory tree
+pub fn run_corpus(dir: &Path, options: &CorpusOptions) -> io::Result<CorpusSummary> {
+    let mut files = Vec::new();
+    collect_maps(dir, &mut files)?;
+    files.sort();
+    let mut entries = Vec::with_capacity(files.len());
+    for file in files {
+        let data = std::fs::read(&file)?;
+        let mut entry = check_map(&data, options);
+        entry.path = file.strip_prefix(dir).unwrap_or(&file).to_path_buf();
+        entries.push(entry);
+    }
+    Ok(CorpusSummary { entries })
+}
+
+fn collect_maps(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_maps(&path, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("h3m"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn check_map(data: &[u8], options: &CorpusOptions) -> CorpusEntry {
+    let mut entry = CorpusEntry {
+        path: PathBuf::new(),
+        position: ParsePosition::default(),
+        outcome: Outcome::Parsed,
+        trailing_bytes: 0,
+        nonzero_padding: 0,
+    };
+    let mut reader = match BinaryDataReader::from_slice_possibly_gzip(data) {
+        Ok(r) => r,
+        Err(e) => {
+            entry.position.section = "gzip";
+            entry.outcome = Outcome::Failed {
+                message: e.to_string(),
+            };
+            return entry;
+        }
+    };
+    reader.set_strict(options.strict);
+    let position = &mut entry.position;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        map_reader::parse_map_tracked(&mut reader, position)
+    }));
+    let map = match result {
+        Ok(Ok(map)) => map,
+        Ok(Err(e)) => {
+            let message = match MapParseError::from_io(&e) {
+                Some(pe) => format!("offset {:#x}: {}", pe.offset, pe.source),
+                None => e.to_string(),
+            };
+            entry.outcome = Outcome::Failed { message };
+            return entry;
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            entry.outcome = Outcome::Panicked { message };
+            return entry;
+        }
+    };
+    entry.trailing_bytes = reader.remaining();
+    entry.nonzero_padding = reader.take_nonzero_padding().len();
+    if let Some(writer) = options.roundtrip {
+        if let Err(message) = roundtrip(&map, writer) {
+            entry.outcome = Outcome::RoundtripFailed { message };
+        }
+    }
+    entry
+}
+
+fn roundtrip(map: &Map, writer: &MapWriter) -> Result<(), String> {
+    let data = writer(map).map_err(|e| format!("write: {e}"))?;
+    let again =
+        map_reader::parse_h3m_bytes(&data).map_err(|e| format!("parse written map: {e}"))?;
+    // Map has no PartialEq, the Debug output covers every field
+    if format!("{map:?}") != format!("{again:?}") {
+        return Err("written map differs from the original".to_string());
+    }
+    Ok(())
+}
+
+impl CorpusSummary {
+    pub fn count(&self, status: &str) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.outcome.status() == status)
+            .count()
+    }
+
+    /// Maps that didn't parse, grouped by the section and the format
+    pub fn failure_groups(&self) -> Vec<FailureGroup<'_>> {
+        let mut groups: Vec<FailureGroup> = Vec::new();
+        for e in self.entries.iter().filter(|e| e.outcome != Outcome::Parsed) {
+            let p = &e.position;
+            match groups.iter_mut().find(|g| {
+                g.section == p.section && g.format == p.format && g.hota_version == p.hota_version
+            }) {
+                Some(g) => g.entries.push(e),
+                None => groups.push(FailureGroup {
+                    section: p.section,
+                    format: p.format,
+                    hota_version: p.hota_version,
+                    entries: vec![e],
+                }),
+            }
+        }
+        groups.sort_by_key(|g| (format_name(g.format), g.hota_version, g.section));
+        groups
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n");
+        out += &format!("  \"total\": {},\n", self.entries.len());
+        for status in ["ok", "failed", "panicked", "roundtrip_failed"] {
+            out += &format!("  \"{status}\": {},\n", self.count(status));
+        }
+        out += "  \"groups\": [";
+        for (i, g) in self.failure_groups().iter().enumerate() {
+            out += if i == 0 { "\n" } else { ",\n" };
+            let maps = g
+                .entries
+                .iter()
+                .map(|e| json_string(&e.path.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            out += &format!(
+                "    {{\"format\": {}, \"hota_version\": {}, \"section\": {}, \"count\": {}, \"maps\": [{maps}]}}",
+                json_string(format_name(g.format)),
+                g.hota_version,
+                json_string(g.section),
+                g.entries.len()
+            );
+        }
+        out += "\n  ],\n  \"maps\": [";
+        for (i, e) in self.entries.iter().enumerate() {
+            out += if i == 0 { "\n" } else { ",\n" };
+            out += &format!(
+                "    {{\"path\": {}, \"format\": {}, \"hota_version\": {}, \"status\": {}, \"section\": {}, \"trailing_bytes\": {}, \"nonzero_padding\": {}",
+                json_string(&e.path.to_string_lossy()),
+                json_string(format_name(e.position.format)),
+                e.position.hota_version,
+                json_string(e.outcome.status()),
+                json_string(e.position.section),
+                e.trailing_bytes,
+                e.nonzero_padding
+            );
+            if let Some(m) = e.outcome.message() {
+                out += &format!(", \"error\": {}", json_string(m));
+            }
+            out += "}";
+        }
+        out += "\n  ]\n}\n";
+        out
+    }
+}
+
+fn format_name(format: Option<Format>) -> &'static str {
+    match format {
+        Some(Format::ROE) => "ROE",
+        Some(Format::AB) => "AB",
+        Some(Format::SOD) => "SOD",
+        Some(Format::HOTA) => "HOTA",
+        Some(Format::WOG) => "WOG",
+        Some(Format::VCMI) => "VCMI",
+        None => "unknown",
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,348 @@
+use crate::map_obj_type::ObjectType;
+use crate::map_roads;
+use crate::map_structs::*;
+use crate::map_tiling;
+use std::ops::Range;
+
+// Editing operations on a parsed Map. Every change goes through an EditCommand
+// so it can be validated against the invariants the parser relies on
+// (valid obj_templ_id, in-bounds positions) and undone later.
+
+#[derive(Debug, Clone)]
+pub enum EditCommand {
+    /// Place a new object. The template is added to `Map::object_templates`
+    /// unless an identical one is already there.
+    PlaceObject {
+        template: Box<ObjectTemplate>,
+        position: MapCoord,
+        obj_type: Box<ObjectType>,
+    },
+    RemoveObject {
+        index: usize,
+    },
+    MoveObject {
+        index: usize,
+        to: MapCoord,
+    },
+    PaintTerrain {
+        tiles: Vec<MapCoord>,
+        surface: Surface,
+    },
+    /// None removes the road
+    PaintRoad {
+        tiles: Vec<MapCoord>,
+        road: Option<RoadType>,
+    },
+    SetOwner {
+        index: usize,
+        owner: Option<Ownership>,
+    },
+}
+
+// What has to be done to revert an applied command
+#[derive(Debug)]
+enum UndoOp {
+    RemoveObject {
+        index: usize,
+        drop_template: bool,
+    },
+    InsertObject {
+        index: usize,
+        object: Box<Object>,
+    },
+    MoveObject {
+        index: usize,
+        to: MapCoord,
+    },
+    RestoreTiles {
+        tiles: Vec<(MapCoord, TerrainTile)>,
+    },
+    SetOwner {
+        index: usize,
+        owner: Option<Ownership>,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct MapEditor {
+    done: Vec<(EditCommand, UndoOp)>,
+    undone: Vec<EditCommand>,
+}
+
+impl MapEditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the command to the map. On error the map is left untouched.
+    pub fn apply(&mut self, map: &mut Map, cmd: EditCommand) -> Result<(), String> {
+        let undo = execute(map, &cmd)?;
+        self.done.push((cmd, undo));
+        self.undone.clear();
+        Ok(())
+    }
+
+    /// Revert the last applied command, returns false if there is nothing to undo
+    pub fn undo(&mut self, map: &mut Map) -> Result<bool, String> {
+        let Some((cmd, undo)) = self.done.pop() else {
+            return Ok(false);
+        };
+        revert(map, undo)?;
+        self.undone.push(cmd);
+        Ok(true)
+    }
+
+    /// Re-apply the last undone command, returns false if there is nothing to redo
+    pub fn redo(&mut self, map: &mut Map) -> Result<bool, String> {
+        let Some(cmd) = self.undone.pop() else {
+            return Ok(false);
+        };
+        match execute(map, &cmd) {
+            Ok(undo) => {
+                self.done.push((cmd, undo));
+                Ok(true)
+            }
+            Err(e) => {
+                self.undone.push(cmd);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+}
+
+fn execute(map: &mut Map, cmd: &EditCommand) -> Result<UndoOp, String> {
+    use EditCommand::*;
+    match cmd {
+        PlaceObject {
+            template,
+            position,
+            obj_type,
+        } => {
+            check_coord(map, position)?;
+            let expected = match ObjectType::from(template.id) {
+                Some(o) => o,
+                None => return Err(format!("place object: unknown object id {}", template.id)),
+            };
+            if std::mem::discriminant(&expected) != std::mem::discriminant(obj_type.as_ref()) {
+                return Err(format!(
+                    "place object: object type {obj_type:?} does not match template id {}",
+                    template.id
+                ));
+            }
+            let (obj_templ_id, drop_template) =
+                match map.object_templates.iter().position(|t| *t == **template) {
+                    Some(i) => (i, false),
+                    None => {
+                        map.object_templates.push(template.as_ref().clone());
+                        (map.object_templates.len() - 1, true)
+                    }
+                };
+            map.objects.push(Object {
+                position: *position,
+                obj_templ_id: obj_templ_id as u32,
+                obj_type: obj_type.as_ref().clone(),
+            });
+            Ok(UndoOp::RemoveObject {
+                index: map.objects.len() - 1,
+                drop_template,
+            })
+        }
+        RemoveObject { index } => {
+            check_object(map, *index)?;
+            let object = Box::new(map.objects.remove(*index));
+            Ok(UndoOp::InsertObject {
+                index: *index,
+                object,
+            })
+        }
+        MoveObject { index, to } => {
+            check_object(map, *index)?;
+            check_coord(map, to)?;
+            let from = map.objects[*index].position;
+            map.objects[*index].position = *to;
+            Ok(UndoOp::MoveObject {
+                index: *index,
+                to: from,
+            })
+        }
+        PaintTerrain { tiles, surface } => {
+            for c in tiles {
+                check_coord(map, c)?;
+            }
+            // transitions of the neighbours change as well, so the whole
+            // surrounding area is retiled and saved for undo
+            let regions = retile_regions(map, tiles);
+            let backup = backup_regions(map, &regions);
+            for c in tiles {
+                let tile = &mut map.terrains[c.z][c.x][c.y];
+                tile.surface_type = *surface;
+                if *surface == Surface::Water || *surface == Surface::Rock {
+                    // roads and rivers can't go through water or rock
+                    tile.road_type = None;
+                    tile.road_direction = 0;
+                    tile.river_type = None;
+                    tile.river_direction = 0;
+                }
+            }
+            for (z, xs, ys) in regions {
+                map_tiling::retile(&mut map.terrains[z], xs, ys);
+            }
+            Ok(UndoOp::RestoreTiles { tiles: backup })
+        }
+        PaintRoad { tiles, road } => {
+            for c in tiles {
+                check_coord(map, c)?;
+                if road.is_some() {
+                    let surface = map.terrains[c.z][c.x][c.y].surface_type;
+                    if surface == Surface::Water || surface == Surface::Rock {
+                        return Err(format!(
+                            "paint road: can't put a road on {surface:?} at {c:?}"
+                        ));
+                    }
+                }
+            }
+            // neighbouring road pieces change with the new connections
+            let regions = retile_regions(map, tiles);
+            let backup = backup_regions(map, &regions);
+            for c in tiles {
+                let tile = &mut map.terrains[c.z][c.x][c.y];
+                tile.road_type = *road;
+                tile.road_direction = 0;
+            }
+            for (z, xs, ys) in regions {
+                map_roads::connect_roads(&mut map.terrains[z], xs, ys);
+            }
+            Ok(UndoOp::RestoreTiles { tiles: backup })
+        }
+        SetOwner { index, owner } => {
+            check_object(map, *index)?;
+            let obj = &mut map.objects[*index];
+            let debug_type = format!("{:?}", obj.obj_type);
+            match obj.obj_type.owner_mut() {
+                Some(current) => {
+                    let previous = *current;
+                    *current = *owner;
+                    Ok(UndoOp::SetOwner {
+                        index: *index,
+                        owner: previous,
+                    })
+                }
+                None => Err(format!("set owner: object {debug_type} can't be owned")),
+            }
+        }
+    }
+}
+
+fn revert(map: &mut Map, undo: UndoOp) -> Result<(), String> {
+    use UndoOp::*;
+    match undo {
+        RemoveObject {
+            index,
+            drop_template,
+        } => {
+            check_object(map, index)?;
+            let obj = map.objects.remove(index);
+            if drop_template {
+                // the template was appended by this command, so it is the last one
+                // and nothing else can reference it
+                if obj.obj_templ_id as usize + 1 != map.object_templates.len() {
+                    return Err("undo place object: template list has been modified".to_string());
+                }
+                map.object_templates.pop();
+            }
+        }
+        InsertObject { index, object } => {
+            if index > map.objects.len() {
+                return Err(format!("undo remove object: index {index} out of range"));
+            }
+            map.objects.insert(index, *object);
+        }
+        MoveObject { index, to } => {
+            check_object(map, index)?;
+            map.objects[index].position = to;
+        }
+        RestoreTiles { tiles } => {
+            for (c, tile) in tiles {
+                map.terrains[c.z][c.x][c.y] = tile;
+            }
+        }
+        SetOwner { index, owner } => {
+            check_object(map, index)?;
+            if let Some(current) = map.objects[index].obj_type.owner_mut() {
+                *current = owner;
+            }
+        }
+    }
+    Ok(())
+}
+
+// bounding box of the painted tiles on each level, grown by the area
+// whose transitions depend on them
+fn retile_regions(map: &Map, tiles: &[MapCoord]) -> Vec<(usize, Range<usize>, Range<usize>)> {
+    const MARGIN: usize = 2;
+    let mut ret = Vec::new();
+    for z in 0..map.levels() {
+        let on_level = tiles.iter().filter(|c| c.z == z);
+        let (Some(x0), Some(x1)) = (
+            on_level.clone().map(|c| c.x).min(),
+            on_level.clone().map(|c| c.x).max(),
+        ) else {
+            continue;
+        };
+        let y0 = on_level.clone().map(|c| c.y).min().unwrap_or(0);
+        let y1 = on_level.map(|c| c.y).max().unwrap_or(0);
+        let size = map.dimension();
+        ret.push((
+            z,
+            x0.saturating_sub(MARGIN)..(x1 + MARGIN + 1).min(size),
+            y0.saturating_sub(MARGIN)..(y1 + MARGIN + 1).min(size),
+        ));
+    }
+    ret
+}
+
+fn backup_regions(
+    map: &Map,
+    regions: &[(usize, Range<usize>, Range<usize>)],
+) -> Vec<(MapCoord, TerrainTile)> {
+    let mut backup = Vec::new();
+    for (z, xs, ys) in regions {
+        for x in xs.clone() {
+            for y in ys.clone() {
+                backup.push((MapCoord { x, y, z: *z }, map.terrains[*z][x][y]));
+            }
+        }
+    }
+    backup
+}
+
+fn check_coord(map: &Map, coord: &MapCoord) -> Result<(), String> {
+    if map.contains(coord) {
+        Ok(())
+    } else {
+        Err(format!(
+            "coordinate {coord:?} is outside of the map (size {}, levels {})",
+            map.dimension(),
+            map.levels()
+        ))
+    }
+}
+
+fn check_object(map: &Map, index: usize) -> Result<(), String> {
+    if index < map.objects.len() {
+        Ok(())
+    } else {
+        Err(format!(
+            "object index {index} out of range, map has {} objects",
+            map.objects.len()
+        ))
+    }
+}
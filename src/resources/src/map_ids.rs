@@ -0,0 +1,384 @@
+use crate::map_reader::ParsingContext;
+use crate::map_structs::*;
+use std::fmt;
+
+// Typed identifiers of the game entities stored in maps, with their names
+// in the original game texts. Ids are in the order of the game text files
+// (HOTRAITS.TXT, SSTRAITS.TXT, SPTRAITS.TXT, CRTRAITS.TXT, ARTRAITS.TXT).
+// Names of HotA additions aren't listed, they are displayed by their number.
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct HeroId(pub u32);
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct SecSkillId(pub u32);
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct SpellId(pub u32);
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct CreatureId(pub u16);
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct ArtifactId(pub u32);
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct ArtifactSlot(pub u32);
+
+// formats adding new entities, in order
+const FORMATS: [Format; 4] = [Format::ROE, Format::AB, Format::SOD, Format::HOTA];
+// the latest HotA version known to the parser
+const HOTA_VERSION: usize = 3;
+
+// the first format whose count covers the id
+fn introduced_in(id: usize, count: fn(&ParsingContext) -> usize) -> Option<Format> {
+    FORMATS
+        .into_iter()
+        .find(|f| id < count(&ParsingContext::from(*f, HOTA_VERSION)))
+}
+
+fn name(names: &[&'static str], id: usize) -> Option<&'static str> {
+    names.get(id).copied().filter(|n| !n.is_empty())
+}
+
+impl HeroId {
+    pub fn name(&self) -> Option<&'static str> {
+        name(&HERO_NAMES, self.0 as usize)
+    }
+
+    /// The first format the hero can be used in, None for invalid ids
+    pub fn introduced_in(&self) -> Option<Format> {
+        introduced_in(self.0 as usize, |ctx| ctx.heroes_count)
+    }
+}
+
+impl SecSkillId {
+    pub fn name(&self) -> Option<&'static str> {
+        name(&SEC_SKILL_NAMES, self.0 as usize)
+    }
+
+    /// The first format the skill can be used in, None for invalid ids
+    pub fn introduced_in(&self) -> Option<Format> {
+        introduced_in(self.0 as usize, |ctx| ctx.skills_count)
+    }
+}
+
+impl SpellId {
+    pub fn name(&self) -> Option<&'static str> {
+        name(&SPELL_NAMES, self.0 as usize)
+    }
+
+    /// The first format the spell can be used in, None for invalid ids
+    pub fn introduced_in(&self) -> Option<Format> {
+        introduced_in(self.0 as usize, |ctx| ctx.spells_count)
+    }
+}
+
+impl CreatureId {
+    pub fn name(&self) -> Option<&'static str> {
+        name(&CREATURE_NAMES, self.0 as usize)
+    }
+
+    /// The first format the creature can be used in, None for invalid ids
+    pub fn introduced_in(&self) -> Option<Format> {
+        introduced_in(self.0 as usize, |ctx| ctx.creatures_count)
+    }
+
+    /// Name of a stack of more than one creature, e.g. "Pikemen"
+    pub fn plural_name(&self) -> Option<String> {
+        let name = self.name()?;
+        if let Some((_, plural)) = IRREGULAR_PLURALS.iter().find(|(n, _)| *n == name) {
+            return Some(plural.to_string());
+        }
+        let plural = if let Some(stem) = name.strip_suffix("man") {
+            format!("{stem}men")
+        } else if let Some(stem) = name
+            .strip_suffix('y')
+            .filter(|s| !s.ends_with(['a', 'e', 'o', 'u']))
+        {
+            format!("{stem}ies")
+        } else if name.ends_with(['s', 'x']) || name.ends_with("ch") {
+            format!("{name}es")
+        } else {
+            format!("{name}s")
+        };
+        Some(plural)
+    }
+}
+
+impl ArtifactId {
+    pub fn name(&self) -> Option<&'static str> {
+        name(&ARTIFACT_NAMES, self.0 as usize)
+    }
+
+    /// The first format the artifact can be used in, None for invalid ids
+    pub fn introduced_in(&self) -> Option<Format> {
+        introduced_in(self.0 as usize, |ctx| ctx.artifacts_count)
+    }
+}
+
+impl ArtifactSlot {
+    pub fn name(&self) -> Option<&'static str> {
+        name(&ARTIFACT_SLOT_NAMES, self.0 as usize)
+    }
+
+    /// The first format having the slot, None for invalid slots
+    pub fn introduced_in(&self) -> Option<Format> {
+        introduced_in(self.0 as usize, |ctx| ctx.artifact_slots_count)
+    }
+}
+
+impl fmt::Display for HeroId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(n) => write!(f, "{n}"),
+            None => write!(f, "hero #{}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for SecSkillId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(n) => write!(f, "{n}"),
+            None => write!(f, "skill #{}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for SpellId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(n) => write!(f, "{n}"),
+            None => write!(f, "spell #{}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for CreatureId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(n) => write!(f, "{n}"),
+            None => write!(f, "creature #{}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for ArtifactId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(n) => write!(f, "{n}"),
+            None => write!(f, "artifact #{}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for ArtifactSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(n) => write!(f, "{n}"),
+            None => write!(f, "slot #{}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for SecSkillLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            SecSkillLevel::Basic => "Basic",
+            SecSkillLevel::Advanced => "Advanced",
+            SecSkillLevel::Expert => "Expert",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// e.g. "Expert Wisdom"
+impl fmt::Display for SecSkill {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.level, self.id)
+    }
+}
+
+/// e.g. "Archangel x5", "empty" for a free slot
+impl fmt::Display for CreatureSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.creature {
+            Some(c) => write!(f, "{c} x{}", self.amount),
+            None => write!(f, "empty"),
+        }
+    }
+}
+
+#[rustfmt::skip]
+const HERO_NAMES: [&str; 156] = [
+    // Castle
+    "Orrin", "Valeska", "Edric", "Sylvia", "Lord Haart", "Sorsha", "Christian", "Tyris",
+    "Rion", "Adela", "Cuthbert", "Adelaide", "Ingham", "Sanya", "Loynis", "Caitlin",
+    // Rampart
+    "Mephala", "Ufretin", "Jenova", "Ryland", "Thorgrim", "Ivor", "Clancy", "Kyrre",
+    "Coronius", "Uland", "Elleshar", "Gem", "Malcom", "Melodia", "Alagar", "Aeris",
+    // Tower
+    "Piquedram", "Thane", "Josephine", "Neela", "Torosar", "Fafner", "Rissa", "Iona",
+    "Astral", "Halon", "Serena", "Daremyth", "Theodorus", "Solmyr", "Cyra", "Aine",
+    // Inferno
+    "Fiona", "Rashka", "Marius", "Ignatius", "Octavia", "Calh", "Pyre", "Nymus",
+    "Ayden", "Xyron", "Axsis", "Olema", "Calid", "Ash", "Zydar", "Xarfax",
+    // Necropolis
+    "Straker", "Vokial", "Moandor", "Charna", "Tamika", "Isra", "Clavius", "Galthran",
+    "Septienna", "Aislinn", "Sandro", "Nimbus", "Thant", "Xsi", "Vidomina", "Nagash",
+    // Dungeon
+    "Lorelei", "Arlach", "Dace", "Ajit", "Damacon", "Gunnar", "Synca", "Shakti",
+    "Alamar", "Jaegar", "Malekith", "Jeddite", "Geon", "Deemer", "Sephinroth", "Darkstorn",
+    // Stronghold
+    "Yog", "Gurnisson", "Jabarkas", "Shiva", "Gretchin", "Krellion", "Crag Hack", "Tyraxor",
+    "Gird", "Vey", "Dessa", "Terek", "Zubin", "Gundula", "Oris", "Saurug",
+    // Fortress
+    "Bron", "Drakon", "Wystan", "Tazar", "Alkin", "Korbac", "Gerwulf", "Broghild",
+    "Mirlanda", "Rosic", "Voy", "Verdish", "Merist", "Styg", "Andra", "Tiva",
+    // Conflux
+    "Pasis", "Thunar", "Ignissa", "Lacus", "Monere", "Erdamon", "Fiur", "Kalt",
+    "Luna", "Brissa", "Ciele", "Labetha", "Inteus", "Aenain", "Gelare", "Grindan",
+    // campaign heroes
+    "Sir Mullich", "Adrienne", "Catherine", "Dracon", "Gelu", "Kilgor", "Lord Haart", "Mutare",
+    "Roland", "Mutare Drake", "Boragus", "Xeron",
+];
+
+#[rustfmt::skip]
+const SEC_SKILL_NAMES: [&str; 29] = [
+    "Pathfinding", "Archery", "Logistics", "Scouting", "Diplomacy", "Navigation", "Leadership",
+    "Wisdom", "Mysticism", "Luck", "Ballistics", "Eagle Eye", "Necromancy", "Estates",
+    "Fire Magic", "Air Magic", "Water Magic", "Earth Magic", "Scholar", "Tactics", "Artillery",
+    "Learning", "Offense", "Armorer", "Intelligence", "Sorcery", "Resistance", "First Aid",
+    "Interference",
+];
+
+#[rustfmt::skip]
+const SPELL_NAMES: [&str; 70] = [
+    // adventure spells
+    "Summon Boat", "Scuttle Boat", "Visions", "View Earth", "Disguise", "View Air", "Fly",
+    "Water Walk", "Dimension Door", "Town Portal",
+    // combat spells
+    "Quicksand", "Land Mine", "Force Field", "Fire Wall", "Earthquake", "Magic Arrow", "Ice Bolt",
+    "Lightning Bolt", "Implosion", "Chain Lightning", "Frost Ring", "Fireball", "Inferno",
+    "Meteor Shower", "Death Ripple", "Destroy Undead", "Armageddon", "Shield", "Air Shield",
+    "Fire Shield", "Protection from Air", "Protection from Fire", "Protection from Water",
+    "Protection from Earth", "Anti-Magic", "Dispel", "Magic Mirror", "Cure", "Resurrection",
+    "Animate Dead", "Sacrifice", "Bless", "Curse", "Bloodlust", "Precision", "Weakness",
+    "Stone Skin", "Disrupting Ray", "Prayer", "Mirth", "Sorrow", "Fortune", "Misfortune", "Haste",
+    "Slow", "Slayer", "Frenzy", "Titan's Lightning Bolt", "Counterstrike", "Berserk", "Hypnotize",
+    "Forgetfulness", "Blind", "Teleport", "Remove Obstacle", "Clone", "Fire Elemental",
+    "Earth Elemental", "Water Elemental", "Air Elemental",
+];
+
+// empty names are unused ids
+#[rustfmt::skip]
+const CREATURE_NAMES: [&str; 145] = [
+    // Castle
+    "Pikeman", "Halberdier", "Archer", "Marksman", "Griffin", "Royal Griffin", "Swordsman",
+    "Crusader", "Monk", "Zealot", "Cavalier", "Champion", "Angel", "Archangel",
+    // Rampart
+    "Centaur", "Centaur Captain", "Dwarf", "Battle Dwarf", "Wood Elf", "Grand Elf", "Pegasus",
+    "Silver Pegasus", "Dendroid Guard", "Dendroid Soldier", "Unicorn", "War Unicorn",
+    "Green Dragon", "Gold Dragon",
+    // Tower
+    "Gremlin", "Master Gremlin", "Stone Gargoyle", "Obsidian Gargoyle", "Stone Golem",
+    "Iron Golem", "Mage", "Arch Mage", "Genie", "Master Genie", "Naga", "Naga Queen", "Giant",
+    "Titan",
+    // Inferno
+    "Imp", "Familiar", "Gog", "Magog", "Hell Hound", "Cerberus", "Demon", "Horned Demon",
+    "Pit Fiend", "Pit Lord", "Efreeti", "Efreet Sultan", "Devil", "Arch Devil",
+    // Necropolis
+    "Skeleton", "Skeleton Warrior", "Walking Dead", "Zombie", "Wight", "Wraith", "Vampire",
+    "Vampire Lord", "Lich", "Power Lich", "Black Knight", "Dread Knight", "Bone Dragon",
+    "Ghost Dragon",
+    // Dungeon
+    "Troglodyte", "Infernal Troglodyte", "Harpy", "Harpy Hag", "Beholder", "Evil Eye", "Medusa",
+    "Medusa Queen", "Minotaur", "Minotaur King", "Manticore", "Scorpicore", "Red Dragon",
+    "Black Dragon",
+    // Stronghold
+    "Goblin", "Hobgoblin", "Wolf Rider", "Wolf Raider", "Orc", "Orc Chieftain", "Ogre",
+    "Ogre Mage", "Roc", "Thunderbird", "Cyclops", "Cyclops King", "Behemoth", "Ancient Behemoth",
+    // Fortress
+    "Gnoll", "Gnoll Marauder", "Lizardman", "Lizard Warrior", "Gorgon", "Mighty Gorgon",
+    "Serpent Fly", "Dragon Fly", "Basilisk", "Greater Basilisk", "Wyvern", "Wyvern Monarch",
+    "Hydra", "Chaos Hydra",
+    // neutrals
+    "Air Elemental", "Earth Elemental", "Fire Elemental", "Water Elemental", "Gold Golem",
+    "Diamond Golem",
+    // Conflux
+    "Pixie", "Sprite", "Psychic Elemental", "Magic Elemental", "", "Ice Elemental", "",
+    "Magma Elemental", "", "Storm Elemental", "", "Energy Elemental", "Firebird", "Phoenix",
+    // neutrals
+    "Azure Dragon", "Crystal Dragon", "Faerie Dragon", "Rust Dragon", "Enchanter", "Sharpshooter",
+    "Halfling", "Peasant", "Boar", "Mummy", "Nomad", "Rogue", "Troll",
+];
+
+// plurals not following the english rules used by plural_name
+const IRREGULAR_PLURALS: [(&str, &str); 12] = [
+    ("Dwarf", "Dwarves"),
+    ("Battle Dwarf", "Battle Dwarves"),
+    ("Wood Elf", "Wood Elves"),
+    ("Grand Elf", "Grand Elves"),
+    ("Pegasus", "Pegasi"),
+    ("Silver Pegasus", "Silver Pegasi"),
+    ("Arch Mage", "Arch Magi"),
+    ("Cerberus", "Cerberi"),
+    ("Cyclops", "Cyclopes"),
+    ("Efreeti", "Efreet"),
+    ("Walking Dead", "Walking Dead"),
+    ("Ogre Mage", "Ogre Magi"),
+];
+
+#[rustfmt::skip]
+const ARTIFACT_NAMES: [&str; 144] = [
+    "Spell Book", "Spell Scroll", "Grail", "Catapult", "Ballista", "Ammo Cart", "First Aid Tent",
+    "Centaur's Axe", "Blackshard of the Dead Knight", "Greater Gnoll's Flail",
+    "Ogre's Club of Havoc", "Sword of Hellfire", "Titan's Gladius", "Shield of the Dwarven Lords",
+    "Shield of the Yawning Dead", "Buckler of the Gnoll King", "Targ of the Rampaging Ogre",
+    "Shield of the Damned", "Sentinel's Shield", "Helm of the Alabaster Unicorn", "Skull Helmet",
+    "Helm of Chaos", "Crown of the Supreme Magi", "Hellstorm Helmet", "Thunder Helmet",
+    "Breastplate of Petrified Wood", "Rib Cage", "Scales of the Greater Basilisk",
+    "Tunic of the Cyclops King", "Breastplate of Brimstone", "Titan's Cuirass", "Armor of Wonder",
+    "Sandals of the Saint", "Celestial Necklace of Bliss", "Lion's Shield of Courage",
+    "Sword of Judgement", "Helm of Heavenly Enlightenment", "Quiet Eye of the Dragon",
+    "Red Dragon Flame Tongue", "Dragon Scale Shield", "Dragon Scale Armor", "Dragonbone Greaves",
+    "Dragon Wing Tabard", "Necklace of Dragonteeth", "Crown of Dragontooth",
+    "Still Eye of the Dragon", "Clover of Fortune", "Cards of Prophecy", "Ladybird of Luck",
+    "Badge of Courage", "Crest of Valor", "Glyph of Gallantry", "Speculum", "Spyglass",
+    "Amulet of the Undertaker", "Vampire's Cowl", "Dead Man's Boots", "Garniture of Interference",
+    "Surcoat of Counterpoise", "Boots of Polarity", "Bow of Elven Cherrywood",
+    "Bowstring of the Unicorn's Mane", "Angel Feather Arrows", "Bird of Perception",
+    "Stoic Watchman", "Emblem of Cognizance", "Statesman's Medal", "Diplomat's Ring",
+    "Ambassador's Sash", "Ring of the Wayfarer", "Equestrian's Gloves",
+    "Necklace of Ocean Guidance", "Angel Wings", "Charm of Mana", "Talisman of Mana",
+    "Mystic Orb of Mana", "Collar of Conjuring", "Ring of Conjuring", "Cape of Conjuring",
+    "Orb of the Firmament", "Orb of Silt", "Orb of Tempestuous Fire", "Orb of Driving Rain",
+    "Recanter's Cloak", "Spirit of Oppression", "Hourglass of the Evil Hour", "Tome of Fire Magic",
+    "Tome of Air Magic", "Tome of Water Magic", "Tome of Earth Magic", "Boots of Levitation",
+    "Golden Bow", "Sphere of Permanence", "Orb of Vulnerability", "Ring of Vitality",
+    "Ring of Life", "Vial of Lifeblood", "Necklace of Swiftness", "Boots of Speed",
+    "Cape of Velocity", "Pendant of Dispassion", "Pendant of Second Sight", "Pendant of Holiness",
+    "Pendant of Life", "Pendant of Death", "Pendant of Free Will", "Pendant of Negativity",
+    "Pendant of Total Recall", "Pendant of Courage", "Everflowing Crystal Cloak",
+    "Ring of Infinite Gems", "Everpouring Vial of Mercury", "Inexhaustible Cart of Ore",
+    "Eversmoking Ring of Sulfur", "Inexhaustible Cart of Lumber", "Endless Sack of Gold",
+    "Endless Bag of Gold", "Endless Purse of Gold", "Legs of Legion", "Loins of Legion",
+    "Torso of Legion", "Arms of Legion", "Head of Legion", "Sea Captain's Hat",
+    "Spellbinder's Hat", "Shackles of War", "Orb of Inhibition",
+    // AB
+    "Vial of Dragon Blood", "Armageddon's Blade",
+    // SoD combination artifacts
+    "Angelic Alliance", "Cloak of the Undead King", "Elixir of Life", "Armor of the Damned",
+    "Statue of Legion", "Power of the Dragon Father", "Titan's Thunder", "Admiral's Hat",
+    "Bow of the Sharpshooter", "Wizard's Well", "Ring of the Magi", "Cornucopia",
+    // unfinished, but used by some maps
+    "Magic Wand", "Gold Tower Arrow", "Monster's Power",
+];
+
+#[rustfmt::skip]
+const ARTIFACT_SLOT_NAMES: [&str; 19] = [
+    "Head", "Shoulders", "Neck", "Right Hand", "Left Hand", "Torso", "Right Ring", "Left Ring",
+    "Feet", "Misc 1", "Misc 2", "Misc 3", "Misc 4", "War Machine 1", "War Machine 2",
+    "War Machine 3", "War Machine 4", "Spell Book", "Misc 5",
+];
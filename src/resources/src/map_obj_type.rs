@@ -89,9 +89,9 @@ pub enum ObjectType {
     Shipwreck(BankData),
     ShipwreckSurvivor,
     Shipyard{owner: Option<Ownership>},
-    ShrineOfMagicIncantation{spell_id: u32},
-    ShrineOfMagicGesture{spell_id: u32},
-    ShrineOfMagicThought{spell_id: u32},
+    ShrineOfMagicIncantation{spell_id: Option<SpellId>},
+    ShrineOfMagicGesture{spell_id: Option<SpellId>},
+    ShrineOfMagicThought{spell_id: Option<SpellId>},
     Sign(String),
     Sirens,
     SpellScroll(SpellScrollData),
@@ -114,7 +114,7 @@ pub enum ObjectType {
     WateringHole,
     Whirlpool,
     Windmill,
-    WitchHut{secondary_skills: Vec<u8>},
+    WitchHut{secondary_skills: Vec<SecSkillId>},
     Brush, // Todo: How does it look like?
     Bush,
     Cactus,
@@ -161,7 +161,7 @@ pub enum ObjectType {
     RandomMonsterL7(MonsterData),
     BorderGate,
     FreelancersGuild,
-    HeroPlaceholder{owner: Option<Ownership>, hero_id: u32},
+    HeroPlaceholder{owner: Option<Ownership>, hero_id: Option<HeroId>},
     QuestGuard(QuestMission),
     RandomDwelling(RandomDwellingData),
     RandomDwellingLvl(RandomDwellingData),     //subtype = creature level
@@ -271,9 +271,9 @@ impl ObjectType {
             85 => Shipwreck(BankData::default()),
             86 => ShipwreckSurvivor,
             87 => Shipyard{owner: None},
-            88 => ShrineOfMagicIncantation{spell_id : 0},
-            89 => ShrineOfMagicGesture{spell_id : 0},
-            90 => ShrineOfMagicThought{spell_id : 0},
+            88 => ShrineOfMagicIncantation{spell_id : None},
+            89 => ShrineOfMagicGesture{spell_id : None},
+            90 => ShrineOfMagicThought{spell_id : None},
             91 => Sign(String::new()),
             92 => Sirens,
             93 => SpellScroll(SpellScrollData::default()),
@@ -343,7 +343,7 @@ impl ObjectType {
             164 => RandomMonsterL7(MonsterData::default()),
             212 => BorderGate,
             213 => FreelancersGuild,
-            214 => HeroPlaceholder{owner: None, hero_id: 0},
+            214 => HeroPlaceholder{owner: None, hero_id: None},
             215 => QuestGuard(QuestMission::default()),
             216 => RandomDwelling(RandomDwellingData::default()),
             217 => RandomDwellingLvl(RandomDwellingData::default()),     //subtype = creature level
@@ -366,6 +366,50 @@ impl ObjectType {
         };
         Some(ret)
     }
+
+    /// Access the owner field of flaggable objects, None if the object can't be owned
+    pub fn owner_mut(&mut self) -> Option<&mut Option<Ownership>> {
+        use ObjectType::*;
+        match self {
+            Shipyard { ref mut owner }
+            | Lighthouse { ref mut owner }
+            | CreatureGenerator1 { ref mut owner }
+            | CreatureGenerator2 { ref mut owner }
+            | CreatureGenerator3 { ref mut owner }
+            | CreatureGenerator4 { ref mut owner }
+            | HeroPlaceholder {
+                ref mut owner,
+                ..
+            } => Some(owner),
+            Mine(ref mut m) | AbandonedMine(ref mut m) => Some(&mut m.owner),
+            Hero(ref mut h) | RandomHero(ref mut h) | Prison(ref mut h) => Some(&mut h.owner),
+            Garrison(ref mut d) | Garrison2(ref mut d) => Some(&mut d.owner),
+            Town(ref mut d) | RandomTown(ref mut d) => Some(&mut d.owner),
+            RandomDwelling(ref mut d)
+            | RandomDwellingLvl(ref mut d)
+            | RandomDwellingFaction(ref mut d) => Some(&mut d.owner),
+            _ => None,
+        }
+    }
+
+    pub fn owner(&self) -> Option<Ownership> {
+        use ObjectType::*;
+        match self {
+            Shipyard { owner }
+            | Lighthouse { owner }
+            | CreatureGenerator1 { owner }
+            | CreatureGenerator2 { owner }
+            | CreatureGenerator3 { owner }
+            | CreatureGenerator4 { owner }
+            | HeroPlaceholder { owner, .. } => *owner,
+            Mine(m) | AbandonedMine(m) => m.owner,
+            Hero(h) | RandomHero(h) | Prison(h) => h.owner,
+            Garrison(d) | Garrison2(d) => d.owner,
+            Town(d) | RandomTown(d) => d.owner,
+            RandomDwelling(d) | RandomDwellingLvl(d) | RandomDwellingFaction(d) => d.owner,
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -379,7 +423,9 @@ pub struct EventData {
 
 #[derive(Debug, Default, Clone)]
 pub struct MonsterData {
-    pub id: u32,
+    pub quest_id: u32,
+    // None for random monsters
+    pub creature: Option<CreatureId>,
     pub amount: u32,
     pub character: u8,
     pub message: Option<String>,
@@ -404,7 +450,8 @@ pub struct MineData {
 pub struct HeroData {
     pub quest_id: u32,
     pub owner: Option<Ownership>,
-    pub hero_id: u32,
+    // None for random heroes
+    pub hero_id: Option<HeroId>,
     pub name: Option<String>,
     pub experience: Option<u32>,
     pub portrait_id: Option<u8>,
@@ -416,14 +463,14 @@ pub struct HeroData {
     pub patrol_radius: u8,
     pub custom_biography: Option<String>,
     pub gender: Option<Gender>,
-    pub custom_spells: Vec<u8>,
+    pub custom_spells: Vec<SpellId>,
     pub custom_primary_skills: Option<PrimarySkills>,
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct SpellScrollData {
     pub guards: Option<CreatureGuard>,
-    pub spell_scroll_id: u32,
+    pub spell_scroll_id: SpellId,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -452,7 +499,7 @@ pub enum QuestMissionType {
     Artifact(Vec<ArtifactId>),
     Army(Vec<(CreatureId, u32)>),
     Resources(ResourcePack),
-    Hero(u8),
+    Hero(HeroId),
     Player(Option<Player>),
     HOTAMulti,
     Keymaster,
@@ -472,7 +519,7 @@ impl QuestMissionType {
             5 => Artifact(Vec::new()),
             6 => Army(Vec::new()),
             7 => Resources(ResourcePack::default()),
-            8 => Hero(0),
+            8 => Hero(HeroId::default()),
             9 => Player(None),
             10 => HOTAMulti,
             11 => Keymaster,
@@ -495,7 +542,7 @@ pub enum SeerHutRewardType {
     PrimarySkills(PrimarySkills),
     SecondarySkills(Vec<SecSkill>),
     Artifact(Vec<ArtifactId>),
-    Spell(Vec<u8>),
+    Spell(Vec<SpellId>),
     Creature(Vec<(CreatureId, u32)>),
 }
 
@@ -532,8 +579,8 @@ pub struct SeerHutData {
 #[derive(Default, Debug, Clone)]
 pub enum ScholarBonus {
     PrimarySkill(u8),
-    SecondarySkill(u8),
-    Spell(u8),
+    SecondarySkill(SecSkillId),
+    Spell(SpellId),
     #[default]
     Random,
 }
@@ -543,8 +590,8 @@ impl ScholarBonus {
         use ScholarBonus::*;
         match code {
             0 => PrimarySkill(id),
-            1 => SecondarySkill(id),
-            2 => Spell(id),
+            1 => SecondarySkill(SecSkillId(id as u32)),
+            2 => Spell(SpellId(id as u32)),
             0xff => Random,
             _ => Random,
         }
@@ -580,8 +627,8 @@ pub struct TownData {
     pub army_formation: ArmyFormation, 
     pub built_buildings: Vec<Buildings>,
     pub forbidden_buildings: Vec<Buildings>,
-    pub obligatory_spells: Vec<u8>,
-    pub possible_spells: Vec<u8>,
+    pub obligatory_spells: Vec<SpellId>,
+    pub possible_spells: Vec<SpellId>,
     pub events: Vec<TownEvent>,
     pub alignment_to_player: Option<Player>,
 }
@@ -591,6 +638,7 @@ pub struct RandomDwellingData {
     pub owner: Option<Ownership>,
     pub rnd_info_id: Option<u32>,
     pub factions: Option<Vec<Town>>,
+    /// dwelling levels 1-7 as stored in the file
     pub rnd_info_min_lev: Option<u8>,
     pub rnd_info_max_lev: Option<u8>,
 }
@@ -0,0 +1,203 @@
+use crate::map_obj_type::ObjectType;
+use crate::map_structs::*;
+
+// Which tiles a hero walking on land can enter. Object cells come from the
+// template masks; a visitable cell can be entered to visit the object, but
+// the hero can't walk through it. Tiles next to a monster are its zone of
+// control: entering them starts a fight.
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TileState {
+    #[default]
+    Free,
+    /// rock, water or a blocking object cell
+    Blocked,
+    /// visitable object cell, entering it ends the movement
+    Visitable,
+    /// monster or a tile in its zone of control
+    Guarded,
+}
+
+#[derive(Debug, Clone)]
+pub struct Passability {
+    dimension: usize,
+    /// indexed as [z][x][y], like Map::terrains
+    tiles: Vec<Vec<Vec<TileState>>>,
+}
+
+/// Movement points a step from a tile of this surface costs, None if heroes can't walk there
+pub fn surface_cost(surface: Surface) -> Option<u32> {
+    use Surface::*;
+    match surface {
+        Dirt | Grass | Subterranean | Lava => Some(100),
+        Rough | Highlands => Some(125),
+        Sand | Snow | Wasteland => Some(150),
+        Swamp => Some(175),
+        Water | Rock => None,
+    }
+}
+
+/// Movement points a step along a road costs, both tiles need a road
+pub fn road_cost(road: RoadType) -> u32 {
+    match road {
+        RoadType::Dirt => 75,
+        RoadType::Gravel => 65,
+        RoadType::Cobblestone => 50,
+    }
+}
+
+/// Movement points of a straight step between neighbouring tiles: the slower
+/// road when both tiles have one, otherwise what `surface` charges for leaving
+/// the surface of the first tile
+pub fn step_cost(from: &TerrainTile, to: &TerrainTile, surface: impl Fn(Surface) -> u32) -> u32 {
+    match (from.road_type, to.road_type) {
+        (Some(a), Some(b)) => road_cost(a).max(road_cost(b)),
+        _ => surface(from.surface_type),
+    }
+}
+
+/// A diagonal step costs sqrt(2) times a straight one
+pub fn diagonal_cost(straight: u32) -> u32 {
+    straight * 1414 / 1000
+}
+
+pub fn is_monster(obj_type: &ObjectType) -> bool {
+    use ObjectType::*;
+    matches!(
+        obj_type,
+        Monster(_)
+            | RandomMonster(_)
+            | RandomMonsterL1(_)
+            | RandomMonsterL2(_)
+            | RandomMonsterL3(_)
+            | RandomMonsterL4(_)
+            | RandomMonsterL5(_)
+            | RandomMonsterL6(_)
+            | RandomMonsterL7(_)
+    )
+}
+
+/// Map tiles of the object with the given transit property
+pub fn object_tiles(map: &Map, obj: &Object, property: TileTransitProperty) -> Vec<MapCoord> {
+    let Some(template) = map.object_templates.get(obj.obj_templ_id as usize) else {
+        return Vec::new();
+    };
+    let mut ret = Vec::new();
+    for (dy, row) in template.transit_matrix.iter().enumerate() {
+        for (dx, cell) in row.iter().enumerate() {
+            if *cell != property {
+                continue;
+            }
+            let (Some(x), Some(y)) = (
+                obj.position.x.checked_sub(dx),
+                obj.position.y.checked_sub(dy),
+            ) else {
+                continue;
+            };
+            let coord = MapCoord {
+                x,
+                y,
+                ..obj.position
+            };
+            if map.contains(&coord) {
+                ret.push(coord);
+            }
+        }
+    }
+    ret
+}
+
+/// Objects a coordinate stored in the map refers to, the ones it is a
+/// visitable tile of first. Player info and conditions store a town by its
+/// entrance, two tiles left of the anchor, and other objects by their anchor,
+/// so both match.
+pub fn objects_at(map: &Map, c: MapCoord) -> Vec<usize> {
+    let visited = map
+        .objects
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| object_tiles(map, o, TileTransitProperty::Visitable).contains(&c));
+    let anchored = map
+        .objects
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| o.position == c);
+    let mut ret: Vec<usize> = visited.map(|(i, _)| i).collect();
+    for (i, _) in anchored {
+        if !ret.contains(&i) {
+            ret.push(i);
+        }
+    }
+    ret
+}
+
+impl Passability {
+    pub fn from_map(map: &Map) -> Self {
+        let dimension = map.dimension();
+        let mut tiles = map
+            .terrains
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .map(|column| {
+                        column
+                            .iter()
+                            .map(|t| match surface_cost(t.surface_type) {
+                                Some(_) => TileState::Free,
+                                None => TileState::Blocked,
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect::<Vec<Vec<Vec<_>>>>();
+        for obj in map.objects.iter() {
+            for c in object_tiles(map, obj, TileTransitProperty::TransitBlocked) {
+                tiles[c.z][c.x][c.y] = TileState::Blocked;
+            }
+        }
+        // entrances of objects win over the decorations around them
+        for obj in map.objects.iter() {
+            for c in object_tiles(map, obj, TileTransitProperty::Visitable) {
+                tiles[c.z][c.x][c.y] = TileState::Visitable;
+            }
+        }
+        for obj in map.objects.iter().filter(|o| is_monster(&o.obj_type)) {
+            let p = obj.position;
+            if map.contains(&p) {
+                tiles[p.z][p.x][p.y] = TileState::Guarded;
+            }
+            for (dx, dy) in crate::map_tiling::NEIGHBOURS {
+                let (Some(x), Some(y)) = (p.x.checked_add_signed(dx), p.y.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                let c = MapCoord { x, y, z: p.z };
+                // objects next to the monster can't be visited without a fight either
+                if map.contains(&c) && tiles[c.z][c.x][c.y] != TileState::Blocked {
+                    tiles[c.z][c.x][c.y] = TileState::Guarded;
+                }
+            }
+        }
+        Self { dimension, tiles }
+    }
+
+    /// Blocked outside of the map
+    pub fn get(&self, c: MapCoord) -> TileState {
+        self.tiles
+            .get(c.z)
+            .and_then(|level| level.get(c.x))
+            .and_then(|column| column.get(c.y))
+            .copied()
+            .unwrap_or(TileState::Blocked)
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    pub fn levels(&self) -> usize {
+        self.tiles.len()
+    }
+}
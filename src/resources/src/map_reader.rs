@@ -2,42 +2,149 @@ use crate::map_buildings::*;
 use crate::map_obj_type::*;
 use crate::map_structs::*;
 use crate::reader::BinaryDataReader;
-use std::io;
+pub use crate::reader::SkippedPadding;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
 use std::path::Path;
 
 // this logic is heavily based on the VCMI implementation
 //
 pub fn load_h3m(filename: &Path) -> io::Result<Map> {
-    if !filename.exists() {
-        panic!("load_h3m: file {filename:?} does not exist");
-    }
-
     if !filename.is_file() {
-        panic!("load_h3m: {filename:?} is not a file");
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("load_h3m: {filename:?} is not a file"),
+        ));
     }
     let mut reader = BinaryDataReader::new_possibly_gzip(std::fs::read(&filename)?)?;
     Ok(parse_map(&mut reader)?)
 }
 
+/// Parse a map held in memory, e.g. a LOD entry or an uploaded file.
+/// The data may be gzip compressed, like .h3m files usually are.
+pub fn parse_h3m_bytes(data: &[u8]) -> io::Result<Map> {
+    let mut reader = BinaryDataReader::from_slice_possibly_gzip(data)?;
+    parse_map(&mut reader)
+}
+
+/// Things the strict parsing noticed that may mean the parser has drifted
+/// from the real layout of the file
+#[derive(Debug, Default, Clone)]
+pub struct StrictReport {
+    /// bytes left after the events, the last section of the map
+    pub trailing_bytes: usize,
+    pub nonzero_padding: Vec<SkippedPadding>,
+}
+
+/// Parse a map failing on any read or skip past the end of the data
+pub fn parse_h3m_strict(data: &[u8]) -> io::Result<(Map, StrictReport)> {
+    let mut reader = BinaryDataReader::from_slice_possibly_gzip(data)?;
+    reader.set_strict(true);
+    let map = parse_map(&mut reader)?;
+    let report = StrictReport {
+        trailing_bytes: reader.remaining(),
+        nonzero_padding: reader.take_nonzero_padding(),
+    };
+    Ok((map, report))
+}
+
+/// Parse a map from any source, the data may be gzip compressed
+pub fn parse_h3m_reader(mut source: impl Read) -> io::Result<Map> {
+    let mut data = Vec::new();
+    source.read_to_end(&mut data)?;
+    let mut reader = BinaryDataReader::new_possibly_gzip(data)?;
+    parse_map(&mut reader)
+}
+
+/// The part of the map the parser is in
+#[derive(Debug, Clone, Default)]
+pub struct ParsePosition {
+    pub section: &'static str,
+    /// None until the header is read
+    pub format: Option<Format>,
+    pub hota_version: usize,
+}
+
+/// Parsing error with its location, carried inside the returned io::Error
+#[derive(Debug)]
+pub struct MapParseError {
+    pub position: ParsePosition,
+    /// offset in the (decompressed) data where the error happened
+    pub offset: u64,
+    pub source: io::Error,
+}
+
+impl fmt::Display for MapParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at offset {:#x}: {}",
+            self.position.section, self.offset, self.source
+        )
+    }
+}
+
+impl Error for MapParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl MapParseError {
+    /// The location of the error, if it came from the map parser
+    pub fn from_io(e: &io::Error) -> Option<&MapParseError> {
+        e.get_ref()?.downcast_ref::<MapParseError>()
+    }
+}
+
 fn parse_map(reader: &mut BinaryDataReader) -> io::Result<Map> {
+    let mut position = ParsePosition::default();
+    parse_map_tracked(reader, &mut position)
+}
+
+// `position` is kept up to date, so it tells where the parser was even if it panics
+pub(crate) fn parse_map_tracked(
+    reader: &mut BinaryDataReader,
+    position: &mut ParsePosition,
+) -> io::Result<Map> {
+    parse_sections(reader, position).map_err(|source| {
+        io::Error::new(
+            source.kind(),
+            MapParseError {
+                position: position.clone(),
+                offset: reader.position(),
+                source,
+            },
+        )
+    })
+}
+
+fn parse_sections(reader: &mut BinaryDataReader, pos: &mut ParsePosition) -> io::Result<Map> {
+    pos.section = "header";
     let map_format = reader.read_u32_le()?;
     let format = match Format::from(map_format) {
         Some(f) => f,
         None => return Err(gen_error("Unknown map format")),
     };
 
+    if format == Format::VCMI {
+        return Err(gen_error("VCMI maps are not supported"));
+    }
+    pos.format = Some(format);
     let mut ctx = ParsingContext::from(format, 0);
 
     if format == Format::HOTA {
         // TODO: HOTA format not supported yet
         let hota_version = reader.read_u32_le()?;
+        pos.hota_version = hota_version as usize;
         ctx = ParsingContext::from(format, hota_version as usize);
         if hota_version > 0 {
             let _is_mirror_map = reader.read_bool()?;
             let _is_arena_map = reader.read_bool()?;
         }
         if hota_version > 1 {
-            let _ = reader.read_u32_le(); // always equal to 12?
+            let _ = reader.read_u32_le()?; // always equal to 12?
         }
     }
     let any_players = reader.read_bool()?;
@@ -49,6 +156,9 @@ fn parse_map(reader: &mut BinaryDataReader) -> io::Result<Map> {
         Some(d) => d,
         None => return Err(gen_error("Unknown difficulty level")),
     };
+    if map_dimension <= 0 || map_dimension as usize > MapSize::G.dimension() {
+        return Err(gen_error(&format!("Invalid map size {map_dimension}")));
+    }
     let hero_level_limit = if ctx.level_AB {
         Some(reader.read_u8()? as usize)
     } else {
@@ -64,38 +174,51 @@ fn parse_map(reader: &mut BinaryDataReader) -> io::Result<Map> {
         difficulty,
         hero_level_limit,
     };
+    pos.section = "players";
     let players = parse_player_info(reader, &ctx)?;
     // read victory loss conditions
+    pos.section = "win_loss_cond";
     let win_loss_cond = parse_win_loss_cond(reader, &ctx)?;
     // println!("{win_loss_cond:?}");
     // read team info
+    pos.section = "teams";
     let team_info = parse_team_info(reader, &ctx)?;
     // println!("{team_info:?}");
     // read all allowed heroes & read disposed heroes
+    pos.section = "heroes";
     let heroes_def = parse_heroes_def(reader, &ctx)?;
     // println!("{heroes_def:?}");
     // read map options
+    pos.section = "map_options";
     let map_options = parse_map_options(reader, &ctx)?;
     // println!("{map_options:?}");
     // read allowed artifacts
+    pos.section = "allowed_artifacts";
     let allowed_artifacts = parse_allowed_artifacts(reader, &ctx)?;
     // read allowed spell abilities
+    pos.section = "allowed_spells";
     let allowed_spells = parse_allowed_spells(reader, &ctx)?;
     // read rumors
+    pos.section = "rumors";
     let rumors = parse_rumors(reader, &ctx)?;
     // read predefined heroes
+    pos.section = "predefined_heroes";
     let predefined_heroes = parse_predefined_heroes(reader, &ctx)?;
     // println!("{predefined_heroes:?}");
     // read terrain
     let levels_no = if info.two_levels { 2 } else { 1 };
     let map_size = info.map_dimension as usize;
+    pos.section = "terrain";
     let terrains = parse_terrain(reader, &ctx, levels_no, map_size)?;
     // read object templates
+    pos.section = "object_templates";
     let object_templates = parse_object_templates(reader, &ctx)?;
     // println!("{object_templates:?}");
     // read objects
+    pos.section = "objects";
     let objects = parse_objects(reader, &ctx, &object_templates)?;
     // read events
+    pos.section = "events";
     let events = parse_events(reader, &ctx)?;
     Ok(Map {
         info,
@@ -127,13 +250,13 @@ fn parse_player_info(
         if !(can_be_human || can_be_computer) {
             // inactive player
             if ctx.level_ROE {
-                reader.skip_n(6);
+                reader.skip_n(6)?;
             }
             if ctx.level_AB {
-                reader.skip_n(6)
+                reader.skip_n(6)?;
             }
             if ctx.level_SOD {
-                reader.skip_n(1);
+                reader.skip_n(1)?;
             }
             players.push(PlayerInfo {
                 player,
@@ -148,7 +271,7 @@ fn parse_player_info(
             None => return Err(gen_error("Unknown player behaviour")),
         };
         if ctx.level_SOD {
-            reader.skip_n(1);
+            reader.skip_n(1)?;
         }
         // TODO: add support for factory (HOTA4?)
         let faction_towns = read_bitmask_factions(reader, ctx)?;
@@ -172,7 +295,7 @@ fn parse_player_info(
             if ctx.level_AB {
                 generate_hero_at_main_town = reader.read_bool()?;
                 // Type of town: FF - Random town, others correspond to 0 - Castle etc.
-                reader.skip_n(1);
+                reader.skip_n(1)?;
             }
             main_town_position = Some(read_coord(reader)?);
         } else {
@@ -186,7 +309,7 @@ fn parse_player_info(
             let portrait_id = Some(reader.read_u8()?);
             let name = reader.read_string_le()?;
             Some(Hero {
-                id: hero_type_id,
+                id: HeroId(hero_type_id as u32),
                 portrait_id,
                 name,
             })
@@ -196,10 +319,10 @@ fn parse_player_info(
         // other heroes
         let mut other_heroes = Vec::new();
         if ctx.level_AB {
-            reader.skip_n(1);
+            reader.skip_n(1)?;
             let hero_count = reader.read_u32_le()?;
             for _ in 0..hero_count {
-                let id = reader.read_u8()?;
+                let id = HeroId(reader.read_u8()? as u32);
                 let name = reader.read_string_le()?;
                 other_heroes.push(Hero {
                     id,
@@ -251,11 +374,11 @@ fn parse_win_loss_cond(
         }
         1 => {
             // gather creatures
-            let unit_code = if ctx.level_AB {
+            let unit_code = CreatureId(if ctx.level_AB {
                 reader.read_u16_le()?
             } else {
                 reader.read_u8()? as u16
-            };
+            });
             let amount = reader.read_u32_le()?;
             Some(AccumulateCreatures { unit_code, amount })
         }
@@ -392,17 +515,23 @@ fn parse_heroes_def(reader: &mut BinaryDataReader, ctx: &ParsingContext) -> io::
     // read allowed heroes
     let allowed_heroes = if ctx.level_HOTA0 {
         let heroes_count = reader.read_u32_le()?;
-        assert!(heroes_count < 256);
+        if heroes_count >= 256 {
+            return Err(gen_error(&format!("Too many heroes: {heroes_count}")));
+        }
         map_bits_to_numbers(reader, heroes_count as u8)?
     } else {
         map_bits_to_numbers(reader, ctx.heroes_count as u8)?
     };
+    let allowed_heroes = allowed_heroes
+        .into_iter()
+        .map(|h| HeroId(h as u32))
+        .collect();
     let mut reserved_for_campaign = Vec::new();
     if ctx.level_AB {
         let heroes_no = reader.read_u32_le()?;
         for _ in 0..heroes_no {
             let hero_id = reader.read_u8()?;
-            reserved_for_campaign.push(hero_id);
+            reserved_for_campaign.push(HeroId(hero_id as u32));
         }
     }
     // read disposed heroes
@@ -410,7 +539,7 @@ fn parse_heroes_def(reader: &mut BinaryDataReader, ctx: &ParsingContext) -> io::
     if ctx.level_SOD {
         let disp_no = reader.read_u8()?;
         for _ in 0..disp_no {
-            let id = reader.read_u8()?;
+            let id = HeroId(reader.read_u8()? as u32);
             let portrait_id = Some(reader.read_u8()?);
             let name = reader.read_string_le()?;
             let hero = Hero {
@@ -433,10 +562,10 @@ fn parse_map_options(
     reader: &mut BinaryDataReader,
     ctx: &ParsingContext,
 ) -> io::Result<MapOptions> {
-    reader.skip_n(31);
+    reader.skip_n(31)?;
     let allow_special_months = if ctx.level_HOTA0 {
         let ret = reader.read_bool()?;
-        reader.skip_n(3);
+        reader.skip_n(3)?;
         ret
     } else {
         false
@@ -444,7 +573,7 @@ fn parse_map_options(
     if ctx.level_HOTA1 {
         // unknown part
         let _ = reader.read_u8()?;
-        reader.skip_n(5);
+        reader.skip_n(5)?;
     }
     let round_limit = if ctx.level_HOTA3 {
         Some(reader.read_u32_le()?)
@@ -486,8 +615,11 @@ fn parse_allowed_spells(
 ) -> io::Result<AllowedSpells> {
     let (spells, skills) = if ctx.level_SOD {
         (
-            map_bits_to_numbers(reader, ctx.spells_count as u8)?,
-            map_bits_to_numbers(reader, ctx.skills_count as u8)?,
+            read_spells(reader, ctx)?,
+            map_bits_to_numbers(reader, ctx.skills_count as u8)?
+                .into_iter()
+                .map(|s| SecSkillId(s as u32))
+                .collect(),
         )
     } else {
         (Vec::new(), Vec::new())
@@ -552,7 +684,7 @@ fn parse_predefined_heroes(
         };
         let custom_spells = if reader.read_bool()? {
             // read custom spells
-            map_bits_to_numbers(reader, ctx.spells_count as u8)?
+            read_spells(reader, ctx)?
         } else {
             Vec::new()
         };
@@ -567,7 +699,7 @@ fn parse_predefined_heroes(
             None
         };
         predefined_heroes.push(PredefinedHero {
-            id: id as u32,
+            id: HeroId(id as u32),
             experience,
             secondary_skills,
             artifacts,
@@ -598,19 +730,22 @@ fn parse_terrain(
     let mut terrains = Vec::new();
     for _ in 0..levels_no {
         let mut tiles = vec![vec![TerrainTile::default(); map_size]; map_size];
-        for x in 0..map_size {
-            for y in 0..map_size {
+        // the file stores the tiles row by row (y outer, x inner), the map
+        // indexes them column first as tiles[x][y] like MapCoord
+        for y in 0..map_size {
+            for column in tiles.iter_mut() {
+                let tile = &mut column[y];
                 let code = reader.read_u8()?;
-                tiles[x][y].surface_type = match Surface::from(code) {
+                tile.surface_type = match Surface::from(code) {
                     Some(t) => t,
                     None => return Err(gen_error(&format!("parse_terrain: cannot convert surface type - got {code} which is incorrect"))),
                 };
-                tiles[x][y].surface_picture = reader.read_u8()?;
-                tiles[x][y].river_type = RiverType::from(reader.read_u8()?);
-                tiles[x][y].river_direction = reader.read_u8()?;
-                tiles[x][y].road_type = RoadType::from(reader.read_u8()?);
-                tiles[x][y].road_direction = reader.read_u8()?;
-                tiles[x][y].mirroring_flags = reader.read_u8()?;
+                tile.surface_picture = reader.read_u8()?;
+                tile.river_type = RiverType::from(reader.read_u8()?);
+                tile.river_direction = reader.read_u8()?;
+                tile.road_type = RoadType::from(reader.read_u8()?);
+                tile.road_direction = reader.read_u8()?;
+                tile.mirroring_flags = reader.read_u8()?;
             }
         }
         terrains.push(tiles);
@@ -638,8 +773,9 @@ fn parse_object_templates(
         let mut transit_matrix = [[TileTransitProperty::default(); 8]; 6];
         for (i, row) in transit_matrix.iter_mut().enumerate() {
             for (j, trans) in row.iter_mut().enumerate() {
-                // in h3m counted from the bottom right corner
-                // remap to be counted from the top left corner
+                // in h3m rows go from the top and bits from the left,
+                // the object position is the last bit of the last row;
+                // remap to be counted from the object position (bottom right corner)
                 let i = 5 - i;
                 let j = 7 - j;
                 if (block_bits[i] >> j) & 0x01 == 0 {
@@ -666,7 +802,7 @@ fn parse_object_templates(
         let subid = reader.read_u32_le()?;
         let obj_kind = ObjectKind::from(reader.read_u8()?);
         let render_priority = reader.read_u8()?;
-        reader.skip_n(16);
+        reader.skip_n(16)?;
         ret.push(ObjectTemplate {
             animation_file,
             transit_matrix,
@@ -691,10 +827,17 @@ fn parse_objects(
         // reader.dump_hex(0, 16 * 4)?;
         let position = read_coord(reader)?;
         let obj_templ_id = reader.read_u32_le()?;
-        reader.skip_n(5);
-        let template = &templates[obj_templ_id as usize];
+        reader.skip_n(5)?;
+        let template = match templates.get(obj_templ_id as usize) {
+            Some(t) => t,
+            None => {
+                return Err(gen_error(&format!(
+                    "parse_objects: template {obj_templ_id} does not exist, there are {}",
+                    templates.len()
+                )))
+            }
+        };
         let obj_id = template.id;
-        println!("parsing {obj_id} at {position:?}...");
         let mut obj_type = match ObjectType::from(obj_id) {
             Some(o) => o,
             None => {
@@ -716,7 +859,7 @@ fn parse_objects(
             | RandomMonsterL7(ref mut m) => {
                 // read moster data
                 if ctx.level_AB {
-                    m.id = reader.read_u32_le()?;
+                    m.quest_id = reader.read_u32_le()?;
                 }
                 m.amount = reader.read_u16_le()? as u32;
                 // println!("amount = {}", m.amount);
@@ -730,7 +873,7 @@ fn parse_objects(
                 }
                 m.never_flees = reader.read_bool()?;
                 m.growing_team = !reader.read_bool()?;
-                reader.skip_n(2);
+                reader.skip_n(2)?;
                 if ctx.level_HOTA3 {
                     m.aggression_factor = match reader.read_u32_le()? {
                         0xffffffff => None,
@@ -754,7 +897,7 @@ fn parse_objects(
                 ev.available_for = map_bits_to_objects(reader, &ALL_PLAYERS, 1)?;
                 ev.computer_can_activate = reader.read_bool()?;
                 ev.remove_after_visit = reader.read_bool()?;
-                reader.skip_n(4);
+                reader.skip_n(4)?;
                 ev.human_can_activate = if ctx.level_HOTA3 {
                     reader.read_bool()?
                 } else {
@@ -782,7 +925,7 @@ fn parse_objects(
                     h.quest_id = reader.read_u32_le()?;
                 }
                 h.owner = Ownership::from(reader.read_u8()? as u32);
-                h.hero_id = reader.read_u8()? as u32;
+                h.hero_id = read_hero_id(reader, ctx)?;
                 if reader.read_bool()? {
                     h.name = Some(reader.read_string_le()?);
                 }
@@ -823,11 +966,10 @@ fn parse_objects(
                 }
                 if ctx.level_SOD {
                     if reader.read_bool()? {
-                        h.custom_spells = map_bits_to_numbers(reader, ctx.spells_count as u8)?;
+                        h.custom_spells = read_spells(reader, ctx)?;
                     }
                 } else if ctx.level_AB {
-                    let spell_id = reader.read_u8()?;
-                    if spell_id != ctx.spell_identifier_invalid {
+                    if let Some(spell_id) = read_spell_id(reader, ctx)? {
                         h.custom_spells.push(spell_id);
                     }
                 }
@@ -841,7 +983,7 @@ fn parse_objects(
                         });
                     }
                 }
-                reader.skip_n(16);
+                reader.skip_n(16)?;
             }
             Artifact(ref mut guards)
             | RandomArt(ref mut guards)
@@ -853,16 +995,16 @@ fn parse_objects(
             }
             SpellScroll(ref mut s) => {
                 s.guards = read_message_and_guards(reader, ctx)?;
-                s.spell_scroll_id = reader.read_u32_le()?;
+                s.spell_scroll_id = SpellId(reader.read_u32_le()?);
             }
             Resource(ref mut r) | RandomResource(ref mut r) => {
                 r.guards = read_message_and_guards(reader, ctx)?;
                 r.amount = reader.read_u32_le()?;
-                reader.skip_n(4);
+                reader.skip_n(4)?;
             }
             Sign(ref mut msg) | OceanBottle(ref mut msg) => {
                 *msg = reader.read_string_le()?;
-                reader.skip_n(4);
+                reader.skip_n(4)?;
             }
             SeerHut(ref mut vec) => {
                 let mut quest_cnt = 1;
@@ -880,20 +1022,23 @@ fn parse_objects(
                         vec.push(q);
                     }
                 }
-                reader.skip_n(2);
+                reader.skip_n(2)?;
             }
             WitchHut {
                 ref mut secondary_skills,
             } => {
                 if ctx.level_AB {
-                    *secondary_skills = map_bits_to_numbers(reader, ctx.skills_count as u8)?;
+                    *secondary_skills = map_bits_to_numbers(reader, ctx.skills_count as u8)?
+                        .into_iter()
+                        .map(|s| SecSkillId(s as u32))
+                        .collect();
                 }
             }
             Scholar(ref mut s) => {
                 let bonus_type = reader.read_u8()?;
                 let bonus_id = reader.read_u8()?;
                 *s = ScholarBonus::from(bonus_type, bonus_id);
-                reader.skip_n(6);
+                reader.skip_n(6)?;
             }
             Garrison(ref mut d) | Garrison2(ref mut d) => {
                 d.owner = Ownership::from(reader.read_u32_le()?);
@@ -903,7 +1048,7 @@ fn parse_objects(
                 } else {
                     d.removable_units = true;
                 }
-                reader.skip_n(8);
+                reader.skip_n(8)?;
             }
             Town(ref mut d) | RandomTown(ref mut d) => {
                 if ctx.level_AB {
@@ -929,9 +1074,9 @@ fn parse_objects(
                     d.built_buildings.push(Buildings::Default);
                 }
                 if ctx.level_AB {
-                    d.obligatory_spells = map_bits_to_numbers(reader, ctx.spells_count as u8)?;
+                    d.obligatory_spells = read_spells(reader, ctx)?;
                 }
-                d.possible_spells = map_bits_to_numbers(reader, ctx.spells_count as u8)?;
+                d.possible_spells = read_spells(reader, ctx)?;
                 if ctx.level_HOTA1 {
                     let _spells_research_available = reader.read_bool()?;
                 }
@@ -949,13 +1094,13 @@ fn parse_objects(
                     let computer_affected = reader.read_bool()?;
                     let first_occurrence_at = reader.read_u16_le()?;
                     let next_occurrence = reader.read_u8()?;
-                    reader.skip_n(17);
+                    reader.skip_n(17)?;
                     let new_buildings = read_bitmask_buildings(reader, ctx)?;
                     let mut new_creatures_at = Vec::with_capacity(7);
                     for i in 0..7 {
                         new_creatures_at.push((i as u8, reader.read_u16_le()?));
                     }
-                    reader.skip_n(4);
+                    reader.skip_n(4)?;
                     d.events.push(TownEvent {
                         name,
                         message,
@@ -977,11 +1122,14 @@ fn parse_objects(
                         }
                     }
                 }
-                reader.skip_n(3);
+                reader.skip_n(3)?;
             }
             ShrineOfMagicIncantation { ref mut spell_id }
             | ShrineOfMagicGesture { ref mut spell_id }
-            | ShrineOfMagicThought { ref mut spell_id } => *spell_id = reader.read_u32_le()?,
+            | ShrineOfMagicThought { ref mut spell_id } => {
+                *spell_id = read_spell_id(reader, ctx)?;
+                reader.skip_n(3)?;
+            }
             PandorasBox(ref mut pand_box) => *pand_box = read_box_content(reader, ctx)?,
             Grail { ref mut radius } => {
                 if template.subid < 1000 {
@@ -1015,7 +1163,7 @@ fn parse_objects(
                 ref mut hero_id,
             } => {
                 *owner = Ownership::from(reader.read_u8()? as u32);
-                *hero_id = reader.read_u8()? as u32;
+                *hero_id = read_hero_id(reader, ctx)?;
             }
             CreatureBank(ref mut b)
             | DerelictShip(ref mut b)
@@ -1034,6 +1182,10 @@ fn parse_objects(
             }
             _ => {}
         }
+        // the creature of a monster is the template subid
+        if let Monster(ref mut m) = obj_type {
+            m.creature = Some(CreatureId(template.subid as u16));
+        }
         ret.push(Object {
             position,
             obj_templ_id,
@@ -1059,7 +1211,7 @@ fn parse_events(reader: &mut BinaryDataReader, ctx: &ParsingContext) -> io::Resu
         let computer_affected = reader.read_bool()?;
         let first_occurrence_at = reader.read_u16_le()?;
         let next_occurrence = reader.read_u8()?;
-        reader.skip_n(17);
+        reader.skip_n(17)?;
         ret.push(Event {
             name,
             message,
@@ -1112,7 +1264,7 @@ fn read_box_content(reader: &mut BinaryDataReader, ctx: &ParsingContext) -> io::
             let _ = reader.read_u16_le()?;
         }
     }
-    reader.skip_n(8);
+    reader.skip_n(8)?;
     Ok(BoxContent {
         guards,
         reward_experience,
@@ -1139,7 +1291,7 @@ fn read_message_and_guards(
         } else {
             Vec::new()
         };
-        reader.skip_n(4);
+        reader.skip_n(4)?;
         Ok(Some(CreatureGuard { message, slot }))
     } else {
         Ok(None)
@@ -1224,6 +1376,21 @@ fn read_spell_id(
     Ok(Some(SpellId(id as u32)))
 }
 
+fn read_spells(reader: &mut BinaryDataReader, ctx: &ParsingContext) -> io::Result<Vec<SpellId>> {
+    Ok(map_bits_to_numbers(reader, ctx.spells_count as u8)?
+        .into_iter()
+        .map(|s| SpellId(s as u32))
+        .collect())
+}
+
+fn read_hero_id(reader: &mut BinaryDataReader, ctx: &ParsingContext) -> io::Result<Option<HeroId>> {
+    let id = reader.read_u8()?;
+    if id == ctx.hero_identifier_invalid {
+        return Ok(None);
+    }
+    Ok(Some(HeroId(id as u32)))
+}
+
 fn read_resource_pack(
     reader: &mut BinaryDataReader,
     _ctc: &ParsingContext,
@@ -1239,7 +1406,7 @@ fn read_secondary_skill(
     reader: &mut BinaryDataReader,
     _ctx: &ParsingContext,
 ) -> io::Result<SecSkill> {
-    let id = reader.read_u8()? as u32;
+    let id = SecSkillId(reader.read_u8()? as u32);
     let level_id = reader.read_u8()?;
     let level = match SecSkillLevel::from(level_id) {
         Some(l) => l,
@@ -1265,7 +1432,7 @@ fn read_heroes_artifacts(
             };
             ret.push(HeroesArtifact {
                 artifact_id,
-                slot_id: slot as u32,
+                slot_id: ArtifactSlot(slot as u32),
             })
         }
         ret
@@ -1319,7 +1486,7 @@ fn read_seer_hut_quest(
                 )
             }
             SecondarySkills(ref mut skills) => {
-                let id = reader.read_u8()? as u32;
+                let id = SecSkillId(reader.read_u8()? as u32);
                 if let Some(level) = SecSkillLevel::from(reader.read_u8()?) {
                     skills.push(SecSkill { id, level });
                 }
@@ -1329,7 +1496,7 @@ fn read_seer_hut_quest(
                     vec.push(a);
                 }
             }
-            Spell(ref mut vec) => vec.push(reader.read_u8()?),
+            Spell(ref mut vec) => vec.push(SpellId(reader.read_u8()? as u32)),
             Creature(ref mut vec) => {
                 if let Some(id) = read_creature(reader, ctx)? {
                     let amount = reader.read_u16_le()? as u32;
@@ -1340,9 +1507,14 @@ fn read_seer_hut_quest(
             }
         }
     } else {
-        reader.skip_n(1);
+        reader.skip_n(1)?;
     }
+    // empty messages are replaced by the game with the default texts
     Ok(SeerHutData {
+        time_limit: u32::try_from(mission.last_day).ok(),
+        is_custom_first: !mission.proposal_message.is_empty(),
+        is_custom_last: !mission.progress_message.is_empty(),
+        is_custom_complete: !mission.completion_message.is_empty(),
         mission,
         reward,
         ..Default::default()
@@ -1390,7 +1562,7 @@ fn read_quest(reader: &mut BinaryDataReader, ctx: &ParsingContext) -> io::Result
             *r = read_resource_pack(reader, ctx)?;
             // println!("resources: {r:?}");
         }
-        Hero(ref mut id) => *id = reader.read_u8()?,
+        Hero(ref mut id) => *id = HeroId(reader.read_u8()? as u32),
         Player(ref mut pl) => {
             let id = reader.read_u8()? as usize;
             if id < ALL_PLAYERS.len() {
@@ -1401,7 +1573,9 @@ fn read_quest(reader: &mut BinaryDataReader, ctx: &ParsingContext) -> io::Result
             let sub_mission = reader.read_u32_le()?;
             if sub_mission == 0 {
                 let heroes_count = reader.read_u32_le()?;
-                assert!(heroes_count < 256);
+                if heroes_count >= 256 {
+                    return Err(gen_error(&format!("Too many hero classes: {heroes_count}")));
+                }
                 let heroes = map_bits_to_numbers(reader, heroes_count as u8)?;
                 mission_type = HOTAHeroClass(heroes);
             } else if sub_mission == 1 {
@@ -1473,9 +1647,9 @@ fn map_bits_to_objects<T: Copy + Clone>(
         bytes_read += 1;
     }
     if bytes_read > bytes_to_read {
-        panic!(
+        return Err(gen_error(&format!(
             "More bytes have been read than specified: read={bytes_read}, expected={bytes_to_read}"
-        );
+        )));
     }
     Ok(ret)
 }
@@ -1502,50 +1676,50 @@ fn gen_error(msg: &str) -> io::Error {
 
 #[allow(non_snake_case)]
 #[derive(Default)]
-struct ParsingContext {
-    factions: Vec<Town>,
-    factions_bytes: usize,
-    heroes_bytes: usize,
-    artifacts_bytes: usize,
-    resources_bytes: usize,
-    skills_bytes: usize,
-    spells_bytes: usize,
-    buildings_bytes: usize,
+pub(crate) struct ParsingContext {
+    pub(crate) factions: Vec<Town>,
+    pub(crate) factions_bytes: usize,
+    pub(crate) heroes_bytes: usize,
+    pub(crate) artifacts_bytes: usize,
+    pub(crate) resources_bytes: usize,
+    pub(crate) skills_bytes: usize,
+    pub(crate) spells_bytes: usize,
+    pub(crate) buildings_bytes: usize,
 
     // total number of elements of appropriate type
-    factions_count: usize,
-    heroes_count: usize,
-    heroes_portraits_count: usize,
-    artifacts_count: usize,
-    resources_count: usize,
-    creatures_count: usize,
-    spells_count: usize,
-    skills_count: usize,
-    terrains_count: usize,
-    roads_count: usize,
-    rivers_count: usize,
-    artifact_slots_count: usize,
-    buildings_count: usize,
+    pub(crate) factions_count: usize,
+    pub(crate) heroes_count: usize,
+    pub(crate) heroes_portraits_count: usize,
+    pub(crate) artifacts_count: usize,
+    pub(crate) resources_count: usize,
+    pub(crate) creatures_count: usize,
+    pub(crate) spells_count: usize,
+    pub(crate) skills_count: usize,
+    pub(crate) terrains_count: usize,
+    pub(crate) roads_count: usize,
+    pub(crate) rivers_count: usize,
+    pub(crate) artifact_slots_count: usize,
+    pub(crate) buildings_count: usize,
 
     // identifier that should be treated as "invalid", usually - '-1'
-    hero_identifier_invalid: u8,
-    artifact_identifier_invalid: u32,
-    creature_identifier_invalid: u16,
-    spell_identifier_invalid: u8,
+    pub(crate) hero_identifier_invalid: u8,
+    pub(crate) artifact_identifier_invalid: u32,
+    pub(crate) creature_identifier_invalid: u16,
+    pub(crate) spell_identifier_invalid: u8,
 
     // features from which map format are available
-    level: Format,
-    level_ROE: bool,
-    level_AB: bool,
-    level_SOD: bool,
-    level_WOG: bool,
-    level_HOTA0: bool,
-    level_HOTA1: bool,
-    level_HOTA3: bool,
+    pub(crate) level: Format,
+    pub(crate) level_ROE: bool,
+    pub(crate) level_AB: bool,
+    pub(crate) level_SOD: bool,
+    pub(crate) level_WOG: bool,
+    pub(crate) level_HOTA0: bool,
+    pub(crate) level_HOTA1: bool,
+    pub(crate) level_HOTA3: bool,
 }
 
 impl ParsingContext {
-    fn from(map_format: Format, hota_version: usize) -> Self {
+    pub(crate) fn from(map_format: Format, hota_version: usize) -> Self {
         let mut ctx = Self::default();
         ctx.level = map_format;
         // Format::ROE
@@ -0,0 +1,287 @@
+use crate::map_structs::*;
+use crate::map_tiling::variant;
+use std::ops::Range;
+
+// Road and river auto-connection.
+//
+// Road and river DEFs contain each piece once, other orientations are drawn
+// with the mirroring flags. Pieces are stored in their canonical orientation:
+// a turn goes right and down, a T junction is either a vertical line with a
+// branch to the right or a horizontal line with a branch down and an end
+// continues downwards or to the right.
+//
+// Rivers flow into the sea: a water neighbour counts as a connection and the
+// place is reported as a river mouth, where a RiverDelta object belongs.
+
+pub const MIRROR_RIVER_HORIZONTAL: u8 = 0x04;
+pub const MIRROR_RIVER_VERTICAL: u8 = 0x08;
+pub const MIRROR_RIVER_MASK: u8 = 0x0c;
+pub const MIRROR_ROAD_HORIZONTAL: u8 = 0x10;
+pub const MIRROR_ROAD_VERTICAL: u8 = 0x20;
+pub const MIRROR_ROAD_MASK: u8 = 0x30;
+
+// connection bits
+const T: u8 = 1 << 0;
+const L: u8 = 1 << 1;
+const R: u8 = 1 << 2;
+const B: u8 = 1 << 3;
+
+const CONNECTIONS: [(u8, (isize, isize)); 4] =
+    [(T, (0, -1)), (L, (-1, 0)), (R, (1, 0)), (B, (0, 1))];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Piece {
+    Turn,
+    JunctionVertical,
+    JunctionHorizontal,
+    StraightVertical,
+    StraightHorizontal,
+    EndVertical,
+    EndHorizontal,
+    Cross,
+}
+
+impl Piece {
+    // `mask` is already flipped to the canonical orientation
+    fn classify(mask: u8) -> Option<Self> {
+        use Piece::*;
+        match mask {
+            m if m == R | B => Some(Turn),
+            m if m == T | B | R => Some(JunctionVertical),
+            m if m == L | R | B => Some(JunctionHorizontal),
+            m if m == T | B => Some(StraightVertical),
+            m if m == L | R => Some(StraightHorizontal),
+            // a lonely piece is drawn as the end of a vertical line
+            m if m == B || m == 0 => Some(EndVertical),
+            m if m == R => Some(EndHorizontal),
+            m if m == T | L | R | B => Some(Cross),
+            _ => None,
+        }
+    }
+}
+
+// first frame and number of variants of every piece
+struct PieceFrames {
+    turn: (u8, u8),
+    junction_vertical: (u8, u8),
+    junction_horizontal: (u8, u8),
+    straight_vertical: (u8, u8),
+    straight_horizontal: (u8, u8),
+    end_vertical: (u8, u8),
+    end_horizontal: (u8, u8),
+    cross: (u8, u8),
+}
+
+impl PieceFrames {
+    fn get(&self, piece: Piece) -> (u8, u8) {
+        use Piece::*;
+        match piece {
+            Turn => self.turn,
+            JunctionVertical => self.junction_vertical,
+            JunctionHorizontal => self.junction_horizontal,
+            StraightVertical => self.straight_vertical,
+            StraightHorizontal => self.straight_horizontal,
+            EndVertical => self.end_vertical,
+            EndHorizontal => self.end_horizontal,
+            Cross => self.cross,
+        }
+    }
+}
+
+// dirtrd.def, gravrd.def, cobbrd.def
+const ROAD_FRAMES: PieceFrames = PieceFrames {
+    turn: (0, 2),
+    junction_vertical: (2, 4),
+    junction_horizontal: (6, 2),
+    straight_vertical: (8, 4),
+    straight_horizontal: (12, 2),
+    end_vertical: (14, 1),
+    end_horizontal: (15, 1),
+    cross: (16, 1),
+};
+
+// clrrvr.def, icyrvr.def, mudrvr.def, lavrvr.def
+const RIVER_FRAMES: PieceFrames = PieceFrames {
+    turn: (0, 4),
+    junction_vertical: (4, 4),
+    junction_horizontal: (8, 4),
+    cross: (12, 1),
+    straight_vertical: (13, 4),
+    straight_horizontal: (17, 4),
+    end_vertical: (21, 1),
+    end_horizontal: (22, 1),
+};
+
+/// Place where a river flows into the sea
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RiverMouth {
+    pub river: (usize, usize),
+    pub water: (usize, usize),
+}
+
+fn flip(mask: u8, horizontal: bool, vertical: bool) -> u8 {
+    let mut ret = mask;
+    if horizontal && (ret & L != 0) != (ret & R != 0) {
+        ret ^= L | R;
+    }
+    if vertical && (ret & T != 0) != (ret & B != 0) {
+        ret ^= T | B;
+    }
+    ret
+}
+
+fn match_piece(mask: u8) -> (Piece, bool, bool) {
+    for (h, v) in [(false, false), (true, false), (false, true), (true, true)] {
+        if let Some(p) = Piece::classify(flip(mask, h, v)) {
+            return (p, h, v);
+        }
+    }
+    // every 4-bit mask is one of the pieces in some orientation
+    unreachable!("no road piece for connection mask {mask:#x}")
+}
+
+fn connection_mask(
+    terrain: &Terrain,
+    x: usize,
+    y: usize,
+    connects: impl Fn(&TerrainTile) -> bool,
+) -> u8 {
+    let mut mask = 0;
+    for (bit, (dx, dy)) in CONNECTIONS {
+        let (Some(nx), Some(ny)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+            continue;
+        };
+        if let Some(n) = terrain.get(nx).and_then(|c| c.get(ny)) {
+            if connects(n) {
+                mask |= bit;
+            }
+        }
+    }
+    mask
+}
+
+fn clip(terrain: &Terrain, xs: Range<usize>, ys: Range<usize>) -> (Range<usize>, Range<usize>) {
+    let width = terrain.len();
+    let height = terrain.first().map_or(0, |c| c.len());
+    (
+        xs.start.min(width)..xs.end.min(width),
+        ys.start.min(height)..ys.end.min(height),
+    )
+}
+
+fn frame_and_mirroring(frames: &PieceFrames, mask: u8, x: usize, y: usize) -> (u8, bool, bool) {
+    let (piece, h, v) = match_piece(mask);
+    let (first, count) = frames.get(piece);
+    (first + variant(x, y, count), h, v)
+}
+
+/// Choose road pieces for every road tile in the region
+pub fn connect_roads(terrain: &mut Terrain, xs: Range<usize>, ys: Range<usize>) {
+    let (xs, ys) = clip(terrain, xs, ys);
+    for x in xs {
+        for y in ys.clone() {
+            if terrain[x][y].road_type.is_none() {
+                continue;
+            }
+            let mask = connection_mask(terrain, x, y, |n| n.road_type.is_some());
+            let (frame, h, v) = frame_and_mirroring(&ROAD_FRAMES, mask, x, y);
+            let tile = &mut terrain[x][y];
+            tile.road_direction = frame;
+            tile.mirroring_flags &= !MIRROR_ROAD_MASK;
+            if h {
+                tile.mirroring_flags |= MIRROR_ROAD_HORIZONTAL;
+            }
+            if v {
+                tile.mirroring_flags |= MIRROR_ROAD_VERTICAL;
+            }
+        }
+    }
+}
+
+/// Choose river pieces for every river tile in the region,
+/// returns the places where rivers flow into the sea
+pub fn connect_rivers(
+    terrain: &mut Terrain,
+    xs: Range<usize>,
+    ys: Range<usize>,
+) -> Vec<RiverMouth> {
+    let (xs, ys) = clip(terrain, xs, ys);
+    let mut mouths = Vec::new();
+    for x in xs {
+        for y in ys.clone() {
+            if terrain[x][y].river_type.is_none() {
+                continue;
+            }
+            let mask = connection_mask(terrain, x, y, |n| {
+                n.river_type.is_some() || n.surface_type == Surface::Water
+            });
+            let sea = connection_mask(terrain, x, y, |n| n.surface_type == Surface::Water);
+            for (bit, (dx, dy)) in CONNECTIONS {
+                if sea & bit != 0 {
+                    mouths.push(RiverMouth {
+                        river: (x, y),
+                        water: (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy)),
+                    });
+                }
+            }
+            let (frame, h, v) = frame_and_mirroring(&RIVER_FRAMES, mask, x, y);
+            let tile = &mut terrain[x][y];
+            tile.river_direction = frame;
+            tile.mirroring_flags &= !MIRROR_RIVER_MASK;
+            if h {
+                tile.mirroring_flags |= MIRROR_RIVER_HORIZONTAL;
+            }
+            if v {
+                tile.mirroring_flags |= MIRROR_RIVER_VERTICAL;
+            }
+        }
+    }
+    mouths
+}
+
+// region covering the tiles and their direct neighbours
+fn surrounding(tiles: &[(usize, usize)]) -> (Range<usize>, Range<usize>) {
+    let x0 = tiles.iter().map(|t| t.0).min().unwrap_or(0);
+    let x1 = tiles.iter().map(|t| t.0).max().unwrap_or(0);
+    let y0 = tiles.iter().map(|t| t.1).min().unwrap_or(0);
+    let y1 = tiles.iter().map(|t| t.1).max().unwrap_or(0);
+    (x0.saturating_sub(1)..x1 + 2, y0.saturating_sub(1)..y1 + 2)
+}
+
+/// Put a road on the tiles and reconnect it with the roads around.
+/// Water and rock tiles are skipped.
+pub fn lay_road(terrain: &mut Terrain, tiles: &[(usize, usize)], road: RoadType) {
+    for &(x, y) in tiles {
+        if let Some(tile) = terrain.get_mut(x).and_then(|c| c.get_mut(y)) {
+            if tile.surface_type != Surface::Water && tile.surface_type != Surface::Rock {
+                tile.road_type = Some(road);
+            }
+        }
+    }
+    if !tiles.is_empty() {
+        let (xs, ys) = surrounding(tiles);
+        connect_roads(terrain, xs, ys);
+    }
+}
+
+/// Put a river on the tiles and reconnect it with the rivers around,
+/// returns the places where the river flows into the sea.
+/// Water and rock tiles are skipped.
+pub fn lay_river(
+    terrain: &mut Terrain,
+    tiles: &[(usize, usize)],
+    river: RiverType,
+) -> Vec<RiverMouth> {
+    for &(x, y) in tiles {
+        if let Some(tile) = terrain.get_mut(x).and_then(|c| c.get_mut(y)) {
+            if tile.surface_type != Surface::Water && tile.surface_type != Surface::Rock {
+                tile.river_type = Some(river);
+            }
+        }
+    }
+    if tiles.is_empty() {
+        return Vec::new();
+    }
+    let (xs, ys) = surrounding(tiles);
+    connect_rivers(terrain, xs, ys)
+}
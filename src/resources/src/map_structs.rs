@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+pub use crate::map_ids::*;
+
 use Player::*;
 pub const ALL_PLAYERS: [Player; 8] = [Red, Blue, Tan, Green, Orange, Purple, Teal, Pink];
 
@@ -113,6 +115,21 @@ pub enum Town {
     Factory,
 }
 
+/// Towns in the order of their object subids
+pub const ALL_TOWNS: [Town; 11] = [
+    Town::Castle,
+    Town::Rampart,
+    Town::Tower,
+    Town::Inferno,
+    Town::Necropolis,
+    Town::Dungeon,
+    Town::Stronghold,
+    Town::Fortress,
+    Town::Conflux,
+    Town::Cove,
+    Town::Factory,
+];
+
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub enum Faction {
     #[default]
@@ -122,9 +139,9 @@ pub enum Faction {
     None,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Hero {
-    pub id: u8,
+    pub id: HeroId,
     pub portrait_id: Option<u8>,
     pub name: String,
 }
@@ -146,7 +163,7 @@ impl MapCoord {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct PlayerInfo {
     pub player: Player,
     pub can_be_human: bool,
@@ -160,7 +177,48 @@ pub struct PlayerInfo {
     pub other_heroes: Vec<Hero>,
 }
 
-#[derive(Debug)]
+/// Standard map sizes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MapSize {
+    S,
+    M,
+    L,
+    XL,
+    H,  // HOTA
+    XH, // HOTA
+    G,  // HOTA
+}
+
+impl MapSize {
+    pub fn from(dimension: usize) -> Option<Self> {
+        use MapSize::*;
+        match dimension {
+            36 => Some(S),
+            72 => Some(M),
+            108 => Some(L),
+            144 => Some(XL),
+            180 => Some(H),
+            216 => Some(XH),
+            252 => Some(G),
+            _ => None,
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        use MapSize::*;
+        match *self {
+            S => 36,
+            M => 72,
+            L => 108,
+            XL => 144,
+            H => 180,
+            XH => 216,
+            G => 252,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Info {
     pub format: Format,
     pub any_players: bool,
@@ -205,7 +263,7 @@ impl Resource {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Building {
     Town,
     City,
@@ -215,19 +273,13 @@ pub enum Building {
     Castle,
 }
 
-#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
-pub struct ArtifactId(pub u32);
-
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub struct SpellId(pub u32);
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SpecialVictoryCondition {
     AcquireArtifact {
         artifact_code: ArtifactId,
     },
     AccumulateCreatures {
-        unit_code: u16,
+        unit_code: CreatureId,
         amount: u32,
     },
     AccumulateResources {
@@ -265,14 +317,62 @@ pub enum SpecialVictoryCondition {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SpecialLossCondition {
     LossTown { town_coord: MapCoord },
     LossHero { hero_coord: MapCoord },
     TimeExpires { limit_days: u32 },
 }
 
-#[derive(Debug)]
+impl SpecialVictoryCondition {
+    /// Map position the condition refers to, if any
+    pub fn coord(&self) -> Option<MapCoord> {
+        use SpecialVictoryCondition::*;
+        match *self {
+            UpgradeTown { town_coord, .. } => Some(town_coord),
+            BuildGrail { town_coord } => Some(town_coord),
+            DefeatHero { hero_coord } => Some(hero_coord),
+            CaptureTown { town_coord } => Some(town_coord),
+            DefeatMonster { monster_coord } => Some(monster_coord),
+            TransportArtifact { artifact_coord, .. } => Some(artifact_coord),
+            _ => None,
+        }
+    }
+
+    pub fn coord_mut(&mut self) -> Option<&mut MapCoord> {
+        use SpecialVictoryCondition::*;
+        match self {
+            UpgradeTown { town_coord, .. } => Some(town_coord),
+            BuildGrail { town_coord } => Some(town_coord),
+            DefeatHero { hero_coord } => Some(hero_coord),
+            CaptureTown { town_coord } => Some(town_coord),
+            DefeatMonster { monster_coord } => Some(monster_coord),
+            TransportArtifact { artifact_coord, .. } => Some(artifact_coord),
+            _ => None,
+        }
+    }
+}
+
+impl SpecialLossCondition {
+    /// Map position the condition refers to, if any
+    pub fn coord(&self) -> Option<MapCoord> {
+        match *self {
+            Self::LossTown { town_coord } => Some(town_coord),
+            Self::LossHero { hero_coord } => Some(hero_coord),
+            Self::TimeExpires { .. } => None,
+        }
+    }
+
+    pub fn coord_mut(&mut self) -> Option<&mut MapCoord> {
+        match self {
+            Self::LossTown { town_coord } => Some(town_coord),
+            Self::LossHero { hero_coord } => Some(hero_coord),
+            Self::TimeExpires { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct WinLossCond {
     pub allow_normal_victory: bool,
     pub victory_cond_applies_to_comp: bool,
@@ -280,7 +380,7 @@ pub struct WinLossCond {
     pub special_loss_cond: Option<SpecialLossCondition>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TeamInfo {
     pub teams: HashMap<u8, Vec<Player>>,
 }
@@ -300,37 +400,41 @@ impl TeamInfo {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HeroesDef {
-    pub allowed_heroes: Vec<u8>,
-    pub reserved_for_campaign: Vec<u8>,
+    pub allowed_heroes: Vec<HeroId>,
+    pub reserved_for_campaign: Vec<HeroId>,
     pub disposed_heroes: Vec<(Hero, Vec<Player>)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MapOptions {
     pub allow_special_months: bool,
     pub round_limit: Option<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AllowedArtifacts {
     pub artifacts: Vec<ArtifactId>,
 }
 
-#[derive(Debug)]
+/// Despite the name, the spells and skills the map bans: the set bits of the
+/// file's masks. Empty before SoD, where every spell and skill is allowed.
+#[derive(Debug, Clone)]
 pub struct AllowedSpells {
-    pub spells: Vec<u8>,
-    pub skills: Vec<u8>,
+    /// banned spells
+    pub spells: Vec<SpellId>,
+    /// banned secondary skills
+    pub skills: Vec<SecSkillId>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Rumor {
     pub name: String,
     pub rumor: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Rumors {
     // vector with rumor names
     pub rumors: Vec<Rumor>,
@@ -357,14 +461,14 @@ impl SecSkillLevel {
 
 #[derive(Debug, Copy, Clone)]
 pub struct SecSkill {
-    pub id: u32,
+    pub id: SecSkillId,
     pub level: SecSkillLevel,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct HeroesArtifact {
     pub artifact_id: ArtifactId,
-    pub slot_id: u32,
+    pub slot_id: ArtifactSlot,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -395,20 +499,20 @@ impl PrimarySkills {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PredefinedHero {
-    pub id: u32,
+    pub id: HeroId,
     pub experience: u32,
     pub secondary_skills: Vec<SecSkill>,
     pub artifacts: Vec<HeroesArtifact>,
     pub artifacts_in_bag: Vec<ArtifactId>,
     pub custom_bio: Option<String>,
     pub gender: Option<Gender>,
-    pub custom_spells: Vec<u8>,
+    pub custom_spells: Vec<SpellId>,
     pub custom_primary_skills: Option<PrimarySkills>,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Surface {
     Dirt,
     Sand,
@@ -421,6 +525,8 @@ pub enum Surface {
     Lava,
     Water,
     Rock,
+    Highlands, // HOTA
+    Wasteland, // HOTA
 }
 
 impl Surface {
@@ -437,12 +543,14 @@ impl Surface {
             7 => Some(Lava),
             8 => Some(Water),
             9 => Some(Rock),
+            10 => Some(Highlands),
+            11 => Some(Wasteland),
             _ => None,
         }
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum RiverType {
     #[default]
     Clear,
@@ -464,7 +572,7 @@ impl RiverType {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum RoadType {
     #[default]
     Dirt,
@@ -492,12 +600,14 @@ pub struct TerrainTile {
     pub river_direction: u8,
     pub road_type: Option<RoadType>,
     pub road_direction: u8,
+    /// bits 0-1: terrain, 2-3: river, 4-5: road (horizontal, vertical), bit 6: coast
     pub mirroring_flags: u8,
 }
 
+/// Indexed as terrain[x][y], y grows downwards
 pub type Terrain = Vec<Vec<TerrainTile>>;
 
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub enum TileTransitProperty {
     #[default]
     Transitable,
@@ -505,7 +615,7 @@ pub enum TileTransitProperty {
     Visitable,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ObjectKind {
     Unknown(u8),
     Town,
@@ -529,9 +639,10 @@ impl ObjectKind {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ObjectTemplate {
     pub animation_file: String,
+    /// Indexed as [dy][dx], tile at (x - dx, y - dy) of the object position
     pub transit_matrix: [[TileTransitProperty; 8]; 6],
     pub allowed_terrains: Vec<Surface>,
     pub id: u32,
@@ -542,7 +653,7 @@ pub struct ObjectTemplate {
 
 use crate::map_obj_type::ObjectType;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Object {
     pub position: MapCoord,
     pub obj_templ_id: u32,
@@ -552,9 +663,6 @@ pub struct Object {
 #[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
 pub struct ResourcePack(pub [i32; 7]);
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub struct CreatureId(pub u16);
-
 #[derive(Debug, Copy, Clone)]
 pub struct CreatureSlot {
     pub slot_num: u8,
@@ -599,7 +707,7 @@ pub struct BoxContent {
     pub reward_creatures: Vec<(CreatureId, u32)>,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Ownership {
     Spectator,
     CannotDetermine,
@@ -633,7 +741,7 @@ pub struct Event {
     pub next_occurrence: u8,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Map {
     pub info: Info,
     pub players: Vec<PlayerInfo>,
@@ -650,3 +758,18 @@ pub struct Map {
     pub objects: Vec<Object>,
     pub events: Vec<Event>,
 }
+
+impl Map {
+    /// Number of tiles along one side of a level
+    pub fn dimension(&self) -> usize {
+        self.info.map_dimension as usize
+    }
+
+    pub fn levels(&self) -> usize {
+        self.terrains.len()
+    }
+
+    pub fn contains(&self, coord: &MapCoord) -> bool {
+        coord.x < self.dimension() && coord.y < self.dimension() && coord.z < self.levels()
+    }
+}
@@ -0,0 +1,253 @@
+use crate::map_obj_type::ObjectType;
+use crate::map_structs::*;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Teleport network of a map, following the game rules:
+// - one way monolith entrances lead to a random exit with the same subid,
+// - two way monoliths and whirlpools lead to a random other one with the same subid,
+// - every subterranean gate on the surface is paired with the nearest
+//   not yet paired gate underground, in the order of the objects.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TeleportKind {
+    OneWayMonolith,
+    TwoWayMonolith,
+    SubterraneanGate,
+    Whirlpool,
+}
+
+impl TeleportKind {
+    pub fn from(obj_type: &ObjectType) -> Option<Self> {
+        use ObjectType::*;
+        match obj_type {
+            MonolithOneWayEntrance | MonolithOneWayExit => Some(Self::OneWayMonolith),
+            MonolithTwoWay => Some(Self::TwoWayMonolith),
+            SubterraneanGate => Some(Self::SubterraneanGate),
+            Whirlpool => Some(Self::Whirlpool),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OneWayMonolith => "one way monolith",
+            Self::TwoWayMonolith => "two way monolith",
+            Self::SubterraneanGate => "subterranean gate",
+            Self::Whirlpool => "whirlpool",
+        }
+    }
+}
+
+/// Teleports of one kind and subid, any entrance can lead to any exit.
+/// For two way teleports the entrances are also the exits.
+#[derive(Debug, Clone)]
+pub struct TeleportChannel {
+    pub kind: TeleportKind,
+    pub subid: u32,
+    /// indices into Map::objects
+    pub entrances: Vec<usize>,
+    pub exits: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TeleportIssue {
+    /// one way monolith entrance without an exit of the same subid
+    DanglingEntrance {
+        object: usize,
+        position: MapCoord,
+        subid: u32,
+    },
+    /// one way monolith exit without an entrance of the same subid
+    UnreachableExit {
+        object: usize,
+        position: MapCoord,
+        subid: u32,
+    },
+    /// two way monolith or whirlpool alone in its channel
+    Lonely {
+        kind: TeleportKind,
+        object: usize,
+        position: MapCoord,
+        subid: u32,
+    },
+    /// subterranean gate without a gate on the other level
+    UnpairedGate { object: usize, position: MapCoord },
+}
+
+impl fmt::Display for TeleportIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let at = |p: &MapCoord| format!("({}, {}, {})", p.x, p.y, p.z);
+        match self {
+            Self::DanglingEntrance {
+                position, subid, ..
+            } => write!(
+                f,
+                "one way monolith entrance {subid} at {} has no exit",
+                at(position)
+            ),
+            Self::UnreachableExit {
+                position, subid, ..
+            } => write!(
+                f,
+                "one way monolith exit {subid} at {} has no entrance",
+                at(position)
+            ),
+            Self::Lonely {
+                kind,
+                position,
+                subid,
+                ..
+            } => write!(
+                f,
+                "{} {subid} at {} has no other end",
+                kind.name(),
+                at(position)
+            ),
+            Self::UnpairedGate { position, .. } => write!(
+                f,
+                "subterranean gate at {} has no pair on the other level",
+                at(position)
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TeleportNetwork {
+    /// sorted by kind and subid
+    pub channels: Vec<TeleportChannel>,
+    /// surface and underground gate, indices into Map::objects
+    pub gates: Vec<(usize, usize)>,
+    /// where each teleport can lead to, indices into Map::objects
+    pub links: BTreeMap<usize, Vec<usize>>,
+    pub issues: Vec<TeleportIssue>,
+}
+
+impl TeleportNetwork {
+    /// Objects the teleport can lead to, empty for dead ends and other objects
+    pub fn destinations(&self, object: usize) -> &[usize] {
+        self.links.get(&object).map_or(&[], |v| v.as_slice())
+    }
+}
+
+fn subid(map: &Map, obj: &Object) -> u32 {
+    map.object_templates
+        .get(obj.obj_templ_id as usize)
+        .map_or(0, |t| t.subid)
+}
+
+fn distance2(a: MapCoord, b: MapCoord) -> usize {
+    a.x.abs_diff(b.x).pow(2) + a.y.abs_diff(b.y).pow(2)
+}
+
+/// Build the teleport graph of the map and find teleports leading nowhere
+pub fn teleport_network(map: &Map) -> TeleportNetwork {
+    let mut network = TeleportNetwork::default();
+    let mut channels: BTreeMap<(TeleportKind, u32), TeleportChannel> = BTreeMap::new();
+    let mut gates = Vec::new();
+    for (i, obj) in map.objects.iter().enumerate() {
+        let Some(kind) = TeleportKind::from(&obj.obj_type) else {
+            continue;
+        };
+        if kind == TeleportKind::SubterraneanGate {
+            gates.push(i);
+            continue;
+        }
+        let subid = subid(map, obj);
+        let channel = channels
+            .entry((kind, subid))
+            .or_insert_with(|| TeleportChannel {
+                kind,
+                subid,
+                entrances: Vec::new(),
+                exits: Vec::new(),
+            });
+        match obj.obj_type {
+            ObjectType::MonolithOneWayEntrance => channel.entrances.push(i),
+            ObjectType::MonolithOneWayExit => channel.exits.push(i),
+            _ => {
+                channel.entrances.push(i);
+                channel.exits.push(i);
+            }
+        }
+    }
+
+    for channel in channels.values() {
+        let position = |i: usize| map.objects[i].position;
+        if channel.kind == TeleportKind::OneWayMonolith {
+            for &i in channel.entrances.iter() {
+                if channel.exits.is_empty() {
+                    network.issues.push(TeleportIssue::DanglingEntrance {
+                        object: i,
+                        position: position(i),
+                        subid: channel.subid,
+                    });
+                }
+                network.links.insert(i, channel.exits.clone());
+            }
+            if channel.entrances.is_empty() {
+                for &i in channel.exits.iter() {
+                    network.issues.push(TeleportIssue::UnreachableExit {
+                        object: i,
+                        position: position(i),
+                        subid: channel.subid,
+                    });
+                }
+            }
+        } else {
+            for &i in channel.entrances.iter() {
+                let others = channel
+                    .exits
+                    .iter()
+                    .copied()
+                    .filter(|o| *o != i)
+                    .collect::<Vec<_>>();
+                if others.is_empty() {
+                    network.issues.push(TeleportIssue::Lonely {
+                        kind: channel.kind,
+                        object: i,
+                        position: position(i),
+                        subid: channel.subid,
+                    });
+                }
+                network.links.insert(i, others);
+            }
+        }
+    }
+    network.channels = channels.into_values().collect();
+
+    let mut underground = gates
+        .iter()
+        .copied()
+        .filter(|i| map.objects[*i].position.z > 0)
+        .collect::<Vec<_>>();
+    let mut unpaired = Vec::new();
+    for &i in gates.iter().filter(|i| map.objects[**i].position.z == 0) {
+        let pos = map.objects[i].position;
+        let nearest = underground
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, o)| distance2(pos, map.objects[**o].position))
+            .map(|(n, _)| n);
+        match nearest {
+            Some(n) => {
+                let other = underground.remove(n);
+                network.gates.push((i, other));
+                network.links.insert(i, vec![other]);
+                network.links.insert(other, vec![i]);
+            }
+            None => unpaired.push(i),
+        }
+    }
+    unpaired.extend(underground);
+    unpaired.sort();
+    for i in unpaired {
+        network.issues.push(TeleportIssue::UnpairedGate {
+            object: i,
+            position: map.objects[i].position,
+        });
+        network.links.insert(i, Vec::new());
+    }
+    network
+}
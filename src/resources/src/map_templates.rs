@@ -0,0 +1,164 @@
+use crate::lod_reader;
+use crate::map_structs::*;
+use std::collections::HashMap;
+use std::io;
+
+// Catalogue of the default object templates, OBJECTS.TXT from H3bitmap.lod.
+//
+// The first line is the number of templates, then one template per line:
+//   DEF-name block-mask visit-mask landscape-mask terrain-mask id subid kind priority
+// e.g.
+//   AVWmrnd0.def 111111111111111111111111111111111111111111111110 000000000000000000000000000000000000000000000001 111111111 111111111 54 0 0 0
+// Masks are the bit masks of an .h3m template written as text: 6 rows of
+// 8 tiles from the top left corner, the object position is the last tile.
+// Terrain masks have one character per terrain, the last one is dirt.
+
+pub const OBJECTS_TXT: &str = "OBJECTS.TXT";
+
+#[derive(Debug, Default, Clone)]
+pub struct TemplateCatalogue {
+    templates: Vec<ObjectTemplate>,
+    by_id: HashMap<(u32, u32), Vec<usize>>,
+}
+
+fn error(line: usize, msg: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{OBJECTS_TXT} line {line}: {msg}"),
+    )
+}
+
+fn parse_number(line: usize, field: &str, s: &str) -> io::Result<u32> {
+    s.parse::<u32>()
+        .map_err(|e| error(line, format!("invalid {field} '{s}': {e}")))
+}
+
+fn parse_template(line: usize, text: &str) -> io::Result<ObjectTemplate> {
+    let fields = text.split_whitespace().collect::<Vec<_>>();
+    let [animation_file, block, visit, _landscape, terrain, id, subid, kind, priority] = fields[..]
+    else {
+        return Err(error(
+            line,
+            format!("expected 9 fields, got {}", fields.len()),
+        ));
+    };
+    let is_mask = |m: &str, len: usize| m.len() == len && m.bytes().all(|b| b == b'0' || b == b'1');
+    if !is_mask(block, 48) || !is_mask(visit, 48) {
+        return Err(error(
+            line,
+            "masks must have 48 characters of 0 or 1".to_string(),
+        ));
+    }
+    if !terrain.bytes().all(|b| b == b'0' || b == b'1') {
+        return Err(error(line, format!("invalid terrain mask '{terrain}'")));
+    }
+
+    let block = block.as_bytes();
+    let visit = visit.as_bytes();
+    let mut transit_matrix = [[TileTransitProperty::default(); 8]; 6];
+    for (dy, row) in transit_matrix.iter_mut().enumerate() {
+        for (dx, trans) in row.iter_mut().enumerate() {
+            // the same remapping as for templates stored in a map
+            let i = (5 - dy) * 8 + 7 - dx;
+            if block[i] == b'0' {
+                *trans = TileTransitProperty::TransitBlocked;
+            }
+            if visit[i] == b'1' {
+                *trans = TileTransitProperty::Visitable;
+            }
+        }
+    }
+    let allowed_terrains = terrain
+        .bytes()
+        .rev()
+        .enumerate()
+        .filter(|(_, b)| *b == b'1')
+        .filter_map(|(i, _)| Surface::from(i as u8))
+        .collect();
+    let kind = parse_number(line, "kind", kind)?;
+    let render_priority = parse_number(line, "priority", priority)?;
+    Ok(ObjectTemplate {
+        animation_file: animation_file.to_string(),
+        transit_matrix,
+        allowed_terrains,
+        id: parse_number(line, "id", id)?,
+        subid: parse_number(line, "subid", subid)?,
+        obj_kind: ObjectKind::from(kind as u8),
+        render_priority: render_priority as u8,
+    })
+}
+
+impl TemplateCatalogue {
+    /// Parse the contents of OBJECTS.TXT
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut lines = text.lines().enumerate();
+        let count = match lines.next() {
+            Some((_, l)) => parse_number(1, "template count", l.trim())? as usize,
+            None => return Err(error(1, "file is empty".to_string())),
+        };
+        let mut catalogue = Self::default();
+        for (i, l) in lines.filter(|(_, l)| !l.trim().is_empty()).take(count) {
+            catalogue.add(parse_template(i + 1, l)?);
+        }
+        if catalogue.templates.len() != count {
+            return Err(error(
+                1,
+                format!(
+                    "{count} templates declared, {} found",
+                    catalogue.templates.len()
+                ),
+            ));
+        }
+        Ok(catalogue)
+    }
+
+    /// Load OBJECTS.TXT from a LOD archive, usually H3bitmap.lod
+    pub fn from_lod(lod: &[u8]) -> io::Result<Self> {
+        let dir = lod_reader::parse_lod_directory(lod)?;
+        let Some(entry) = dir
+            .entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(OBJECTS_TXT))
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{OBJECTS_TXT} is not in the archive"),
+            ));
+        };
+        let data = lod_reader::read_lod_entry(lod, entry)?;
+        // game texts aren't utf8, but DEF names and numbers are plain ascii
+        Self::parse(&String::from_utf8_lossy(&data))
+    }
+
+    pub fn add(&mut self, template: ObjectTemplate) {
+        self.by_id
+            .entry((template.id, template.subid))
+            .or_default()
+            .push(self.templates.len());
+        self.templates.push(template);
+    }
+
+    pub fn templates(&self) -> &[ObjectTemplate] {
+        &self.templates
+    }
+
+    /// All templates of the object, in the catalogue order
+    pub fn find(&self, id: u32, subid: u32) -> impl Iterator<Item = &ObjectTemplate> {
+        self.by_id
+            .get(&(id, subid))
+            .into_iter()
+            .flatten()
+            .map(|i| &self.templates[*i])
+    }
+
+    /// The first template of the object that can be placed on the terrain
+    pub fn find_for_terrain(
+        &self,
+        id: u32,
+        subid: u32,
+        surface: Surface,
+    ) -> Option<&ObjectTemplate> {
+        self.find(id, subid)
+            .find(|t| t.allowed_terrains.contains(&surface))
+    }
+}
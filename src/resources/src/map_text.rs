@@ -0,0 +1,362 @@
+use crate::map_obj_type::*;
+use crate::map_structs::*;
+
+// Player facing texts of quests, rewards and guards, in the style of the game.
+// Maps store empty strings for the quest messages left to the default texts,
+// these are generated from the quest the way the game does.
+
+// the smallest amount of each army size, as shown by the game
+const ARMY_SIZES: [(u32, &str); 9] = [
+    (1000, "Legion"),
+    (500, "Zounds"),
+    (250, "Swarm"),
+    (100, "Throng"),
+    (50, "Horde"),
+    (20, "Lots"),
+    (10, "Pack"),
+    (5, "Several"),
+    (0, "Few"),
+];
+
+/// The game's word for the size of a stack: Few, Several, Pack, ...
+pub fn army_size(amount: u32) -> &'static str {
+    ARMY_SIZES
+        .iter()
+        .find(|(min, _)| amount >= *min)
+        .map_or("Few", |(_, word)| word)
+}
+
+fn plural(creature: CreatureId) -> String {
+    creature
+        .plural_name()
+        .unwrap_or_else(|| creature.to_string())
+}
+
+/// A stack the player can't count, e.g. "a Pack of Griffins"
+pub fn describe_stack(creature: CreatureId, amount: u32) -> String {
+    let creatures = plural(creature);
+    match army_size(amount) {
+        "Few" => format!("a Few {creatures}"),
+        "Several" => format!("Several {creatures}"),
+        word @ ("Lots" | "Zounds") => format!("{word} of {creatures}"),
+        word => format!("a {word} of {creatures}"),
+    }
+}
+
+/// An exact amount of creatures, e.g. "3 Gold Dragons"
+pub fn count_stack(creature: CreatureId, amount: u32) -> String {
+    if amount == 1 {
+        format!("1 {creature}")
+    } else {
+        format!("{amount} {}", plural(creature))
+    }
+}
+
+/// "Guarded by a Pack of Griffins and Several Angels", None without creatures.
+/// Stacks of the same creature are shown together.
+pub fn describe_guard(guard: &CreatureGuard) -> Option<String> {
+    let mut stacks: Vec<(CreatureId, u32)> = Vec::new();
+    for s in guard.slot.iter().filter(|s| s.amount > 0) {
+        let Some(c) = s.creature else {
+            continue;
+        };
+        match stacks.iter_mut().find(|(sc, _)| *sc == c) {
+            Some((_, amount)) => *amount += s.amount,
+            None => stacks.push((c, s.amount)),
+        }
+    }
+    if stacks.is_empty() {
+        return None;
+    }
+    let stacks = stacks
+        .into_iter()
+        .map(|(c, amount)| describe_stack(c, amount))
+        .collect::<Vec<_>>();
+    Some(format!("Guarded by {}", join(&stacks)))
+}
+
+pub fn resource_name(resource: Resource) -> &'static str {
+    match resource {
+        Resource::Wood => "Wood",
+        Resource::Mercury => "Mercury",
+        Resource::Ore => "Ore",
+        Resource::Sulfur => "Sulfur",
+        Resource::Crystal => "Crystal",
+        Resource::Gems => "Gems",
+        Resource::Gold => "Gold",
+        Resource::Mithril => "Mithril",
+    }
+}
+
+fn resources(pack: &ResourcePack, signed: bool) -> Vec<String> {
+    ALL_RESOURCES
+        .iter()
+        .zip(pack.0)
+        .filter(|(_, amount)| *amount != 0)
+        .map(|(r, amount)| format!("{} {}", number(amount, signed), resource_name(*r)))
+        .collect()
+}
+
+fn primary_skills(skills: &PrimarySkills, signed: bool) -> Vec<String> {
+    [
+        (skills.attack, "Attack"),
+        (skills.defence, "Defense"),
+        (skills.spell_power, "Spell Power"),
+        (skills.knowledge, "Knowledge"),
+    ]
+    .into_iter()
+    .filter(|(value, _)| *value > 0)
+    .map(|(value, name)| format!("{} {name}", number(value as i32, signed)))
+    .collect()
+}
+
+fn number(value: i32, signed: bool) -> String {
+    if signed {
+        format!("{value:+}")
+    } else {
+        value.to_string()
+    }
+}
+
+fn artifacts(artifacts: &[ArtifactId]) -> Vec<String> {
+    artifacts.iter().map(|a| format!("the {a}")).collect()
+}
+
+fn spells(spells: &[SpellId]) -> Vec<String> {
+    spells.iter().map(|s| format!("the {s} spell")).collect()
+}
+
+/// "A, B and C"
+fn join(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{} and {last}", rest.join(", ")),
+    }
+}
+
+fn location(pos: MapCoord) -> String {
+    let level = if pos.z > 0 { " underground" } else { "" };
+    format!("at ({}, {}){level}", pos.x, pos.y)
+}
+
+// hero or monster objects referenced by quests through their quest identifier
+fn quest_target(map: &Map, quest_id: u32) -> Option<(&ObjectType, MapCoord)> {
+    use ObjectType::*;
+    map.objects.iter().find_map(|o| {
+        let id = match &o.obj_type {
+            Hero(h) | RandomHero(h) | Prison(h) => h.quest_id,
+            Monster(m) | RandomMonster(m) | RandomMonsterL1(m) | RandomMonsterL2(m)
+            | RandomMonsterL3(m) | RandomMonsterL4(m) | RandomMonsterL5(m) | RandomMonsterL6(m)
+            | RandomMonsterL7(m) => m.quest_id,
+            _ => return None,
+        };
+        (id == quest_id).then_some((&o.obj_type, o.position))
+    })
+}
+
+pub(crate) fn hero_name(h: &HeroData) -> String {
+    match (&h.name, h.hero_id) {
+        (Some(name), _) => name.clone(),
+        (None, Some(id)) => id.to_string(),
+        (None, None) => "the hero".to_string(),
+    }
+}
+
+/// What the quest asks for, e.g. "Bring me 3 Gold Dragons".
+/// Heroes and monsters to defeat are looked up in the map.
+pub fn describe_mission(mission: &QuestMissionType, map: &Map) -> String {
+    use QuestMissionType::*;
+    match mission {
+        NoMission => String::new(),
+        ExpLevel(level) => format!("Reach experience level {level}"),
+        PrimarySkill(skills) => {
+            format!("Gain at least {}", join(&primary_skills(skills, false)))
+        }
+        KillHero(quest_id) => match quest_target(map, *quest_id) {
+            Some((
+                ObjectType::Hero(h) | ObjectType::RandomHero(h) | ObjectType::Prison(h),
+                pos,
+            )) => {
+                format!("Defeat {} {}", hero_name(h), location(pos))
+            }
+            _ => "Defeat the hero I seek".to_string(),
+        },
+        KillCreature(quest_id) => match quest_target(map, *quest_id) {
+            Some((
+                ObjectType::Monster(MonsterData {
+                    creature: Some(c),
+                    amount,
+                    ..
+                }),
+                pos,
+            )) => {
+                let stack = describe_stack(*c, *amount);
+                format!("Defeat {stack} {}", location(pos))
+            }
+            Some((_, pos)) => format!("Defeat the monsters {}", location(pos)),
+            None => "Defeat the monsters I seek".to_string(),
+        },
+        Artifact(v) => format!("Bring me {}", join(&artifacts(v))),
+        Army(v) => {
+            let stacks = v
+                .iter()
+                .map(|(c, amount)| count_stack(*c, *amount))
+                .collect::<Vec<_>>();
+            format!("Bring me {}", join(&stacks))
+        }
+        Resources(pack) => format!("Bring me {}", join(&resources(pack, false))),
+        Hero(id) => format!("Only {id} may complete this quest"),
+        Player(Some(p)) => format!("Only the {p:?} player may complete this quest"),
+        Player(None) => "Only a certain player may complete this quest".to_string(),
+        HOTAMulti => "Fulfil all of my requirements".to_string(),
+        Keymaster => "Visit the Keymaster's Tent".to_string(),
+        HOTAHeroClass(_) => "Only heroes of certain classes may complete this quest".to_string(),
+        HOTAReachDate(day) => format!("Return to me on day {day}"),
+    }
+}
+
+/// The reward of a seer hut, e.g. "1000 experience", None for no reward
+pub fn describe_reward(reward: &SeerHutRewardType) -> Option<String> {
+    use SeerHutRewardType::*;
+    let text = match reward {
+        Nothing => return None,
+        Experience(exp) => format!("{exp} experience"),
+        ManaPoints(mana) => format!("{mana} spell points"),
+        Morale(m) => format!("{m:+} morale"),
+        Luck(l) => format!("{l:+} luck"),
+        Resources((r, amount)) => {
+            let name = Resource::from(*r).map_or("resources", resource_name);
+            format!("{amount} {name}")
+        }
+        PrimarySkills(skills) => join(&primary_skills(skills, true)),
+        SecondarySkills(skills) => join(&skills.iter().map(|s| s.to_string()).collect::<Vec<_>>()),
+        Artifact(v) => join(&artifacts(v)),
+        Spell(v) => join(&spells(v)),
+        Creature(v) => join(
+            &v.iter()
+                .map(|(c, amount)| count_stack(*c, *amount))
+                .collect::<Vec<_>>(),
+        ),
+    };
+    Some(text)
+}
+
+/// Everything a Pandora's box or an event gives or takes, guards excluded
+pub fn describe_box(content: &BoxContent) -> Vec<String> {
+    let mut ret = Vec::new();
+    if content.reward_experience > 0 {
+        ret.push(format!("{} experience", content.reward_experience));
+    }
+    if content.reward_mana_diff != 0 {
+        ret.push(format!("{:+} spell points", content.reward_mana_diff));
+    }
+    if content.reward_next_battle_morale != 0 {
+        ret.push(format!("{:+} morale", content.reward_next_battle_morale));
+    }
+    if content.reward_next_battle_luck != 0 {
+        ret.push(format!("{:+} luck", content.reward_next_battle_luck));
+    }
+    ret.extend(resources(&content.reward_resources, true));
+    ret.extend(primary_skills(&content.reward_primary_skills, true));
+    ret.extend(
+        content
+            .reward_secondary_skills
+            .iter()
+            .map(|s| s.to_string()),
+    );
+    ret.extend(artifacts(&content.reward_artifacts));
+    ret.extend(spells(&content.reward_spells));
+    ret.extend(
+        content
+            .reward_creatures
+            .iter()
+            .map(|(c, amount)| count_stack(*c, *amount)),
+    );
+    ret
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QuestTexts {
+    /// shown on the first visit
+    pub proposal: String,
+    /// shown while the quest isn't fulfilled
+    pub progress: String,
+    /// shown when the quest is fulfilled
+    pub completion: String,
+}
+
+fn deadline(last_day: i32) -> String {
+    if last_day >= 0 {
+        format!(" You have until day {last_day}.")
+    } else {
+        String::new()
+    }
+}
+
+fn custom_or(custom: bool, message: &str, default: String) -> String {
+    if custom {
+        message.to_string()
+    } else {
+        default
+    }
+}
+
+/// Messages of a seer hut quest, the default ones where the map doesn't set them
+pub fn seer_hut_texts(hut: &SeerHutData, map: &Map) -> QuestTexts {
+    let m = &hut.mission;
+    let goal = describe_mission(&m.mission_type, map);
+    let reward = describe_reward(&hut.reward);
+    let offer = match &reward {
+        Some(r) => format!(" In return I will give you {r}."),
+        None => String::new(),
+    };
+    let thanks = match &reward {
+        Some(r) => format!(" Please accept {r} as your reward."),
+        None => String::new(),
+    };
+    QuestTexts {
+        proposal: custom_or(
+            hut.is_custom_first,
+            &m.proposal_message,
+            format!("{goal}.{offer}{}", deadline(m.last_day)),
+        ),
+        progress: custom_or(
+            hut.is_custom_last,
+            &m.progress_message,
+            format!(
+                "You have not yet done what I asked. {goal}.{}",
+                deadline(m.last_day)
+            ),
+        ),
+        completion: custom_or(
+            hut.is_custom_complete,
+            &m.completion_message,
+            format!("You have done what I asked.{thanks}"),
+        ),
+    }
+}
+
+/// Messages of a quest guard, the default ones where the map doesn't set them
+pub fn quest_guard_texts(mission: &QuestMission, map: &Map) -> QuestTexts {
+    let goal = describe_mission(&mission.mission_type, map);
+    let or_default =
+        |message: &str, default: String| custom_or(!message.is_empty(), message, default);
+    QuestTexts {
+        proposal: or_default(
+            &mission.proposal_message,
+            format!(
+                "{goal}. Only then will I let you pass.{}",
+                deadline(mission.last_day)
+            ),
+        ),
+        progress: or_default(
+            &mission.progress_message,
+            format!("You may not pass yet. {goal}."),
+        ),
+        completion: or_default(
+            &mission.completion_message,
+            "You have done what I asked. You may pass.".to_string(),
+        ),
+    }
+}
@@ -0,0 +1,473 @@
+use crate::map_structs::*;
+use std::ops::Range;
+
+// Terrain auto-tiling, following the rules of the original map editor.
+//
+// Every terrain DEF starts with the transition frames, all drawn with the
+// foreign terrain on the top/left side; other orientations are obtained
+// through the mirroring flags:
+//   0-3   outer corner (only the top-left diagonal neighbour differs)
+//   4-7   inner corner (top and left neighbours differ)
+//   8-11  vertical edge (left neighbour differs)
+//   12-15 horizontal edge (top neighbour differs)
+//   16-17 two opposite corners (top-left and bottom-right)
+//   18-19 inner corner with the opposite corner
+// The "normal" terrains (grass, snow, swamp, rough, subterranean, lava and the
+// HotA highlands and wasteland) have these frames twice: 0-19 blend into dirt,
+// 20-39 blend into sand (coast). Frames 40-48 blend into both at once, see
+// MIXED_FRAMES.
+// Dirt only blends into sand and water only into the shore.
+// Any other neighbourhood has no frame, the original editor solves it by
+// changing the tile to the neighbouring terrain and so does retile().
+
+pub const MIRROR_TERRAIN_HORIZONTAL: u8 = 0x01;
+pub const MIRROR_TERRAIN_VERTICAL: u8 = 0x02;
+pub const MIRROR_TERRAIN_MASK: u8 = 0x03;
+pub const COAST_FLAG: u8 = 0x40;
+
+const SAND_FRAMES_OFFSET: u8 = 20;
+// how many times invalid tiles are converted before giving up
+const MAX_FIX_PASSES: usize = 8;
+
+// neighbour bits, in the order of NEIGHBOURS
+const TL: u8 = 1 << 0;
+const T: u8 = 1 << 1;
+const TR: u8 = 1 << 2;
+const L: u8 = 1 << 3;
+const R: u8 = 1 << 4;
+const BL: u8 = 1 << 5;
+const B: u8 = 1 << 6;
+const BR: u8 = 1 << 7;
+
+/// Transitions of the normal terrains into dirt and sand at once: frame,
+/// dirt neighbours, sand neighbours, and the neighbours that may also be dirt
+/// or sand. Like the other frames, drawn with the foreign terrains on the
+/// top/left side.
+const MIXED_FRAMES: [(u8, u8, u8, u8, u8); 9] = [
+    // inner corner, dirt on top and sand on the left, or the other way
+    (40, T, L, TL | TR, TL | BL),
+    (41, L, T, TL | BL, TL | TR),
+    // opposite corners
+    (42, TL, BR, 0, 0),
+    // inner corner with the opposite corner of the other terrain
+    (43, T | L, BR, TL | TR | BL, 0),
+    (44, BR, T | L, 0, TL | TR | BL),
+    // horizontal edge turning into the other terrain at the top-right corner
+    (45, T, TR, TL, 0),
+    (46, TR, T, 0, TL),
+    // vertical edge turning into the other terrain at the bottom-left corner
+    (47, L, BL, TL, 0),
+    (48, BL, L, 0, TL),
+];
+
+pub(crate) const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Border {
+    Dirt,
+    Sand,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Pattern {
+    OuterCorner,
+    InnerCorner,
+    VerticalEdge,
+    HorizontalEdge,
+    OppositeCorners,
+    InnerCornerWithOpposite,
+}
+
+impl Pattern {
+    // first frame and number of variants
+    fn frames(&self) -> (u8, u8) {
+        use Pattern::*;
+        match *self {
+            OuterCorner => (0, 4),
+            InnerCorner => (4, 4),
+            VerticalEdge => (8, 4),
+            HorizontalEdge => (12, 4),
+            OppositeCorners => (16, 2),
+            InnerCornerWithOpposite => (18, 2),
+        }
+    }
+
+    // `mask` is already flipped so the foreign terrain is on the top/left side
+    fn classify(mask: u8) -> Option<Self> {
+        use Pattern::*;
+        let sides = mask & (T | L | R | B);
+        let corners = mask & (TL | TR | BL | BR);
+        match sides {
+            0 if corners == TL => Some(OuterCorner),
+            0 if corners == TL | BR => Some(OppositeCorners),
+            s if s == T | L && corners & BR == 0 => Some(InnerCorner),
+            s if s == T | L => Some(InnerCornerWithOpposite),
+            s if s == L && corners & (TR | BR) == 0 => Some(VerticalEdge),
+            s if s == T && corners & (BL | BR) == 0 => Some(HorizontalEdge),
+            _ => None,
+        }
+    }
+}
+
+/// Interior (non transition) frames of the terrain DEF
+pub fn interior_frames(surface: Surface) -> Range<u8> {
+    use Surface::*;
+    match surface {
+        Dirt => 21..45,
+        Sand => 0..24,
+        Water => 21..33,
+        Rock => 0..8,
+        Grass | Snow | Swamp | Rough | Subterranean | Lava | Highlands | Wasteland => 49..73,
+    }
+}
+
+fn border(center: Surface, neighbour: Surface) -> Option<Border> {
+    use Surface::*;
+    if center == neighbour {
+        return None;
+    }
+    match (center, neighbour) {
+        (Sand, _) | (Rock, _) => None,
+        (Water, _) => Some(Border::Sand),
+        (_, Sand) | (_, Water) => Some(Border::Sand),
+        (Dirt, _) => None,
+        _ => Some(Border::Dirt),
+    }
+}
+
+fn flip(mask: u8, horizontal: bool, vertical: bool) -> u8 {
+    let mut ret = mask;
+    if horizontal {
+        ret = swap_bits(ret, TL, TR);
+        ret = swap_bits(ret, L, R);
+        ret = swap_bits(ret, BL, BR);
+    }
+    if vertical {
+        ret = swap_bits(ret, TL, BL);
+        ret = swap_bits(ret, T, B);
+        ret = swap_bits(ret, TR, BR);
+    }
+    ret
+}
+
+fn swap_bits(mask: u8, a: u8, b: u8) -> u8 {
+    let rest = mask & !(a | b);
+    let mut ret = rest;
+    if mask & a != 0 {
+        ret |= b;
+    }
+    if mask & b != 0 {
+        ret |= a;
+    }
+    ret
+}
+
+pub(crate) fn neighbour(
+    terrain: &Terrain,
+    x: usize,
+    y: usize,
+    d: (isize, isize),
+) -> Option<&TerrainTile> {
+    let nx = x.checked_add_signed(d.0)?;
+    let ny = y.checked_add_signed(d.1)?;
+    terrain.get(nx)?.get(ny)
+}
+
+// pseudo random, but stable, choice of a frame variant
+pub(crate) fn variant(x: usize, y: usize, count: u8) -> u8 {
+    let h = (x as u32).wrapping_mul(73_856_093) ^ (y as u32).wrapping_mul(19_349_663);
+    (h % count as u32) as u8
+}
+
+// masks of the neighbours the tile has to blend into as dirt and as sand
+fn border_masks(terrain: &Terrain, x: usize, y: usize) -> (u8, u8) {
+    let center = terrain[x][y].surface_type;
+    let (mut dirt, mut sand) = (0u8, 0u8);
+    for (bit, d) in NEIGHBOURS.iter().enumerate() {
+        match neighbour(terrain, x, y, *d).and_then(|n| border(center, n.surface_type)) {
+            Some(Border::Dirt) => dirt |= 1 << bit,
+            Some(Border::Sand) => sand |= 1 << bit,
+            None => {}
+        }
+    }
+    (dirt, sand)
+}
+
+// pattern and mirroring for the given neighbour mask, None if there is no frame for it
+fn match_pattern(mask: u8) -> Option<(Pattern, bool, bool)> {
+    for (h, v) in [(false, false), (true, false), (false, true), (true, true)] {
+        if let Some(p) = Pattern::classify(flip(mask, h, v)) {
+            return Some((p, h, v));
+        }
+    }
+    None
+}
+
+// mixed frame and mirroring for the dirt and sand masks
+fn match_mixed(dirt: u8, sand: u8) -> Option<(u8, bool, bool)> {
+    for (h, v) in [(false, false), (true, false), (false, true), (true, true)] {
+        let (d, s) = (flip(dirt, h, v), flip(sand, h, v));
+        let frame = MIXED_FRAMES
+            .iter()
+            .find(|(_, dirt, sand, maybe_dirt, maybe_sand)| {
+                d & !maybe_dirt == *dirt && s & !maybe_sand == *sand
+            });
+        if let Some((frame, ..)) = frame {
+            return Some((*frame, h, v));
+        }
+    }
+    None
+}
+
+// first frame, number of variants and mirroring of the transition for the
+// dirt and sand masks, None if there is no frame for them
+fn transition(surface: Surface, dirt: u8, sand: u8) -> Option<(u8, u8, bool, bool)> {
+    let (mask, offset) = match (dirt, sand) {
+        (0, 0) => return None,
+        (_, 0) => (dirt, 0),
+        // water and dirt only have their shore frames
+        (0, _) if matches!(surface, Surface::Water | Surface::Dirt) => (sand, 0),
+        (0, _) => (sand, SAND_FRAMES_OFFSET),
+        _ => return match_mixed(dirt, sand).map(|(frame, h, v)| (frame, 1, h, v)),
+    };
+    let (pattern, h, v) = match_pattern(mask)?;
+    let (first, count) = pattern.frames();
+    Some((offset + first, count, h, v))
+}
+
+// the terrain an invalid tile is converted to: the most frequent foreign neighbour
+fn replacement(terrain: &Terrain, x: usize, y: usize) -> Option<Surface> {
+    let center = terrain[x][y].surface_type;
+    let mut counts: Vec<(Surface, usize)> = Vec::new();
+    for d in NEIGHBOURS {
+        if let Some(n) = neighbour(terrain, x, y, d) {
+            if border(center, n.surface_type).is_none() {
+                continue;
+            }
+            match counts.iter_mut().find(|(s, _)| *s == n.surface_type) {
+                Some((_, c)) => *c += 1,
+                None => counts.push((n.surface_type, 1)),
+            }
+        }
+    }
+    let mut best: Option<(Surface, usize)> = None;
+    for (s, c) in counts {
+        if best.is_none_or(|(_, bc)| c > bc) {
+            best = Some((s, c));
+        }
+    }
+    best.map(|(s, _)| s)
+}
+
+/// Choose surface pictures and mirroring for tiles in the given region.
+/// Tiles outside the region are only read. Tiles whose neighbourhood can't
+/// be drawn are converted to the neighbouring terrain, like the original editor does.
+pub fn retile(terrain: &mut Terrain, xs: Range<usize>, ys: Range<usize>) {
+    let width = terrain.len();
+    let height = terrain.first().map_or(0, |c| c.len());
+    let xs = xs.start.min(width)..xs.end.min(width);
+    let ys = ys.start.min(height)..ys.end.min(height);
+
+    for _ in 0..MAX_FIX_PASSES {
+        let mut invalid = Vec::new();
+        for x in xs.clone() {
+            for y in ys.clone() {
+                let (dirt, sand) = border_masks(terrain, x, y);
+                let surface = terrain[x][y].surface_type;
+                if dirt | sand != 0 && transition(surface, dirt, sand).is_none() {
+                    if let Some(s) = replacement(terrain, x, y) {
+                        invalid.push((x, y, s));
+                    }
+                }
+            }
+        }
+        if invalid.is_empty() {
+            break;
+        }
+        for (x, y, s) in invalid {
+            let tile = &mut terrain[x][y];
+            tile.surface_type = s;
+            if s == Surface::Water || s == Surface::Rock {
+                tile.road_type = None;
+                tile.river_type = None;
+            }
+        }
+    }
+
+    for x in xs.clone() {
+        for y in ys.clone() {
+            let (dirt, sand) = border_masks(terrain, x, y);
+            let surface = terrain[x][y].surface_type;
+            let coast = surface != Surface::Water
+                && NEIGHBOURS.iter().any(|d| {
+                    neighbour(terrain, x, y, *d).is_some_and(|n| n.surface_type == Surface::Water)
+                });
+            let (picture, mirroring) = match transition(surface, dirt, sand) {
+                Some((first, count, h, v)) => {
+                    let mut mirroring = 0;
+                    if h {
+                        mirroring |= MIRROR_TERRAIN_HORIZONTAL;
+                    }
+                    if v {
+                        mirroring |= MIRROR_TERRAIN_VERTICAL;
+                    }
+                    (first + variant(x, y, count), mirroring)
+                }
+                None => {
+                    // interior, or a neighbourhood that couldn't be fixed
+                    let frames = interior_frames(surface);
+                    (frames.start + variant(x, y, frames.end - frames.start), 0)
+                }
+            };
+            let tile = &mut terrain[x][y];
+            tile.surface_picture = picture;
+            tile.mirroring_flags &= !(MIRROR_TERRAIN_MASK | COAST_FLAG);
+            tile.mirroring_flags |= mirroring;
+            if coast {
+                tile.mirroring_flags |= COAST_FLAG;
+            }
+        }
+    }
+}
+
+/// Retile every level of the map, e.g. after importing it from another format
+pub fn retile_map(map: &mut Map) {
+    let size = map.dimension();
+    for terrain in map.terrains.iter_mut() {
+        retile(terrain, 0..size, 0..size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // grass grid with the given tiles replaced, retiled as a whole
+    fn retiled(n: usize, tiles: &[(usize, usize, Surface)]) -> Terrain {
+        let mut terrain = vec![vec![TerrainTile::default(); n]; n];
+        for (x, y, s) in tiles {
+            terrain[*x][*y].surface_type = *s;
+        }
+        retile(&mut terrain, 0..n, 0..n);
+        terrain
+    }
+
+    // picture range and terrain mirroring of the tile
+    fn assert_tile(terrain: &Terrain, x: usize, y: usize, frames: Range<u8>, mirroring: u8) {
+        let tile = &terrain[x][y];
+        assert!(
+            frames.contains(&tile.surface_picture),
+            "({x}, {y}): picture {} not in {frames:?}",
+            tile.surface_picture
+        );
+        assert_eq!(
+            tile.mirroring_flags & MIRROR_TERRAIN_MASK,
+            mirroring,
+            "({x}, {y})"
+        );
+    }
+
+    #[test]
+    fn coast() {
+        let terrain = retiled(
+            3,
+            &[
+                (0, 0, Surface::Water),
+                (0, 1, Surface::Water),
+                (0, 2, Surface::Water),
+            ],
+        );
+        // grass blends into the water through its sand frames
+        assert_tile(&terrain, 1, 1, 28..32, 0);
+        assert_ne!(terrain[1][1].mirroring_flags & COAST_FLAG, 0);
+        // the shore of the water, drawn on its left side
+        assert_tile(&terrain, 0, 1, 8..12, MIRROR_TERRAIN_HORIZONTAL);
+        assert_eq!(terrain[0][1].mirroring_flags & COAST_FLAG, 0);
+        assert_tile(&terrain, 2, 1, 49..73, 0);
+        assert_eq!(terrain[2][1].mirroring_flags & COAST_FLAG, 0);
+    }
+
+    #[test]
+    fn dirt_only() {
+        let terrain = retiled(3, &[(0, 0, Surface::Dirt)]);
+        assert_tile(&terrain, 1, 0, 8..12, 0);
+        assert_tile(&terrain, 0, 1, 12..16, 0);
+        assert_tile(&terrain, 1, 1, 0..4, 0);
+        // dirt doesn't blend into grass
+        assert_tile(&terrain, 0, 0, 21..45, 0);
+    }
+
+    #[test]
+    fn sand_only() {
+        let terrain = retiled(3, &[(2, 2, Surface::Sand)]);
+        assert_tile(&terrain, 1, 1, 20..24, MIRROR_TERRAIN_MASK);
+        assert_tile(&terrain, 1, 2, 28..32, MIRROR_TERRAIN_HORIZONTAL);
+        assert_tile(&terrain, 2, 1, 32..36, MIRROR_TERRAIN_VERTICAL);
+        assert_tile(&terrain, 2, 2, 0..24, 0);
+        assert_eq!(terrain[1][1].mirroring_flags & COAST_FLAG, 0);
+    }
+
+    #[test]
+    fn mixed_corner() {
+        // dirt on top, sand on the left
+        let terrain = retiled(
+            3,
+            &[
+                (0, 0, Surface::Sand),
+                (1, 0, Surface::Dirt),
+                (2, 0, Surface::Dirt),
+                (0, 1, Surface::Sand),
+                (0, 2, Surface::Sand),
+            ],
+        );
+        assert_eq!(terrain[1][1].surface_type, Surface::Grass);
+        assert_tile(&terrain, 1, 1, 40..41, 0);
+        // dirt at the bottom, sand on the right
+        let terrain = retiled(
+            3,
+            &[
+                (0, 2, Surface::Dirt),
+                (1, 2, Surface::Dirt),
+                (2, 2, Surface::Sand),
+                (2, 1, Surface::Sand),
+                (2, 0, Surface::Sand),
+            ],
+        );
+        assert_eq!(terrain[1][1].surface_type, Surface::Grass);
+        assert_tile(&terrain, 1, 1, 40..41, MIRROR_TERRAIN_MASK);
+    }
+
+    #[test]
+    fn mixed_opposite_corners() {
+        let terrain = retiled(3, &[(0, 0, Surface::Dirt), (2, 2, Surface::Sand)]);
+        assert_tile(&terrain, 1, 1, 42..43, 0);
+        let terrain = retiled(3, &[(2, 0, Surface::Sand), (0, 2, Surface::Dirt)]);
+        assert_tile(&terrain, 1, 1, 42..43, MIRROR_TERRAIN_VERTICAL);
+    }
+
+    #[test]
+    fn convert_tiles_without_frame() {
+        // dirt on the left and sand on the right have no frame
+        let terrain = retiled(
+            3,
+            &[
+                (0, 0, Surface::Dirt),
+                (0, 1, Surface::Dirt),
+                (0, 2, Surface::Dirt),
+                (2, 0, Surface::Sand),
+                (2, 1, Surface::Sand),
+                (2, 2, Surface::Sand),
+            ],
+        );
+        assert_ne!(terrain[1][1].surface_type, Surface::Grass);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::map_passability;
+use crate::map_roads;
+use crate::map_structs::*;
+use crate::map_tiling;
+
+// Whole map transforms. Object sprites can't be mirrored or rotated, so an
+// object keeps its shape and is moved so that its footprint covers the
+// transformed footprint area; the anchor (bottom-right corner of the
+// template) is recomputed from it.
+
+const MIRROR_HORIZONTAL_BITS: u8 = map_tiling::MIRROR_TERRAIN_HORIZONTAL
+    | map_roads::MIRROR_RIVER_HORIZONTAL
+    | map_roads::MIRROR_ROAD_HORIZONTAL;
+const MIRROR_VERTICAL_BITS: u8 = map_tiling::MIRROR_TERRAIN_VERTICAL
+    | map_roads::MIRROR_RIVER_VERTICAL
+    | map_roads::MIRROR_ROAD_VERTICAL;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Axis {
+    /// left becomes right
+    Horizontal,
+    /// top becomes bottom
+    Vertical,
+}
+
+/// What had to be dropped because it ended up outside of a resized map
+#[derive(Debug, Default)]
+pub struct ResizeReport {
+    pub removed_objects: Vec<Object>,
+    pub removed_main_towns: Vec<Player>,
+    pub removed_victory_condition: Option<SpecialVictoryCondition>,
+    pub removed_loss_condition: Option<SpecialLossCondition>,
+}
+
+pub fn mirror(map: &mut Map, axis: Axis) {
+    let n = map.dimension();
+    let templates = &map.object_templates;
+    let new_positions = map
+        .objects
+        .iter()
+        .map(|o| {
+            let (w, h) = footprint(templates, o);
+            let mut c = o.position;
+            match axis {
+                Axis::Horizontal => c.x = (n - 1 - c.x + w - 1).min(n - 1),
+                Axis::Vertical => c.y = (n - 1 - c.y + h - 1).min(n - 1),
+            }
+            c
+        })
+        .collect::<Vec<_>>();
+    remap_coords(map, &new_positions, |c| match axis {
+        Axis::Horizontal => MapCoord {
+            x: n - 1 - c.x,
+            ..c
+        },
+        Axis::Vertical => MapCoord {
+            y: n - 1 - c.y,
+            ..c
+        },
+    });
+
+    let flags = match axis {
+        Axis::Horizontal => MIRROR_HORIZONTAL_BITS,
+        Axis::Vertical => MIRROR_VERTICAL_BITS,
+    };
+    for terrain in map.terrains.iter_mut() {
+        match axis {
+            Axis::Horizontal => terrain.reverse(),
+            Axis::Vertical => terrain.iter_mut().for_each(|column| column.reverse()),
+        }
+        for tile in terrain.iter_mut().flatten() {
+            // every picture is drawn mirrored the other way
+            tile.mirroring_flags ^= flags;
+        }
+    }
+}
+
+/// Rotate the map by 90 degrees clockwise
+pub fn rotate_clockwise(map: &mut Map) {
+    let n = map.dimension();
+    let templates = &map.object_templates;
+    let new_positions = map
+        .objects
+        .iter()
+        .map(|o| {
+            let (_, h) = footprint(templates, o);
+            MapCoord {
+                x: (n - 1 - o.position.y + h - 1).min(n - 1),
+                y: o.position.x,
+                z: o.position.z,
+            }
+        })
+        .collect::<Vec<_>>();
+    remap_coords(map, &new_positions, |c| MapCoord {
+        x: n - 1 - c.y,
+        y: c.x,
+        z: c.z,
+    });
+
+    for terrain in map.terrains.iter_mut() {
+        let mut rotated = terrain.clone();
+        for (x, column) in rotated.iter_mut().enumerate() {
+            for (y, tile) in column.iter_mut().enumerate() {
+                *tile = terrain[y][n - 1 - x];
+            }
+        }
+        *terrain = rotated;
+        // horizontal and vertical pieces swap, mirroring can't express that
+        map_tiling::retile(terrain, 0..n, 0..n);
+        map_roads::connect_roads(terrain, 0..n, 0..n);
+        map_roads::connect_rivers(terrain, 0..n, 0..n);
+    }
+}
+
+/// Crop or pad the map to another standard size, the top-left corner stays in place.
+/// New area is filled with water on the surface and rock underground.
+pub fn resize(map: &mut Map, size: MapSize) -> ResizeReport {
+    let old = map.dimension();
+    let n = size.dimension();
+    let mut report = ResizeReport::default();
+    let inside = |c: &MapCoord| c.x < n && c.y < n;
+
+    let (kept, removed) = std::mem::take(&mut map.objects)
+        .into_iter()
+        .partition(|o| inside(&o.position));
+    map.objects = kept;
+    report.removed_objects = removed;
+    for p in map.players.iter_mut() {
+        if p.main_town_position.is_some_and(|c| !inside(&c)) {
+            p.main_town_position = None;
+            report.removed_main_towns.push(p.player);
+        }
+    }
+    let cond = &mut map.win_loss_cond;
+    // a coordinate outside of the old map means "anywhere", e.g. build the grail in any town
+    let dropped = |c: Option<MapCoord>| c.is_some_and(|c| c.x < old && c.y < old && !inside(&c));
+    if dropped(cond.special_victory_cond.as_ref().and_then(|v| v.coord())) {
+        report.removed_victory_condition = cond.special_victory_cond.take();
+    }
+    if dropped(cond.special_loss_cond.as_ref().and_then(|l| l.coord())) {
+        report.removed_loss_condition = cond.special_loss_cond.take();
+    }
+
+    for (level, terrain) in map.terrains.iter_mut().enumerate() {
+        let fill = TerrainTile {
+            surface_type: if level == 0 {
+                Surface::Water
+            } else {
+                Surface::Rock
+            },
+            ..Default::default()
+        };
+        terrain.resize(n, vec![fill; n]);
+        for column in terrain.iter_mut() {
+            column.resize(n, fill);
+        }
+        if n > old {
+            // the new area and its seam with the old one
+            let from = old.saturating_sub(2);
+            map_tiling::retile(terrain, from..n, 0..n);
+            map_tiling::retile(terrain, 0..n, from..n);
+        }
+    }
+    map.info.map_dimension = n as i32;
+    report
+}
+
+/// Exchange the surface and the underground
+pub fn swap_levels(map: &mut Map) -> Result<(), String> {
+    if map.levels() != 2 {
+        return Err("swap levels: the map has no underground".to_string());
+    }
+    map.terrains.swap(0, 1);
+    let swap = |c: MapCoord| MapCoord { z: 1 - c.z, ..c };
+    let new_positions = map
+        .objects
+        .iter()
+        .map(|o| swap(o.position))
+        .collect::<Vec<_>>();
+    remap_coords(map, &new_positions, swap);
+    Ok(())
+}
+
+// width and height of the area occupied by the object, counted from its anchor
+fn footprint(templates: &[ObjectTemplate], obj: &Object) -> (usize, usize) {
+    let Some(template) = templates.get(obj.obj_templ_id as usize) else {
+        return (1, 1);
+    };
+    let mut width = 1;
+    let mut height = 1;
+    for (dy, row) in template.transit_matrix.iter().enumerate() {
+        for (dx, t) in row.iter().enumerate() {
+            if *t != TileTransitProperty::Transitable {
+                width = width.max(dx + 1);
+                height = height.max(dy + 1);
+            }
+        }
+    }
+    (width, height)
+}
+
+// Move objects to their new positions and remap every coordinate that refers to the map.
+// Objects keep their shape, so a coordinate on an object (e.g. a town entrance) keeps
+// its offset from the moved anchor; the others are transformed like tiles with `tile`.
+fn remap_coords(map: &mut Map, new_positions: &[MapCoord], tile: impl Fn(MapCoord) -> MapCoord) {
+    let size = map.dimension();
+    let levels = map.levels();
+    let remap = |c: MapCoord| -> MapCoord {
+        if c.x >= size || c.y >= size || c.z >= levels {
+            // special value, e.g. "any town"
+            return c;
+        }
+        let Some(&i) = map_passability::objects_at(map, c).first() else {
+            return tile(c);
+        };
+        let (old, new) = (map.objects[i].position, new_positions[i]);
+        MapCoord {
+            x: (new.x + c.x).saturating_sub(old.x),
+            y: (new.y + c.y).saturating_sub(old.y),
+            z: new.z,
+        }
+    };
+    let main_towns = map
+        .players
+        .iter()
+        .map(|p| p.main_town_position.map(remap))
+        .collect::<Vec<_>>();
+    let cond = &map.win_loss_cond;
+    let victory = cond
+        .special_victory_cond
+        .as_ref()
+        .and_then(|v| v.coord())
+        .map(remap);
+    let loss = cond
+        .special_loss_cond
+        .as_ref()
+        .and_then(|l| l.coord())
+        .map(remap);
+
+    for (p, c) in map.players.iter_mut().zip(main_towns) {
+        p.main_town_position = c;
+    }
+    let cond = &mut map.win_loss_cond;
+    if let (Some(c), Some(new)) = (
+        cond.special_victory_cond
+            .as_mut()
+            .and_then(|v| v.coord_mut()),
+        victory,
+    ) {
+        *c = new;
+    }
+    if let (Some(c), Some(new)) = (
+        cond.special_loss_cond.as_mut().and_then(|l| l.coord_mut()),
+        loss,
+    ) {
+        *c = new;
+    }
+    for (o, c) in map.objects.iter_mut().zip(new_positions) {
+        o.position = *c;
+    }
+}
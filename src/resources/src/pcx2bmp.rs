@@ -1,79 +1,86 @@
 use crate::bmp;
+use std::io;
 use std::path::Path;
 
-#[repr(C, packed)]
-#[derive(Debug, Copy, Clone)]
-struct PCX {
-    bitmap_size: u32,
-    width: u32,
-    height: u32,
-}
+const HEADER_SIZE: usize = 12;
+const PALETTE_SIZE: usize = 256 * 3;
 
-pub fn convert_file(src: &Path, dst: &Path) {
-    if !src.exists() {
-        panic!("pcx2bmp: file {src:?} does not exist");
-    }
-    if !src.is_file() {
-        panic!("pcx2bmp: {src:?} is not a file");
-    }
+// PCX layout, all integers little endian:
+//   bitmap_size u32, width u32, height u32
+//   either width * height palette indices followed by the palette (256 RGB triples)
+//   or width * height BGR pixels
 
-    let bytes = match std::fs::read(&src) {
-        Ok(bytes) => bytes,
-        Err(e) => panic!("pcx2bmp: reading file {src:?} failure: {e}"),
-    };
+/// Decoded picture, 3 bytes per pixel in BGR order
+#[derive(Debug, Clone)]
+pub struct Pcx {
+    pub bitmap_size: u32,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
 
-    if std::mem::size_of::<PCX>() > bytes.len() {
-        panic!("pcx2bmp: file {src:?} seems to be smaller than PCX header. Corrupted file?");
+fn error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub fn decode_pcx(bytes: &[u8]) -> io::Result<Pcx> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(error(format!(
+            "PCX header needs {HEADER_SIZE} bytes, got {}",
+            bytes.len()
+        )));
     }
+    let read_u32 =
+        |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    let bitmap_size = read_u32(0);
+    let width = read_u32(4) as usize;
+    let height = read_u32(8) as usize;
 
-    let header: PCX = unsafe { std::ptr::read(bytes.as_ptr() as *const _) };
-    let PCX {
+    let got_pixels = bytes.len() - HEADER_SIZE;
+    let area = width
+        .checked_mul(height)
+        .ok_or_else(|| error(format!("PCX size {width} x {height} is too big")))?;
+    let exp_pixels_8b = area.checked_add(PALETTE_SIZE);
+    let exp_pixels_24b = area.checked_mul(3);
+    let data = &bytes[HEADER_SIZE..];
+    let pixels = if Some(got_pixels) == exp_pixels_8b {
+        let (indices, palette) = data.split_at(area);
+        let mut pixels = Vec::<u8>::with_capacity(3 * area);
+        for i in indices {
+            let (r, g, b) = pix_from_pal(palette, *i);
+            pixels.push(b); // b
+            pixels.push(g); // g
+            pixels.push(r); // r
+        }
+        pixels
+    } else if Some(got_pixels) == exp_pixels_24b {
+        data.to_vec()
+    } else {
+        return Err(error(format!(
+            "PCX {width} x {height}: unexpected pixel data size {got_pixels}"
+        )));
+    };
+    Ok(Pcx {
         bitmap_size,
         width,
         height,
-    } = header;
-    println!("{src:?}: {width} x {height}, bitmap size {bitmap_size}");
-
-    let pcxh_size = std::mem::size_of::<PCX>();
-    let got_pixels = bytes.len() - pcxh_size;
-    let exp_pixels_8b = (width * height + 3 * 256) as usize;
-    let exp_pixels_24b = (3 * width * height) as usize;
-    match got_pixels {
-        p if p == exp_pixels_8b => {
-            let mut pixels = Vec::<u8>::with_capacity((3 * width * height) as usize);
-            for i in bytes[pcxh_size..bytes.len() - 256 * 3].iter() {
-                let (r, g, b) = pix_from_pal(&bytes[bytes.len() - 256 * 3..], *i);
-                pixels.push(b); // b
-                pixels.push(g); // g
-                pixels.push(r); // r
-            }
-
-            let bmp = bmp::BMP::from_mem(width as usize, height as usize, &pixels);
-
-            if let Err(e) = bmp.to_file(dst) {
-                panic!("pcx2bmp: writing to {dst:?} error: {e}");
-            }
-        }
-        p if p == exp_pixels_24b => {
-            let bmp = bmp::BMP::from_mem(width as usize, height as usize, &bytes[pcxh_size..]);
+        pixels,
+    })
+}
 
-            if let Err(e) = bmp.to_file(dst) {
-                panic!("pcx2bmp: writing to {dst:?} error: {e}");
-            }
-        }
-        exp_pixels => {
-            if exp_pixels != got_pixels {
-                panic!("pcx2bmp: unexpected pixel count, got {got_pixels}, expected {exp_pixels}");
-            }
-        }
-    }
+pub fn convert_file(src: &Path, dst: &Path) -> io::Result<()> {
+    let bytes = std::fs::read(src)?;
+    let pcx = decode_pcx(&bytes)?;
+    println!(
+        "{src:?}: {} x {}, bitmap size {}",
+        pcx.width, pcx.height, pcx.bitmap_size
+    );
+    let bmp = bmp::BMP::from_mem(pcx.width, pcx.height, &pcx.pixels);
+    bmp.to_file(dst)
 }
 
+// `palette` holds exactly 256 colours, any index is valid
 fn pix_from_pal(palette: &[u8], index: u8) -> (u8, u8, u8) {
-    if palette.len() != 256 * 3 {
-        let pallen = palette.len();
-        panic!("Palette size is {pallen}");
-    }
     let index = index as usize;
     let index = index * 3;
     (palette[index], palette[index + 1], palette[index + 2])
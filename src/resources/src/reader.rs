@@ -1,33 +1,80 @@
+use std::borrow::Cow;
 use std::io::{self, Cursor, Read};
 extern crate libz_sys as zlib;
 use std::ptr;
 
 const GZIP_MAGIC: u32 = 0x00088B1F;
+// far more than the biggest map, protects against decompression bombs
+const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
 
-pub struct BinaryDataReader {
-    cursor: Cursor<Vec<u8>>,
+// The data is either owned or borrowed, so buffers that already live
+// somewhere (LOD entries, uploads) don't have to be copied.
+pub struct BinaryDataReader<'a> {
+    cursor: Cursor<Cow<'a, [u8]>>,
+    // in strict mode skipping past the end is an error and skipped bytes are checked
+    strict: bool,
+    nonzero_padding: Vec<SkippedPadding>,
 }
 
-impl BinaryDataReader {
+/// Skipped bytes that weren't zero, usually a part of the format the parser doesn't know
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedPadding {
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+impl BinaryDataReader<'static> {
     pub fn new(data: Vec<u8>) -> Self {
         BinaryDataReader {
-            cursor: Cursor::new(data),
+            cursor: Cursor::new(Cow::Owned(data)),
+            strict: false,
+            nonzero_padding: Vec::new(),
         }
     }
 
     pub fn new_possibly_gzip(data: Vec<u8>) -> Result<Self, io::Error> {
-        if data.len() >= 4 {
-            let magic = (data[3] as u32) << 24
-                | (data[2] as u32) << 16
-                | (data[1] as u32) << 8
-                | data[0] as u32;
-            if magic == GZIP_MAGIC {
-                // this is gzip archive
-                return Ok(Self::new(decompress(&data)?));
-            }
+        if is_gzip(&data) {
+            // this is gzip archive
+            return Ok(Self::new(decompress(&data)?));
         }
         Ok(Self::new(data))
     }
+}
+
+impl<'a> BinaryDataReader<'a> {
+    pub fn from_slice(data: &'a [u8]) -> Self {
+        BinaryDataReader {
+            cursor: Cursor::new(Cow::Borrowed(data)),
+            strict: false,
+            nonzero_padding: Vec::new(),
+        }
+    }
+
+    // only compressed data has to be copied
+    pub fn from_slice_possibly_gzip(data: &'a [u8]) -> Result<Self, io::Error> {
+        if is_gzip(data) {
+            return Ok(BinaryDataReader::new(decompress(data)?));
+        }
+        Ok(Self::from_slice(data))
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn position(&self) -> u64 {
+        self.cursor.position()
+    }
+
+    // number of bytes not read yet
+    pub fn remaining(&self) -> usize {
+        (self.cursor.get_ref().len() as u64).saturating_sub(self.cursor.position()) as usize
+    }
+
+    // padding recorded in strict mode
+    pub fn take_nonzero_padding(&mut self) -> Vec<SkippedPadding> {
+        std::mem::take(&mut self.nonzero_padding)
+    }
 
     // Read a single u8
     pub fn read_u8(&mut self) -> io::Result<u8> {
@@ -86,6 +133,16 @@ impl BinaryDataReader {
     // }
 
     pub fn read_byte_array(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        // a broken length must not allocate before the read fails
+        if len > self.remaining() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "reading {len} bytes at offset {} goes past the end of data",
+                    self.cursor.position()
+                ),
+            ));
+        }
         let mut buffer = vec![0u8; len];
         self.cursor.read_exact(&mut buffer)?;
         Ok(buffer)
@@ -106,8 +163,26 @@ impl BinaryDataReader {
         // }
     }
 
-    pub fn skip_n(&mut self, n: usize) {
-        self.cursor.set_position(self.cursor.position() + n as u64)
+    pub fn skip_n(&mut self, n: usize) -> io::Result<()> {
+        let offset = self.cursor.position();
+        if !self.strict {
+            self.cursor.set_position(offset + n as u64);
+            return Ok(());
+        }
+        if n > self.remaining() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "skipping {n} bytes at offset {offset} goes past the end of data ({} bytes)",
+                    self.cursor.get_ref().len()
+                ),
+            ));
+        }
+        let bytes = self.read_byte_array(n)?;
+        if bytes.iter().any(|b| *b != 0) {
+            self.nonzero_padding.push(SkippedPadding { offset, bytes });
+        }
+        Ok(())
     }
 
     #[allow(dead_code)]
@@ -152,6 +227,15 @@ impl BinaryDataReader {
     }
 }
 
+fn is_gzip(data: &[u8]) -> bool {
+    if data.len() < 4 {
+        return false;
+    }
+    let magic =
+        (data[3] as u32) << 24 | (data[2] as u32) << 16 | (data[1] as u32) << 8 | data[0] as u32;
+    magic == GZIP_MAGIC
+}
+
 fn decompress(data: &[u8]) -> Result<Vec<u8>, io::Error> {
     unsafe {
         // Initialize z_stream
@@ -213,8 +297,23 @@ fn decompress(data: &[u8]) -> Result<Vec<u8>, io::Error> {
             let written = available_output - stream.avail_out as usize;
             output.set_len(output.len() + written);
 
+            if output.len() > MAX_DECOMPRESSED_SIZE {
+                zlib::inflateEnd(&mut stream);
+                return Err(io::Error::other(format!(
+                    "Decompressed data exceeds {MAX_DECOMPRESSED_SIZE} bytes"
+                )));
+            }
+
             // Check the return value of `inflate` to see if we're done
             match ret {
+                zlib::Z_OK | zlib::Z_BUF_ERROR if stream.avail_in == 0 && written == 0 => {
+                    // no progress is possible, the input is truncated
+                    zlib::inflateEnd(&mut stream);
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Decompression failed: truncated data",
+                    ));
+                }
                 zlib::Z_OK | zlib::Z_BUF_ERROR => continue, // Keep decompressing
                 zlib::Z_STREAM_END => break,                // Done
                 zlib::Z_DATA_ERROR => {